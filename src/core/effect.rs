@@ -1,4 +1,8 @@
+use ffmpeg_next::frame;
+
 use crate::core::primitives::async_state::Ticket;
+use crate::services::adb::DeviceId;
+use crate::services::screenshot::ScreenshotConfig;
 use crate::services::session::{SessionConfig, SessionHandle};
 
 #[derive(Debug)]
@@ -30,4 +34,11 @@ pub enum Effect {
     StopSession {
         session: SessionHandle,
     },
+
+    /// Ask Shell to save a video frame as an image
+    SaveScreenshot {
+        device_id: DeviceId,
+        config: ScreenshotConfig,
+        frame: frame::Video,
+    },
 }
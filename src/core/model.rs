@@ -107,6 +107,60 @@ impl Model {
                 effects.push(Effect::Render);
             }
 
+            // Clipboard
+            Msg::ClipboardSynced {
+                device_id,
+                direction,
+                size,
+            } => {
+                self.logs.info(format!(
+                    "Clipboard of \"{device_id}\" synced {direction}: {size} bytes"
+                ));
+                effects.push(Effect::Render);
+            }
+
+            // Recording
+            Msg::RecordingSaved { device_id, path } => {
+                self.logs.success(format!(
+                    "Recording of \"{device_id}\" saved to {}",
+                    path.display()
+                ));
+                effects.push(Effect::Render);
+            }
+
+            // Decoding
+            Msg::DecoderFallback { device_id, reason } => {
+                self.logs.error(format!(
+                    "Hardware decoding of \"{device_id}\" failed, switched to software: {reason}"
+                ));
+                effects.push(Effect::Render);
+            }
+
+            // Screenshots
+            Msg::RequestScreenshot {
+                device_id,
+                config,
+                frame,
+            } => {
+                effects.push(Effect::SaveScreenshot {
+                    device_id,
+                    config,
+                    frame,
+                });
+            }
+            Msg::ScreenshotSaved { device_id, result } => {
+                match result {
+                    Ok(path) => self.logs.success(format!(
+                        "Screenshot of \"{device_id}\" saved to {}",
+                        path.display()
+                    )),
+                    Err(err) => self
+                        .logs
+                        .error(format!("Screenshot of \"{device_id}\" failed: {err}")),
+                }
+                effects.push(Effect::Render);
+            }
+
             // Misc
             Msg::ClearLogs => {
                 self.logs.clear();
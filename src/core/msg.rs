@@ -1,9 +1,13 @@
-use std::fmt;
+use std::path::PathBuf;
+use std::{fmt, io};
 
+use ffmpeg_next::frame;
 use scrcpy_launcher::adb::server::DeviceLong;
 
 use crate::core::primitives::async_state::AsyncResult;
 use crate::services::adb::{AdbError, DeviceId};
+use crate::services::control::ClipboardDirection;
+use crate::services::screenshot::ScreenshotConfig;
 use crate::services::session::{SessionConfig, SessionHandle};
 use crate::ui::pages::CurrentPage;
 
@@ -14,6 +18,11 @@ pub enum Msg {
     RequestAdbDevices,
     RequestStartSession(SessionConfig),
     RequestStopSession(DeviceId),
+    RequestScreenshot {
+        device_id: DeviceId,
+        config: ScreenshotConfig,
+        frame: frame::Video,
+    },
     ClearLogs,
 
     // System Events
@@ -25,6 +34,23 @@ pub enum Msg {
         device_id: DeviceId,
         error: Option<anyhow::Error>,
     },
+    ClipboardSynced {
+        device_id: DeviceId,
+        direction: ClipboardDirection,
+        size: usize,
+    },
+    RecordingSaved {
+        device_id: DeviceId,
+        path: PathBuf,
+    },
+    DecoderFallback {
+        device_id: DeviceId,
+        reason: String,
+    },
+    ScreenshotSaved {
+        device_id: DeviceId,
+        result: io::Result<PathBuf>,
+    },
 }
 
 impl fmt::Display for Msg {
@@ -34,10 +60,15 @@ impl fmt::Display for Msg {
             Self::RequestAdbDevices => "RequestAdbDevices",
             Self::RequestStartSession(_) => "RequestStartSession",
             Self::RequestStopSession(_) => "RequestStopSession",
+            Self::RequestScreenshot { .. } => "RequestScreenshot",
             Self::ClearLogs => "ClearLogs",
             Self::AdbDevicesLoaded(_) => "AdbDevicesLoaded",
             Self::SessionStarted { .. } => "SessionStarted",
             Self::SessionStopped { .. } => "SessionStopped",
+            Self::ClipboardSynced { .. } => "ClipboardSynced",
+            Self::RecordingSaved { .. } => "RecordingSaved",
+            Self::DecoderFallback { .. } => "DecoderFallback",
+            Self::ScreenshotSaved { .. } => "ScreenshotSaved",
         };
         s.fmt(f)
     }
@@ -45,6 +45,9 @@ use wgpu::{ExperimentalFeatures, MemoryBudgetThresholds, Trace};
 
 use crate::shell::app::Octane;
 use crate::tracing::init_with_default_level;
+use crate::transcoding::hw::device::HWDevice;
+use crate::transcoding::hw::device_pool::HWDevicePool;
+use crate::ui::renderer::device::{DeviceDescriptor, create_shared_device};
 use crate::utils::fs::clean_path;
 
 const APP_NAME: &str = env!("CARGO_CRATE_NAME");
@@ -56,12 +59,12 @@ const ADB_PATH: &str = "bin/adb.exe";
 #[cfg(not(target_os = "windows"))]
 const ADB_PATH: &str = "bin/adb";
 
-pub const HW_DEVICE_INDEX: usize = 0;
-
 fn main() -> Result<(), anyhow::Error> {
     init_with_default_level(LevelFilter::INFO);
 
     ffmpeg_next::init()?;
+    // Hardware devices are listed in the settings, probe them in the background.
+    std::thread::spawn(HWDevicePool::all);
 
     let mut builder = tokio::runtime::Builder::new_multi_thread();
     builder.enable_all();
@@ -77,7 +80,61 @@ fn main() -> Result<(), anyhow::Error> {
     let app_full_name = &*Box::leak(format!("{APP_NAME} v{APP_VERSION}").into_boxed_str());
     let exit = Arc::new(Notify::new());
 
-    // Application
+    // Renderer device
+    let instance_descriptor = InstanceDescriptor {
+        backends: Backends::VULKAN,
+        flags: InstanceFlags::default(),
+        memory_budget_thresholds: MemoryBudgetThresholds::default(),
+        backend_options: BackendOptions::default(),
+    };
+    let power_preference = PowerPreference::HighPerformance;
+    let device_descriptor: DeviceDescriptor = Arc::new(|adapter| {
+        let base_limits = if adapter.get_info().backend == wgpu::Backend::Gl {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        } else {
+            wgpu::Limits::default()
+        };
+
+        wgpu::DeviceDescriptor {
+            label: Some(app_full_name),
+            // 10-bit frames are uploaded as 16-bit textures.
+            required_features: adapter.features() & wgpu::Features::TEXTURE_FORMAT_16BIT_NORM,
+            required_limits: wgpu::Limits {
+                // When using a depth buffer, we have to be able to create a texture
+                // large enough for the entire surface, and we want to support 4k+ displays.
+                max_texture_dimension_2d: 8192,
+                ..base_limits
+            },
+            experimental_features: ExperimentalFeatures::default(),
+            memory_hints: wgpu::MemoryHints::Performance,
+            trace: Trace::Off,
+        }
+    });
+
+    // The decoder shares the renderer device when possible, decoded frames then stay in VRAM.
+    let wgpu_setup = match runtime.block_on(create_shared_device(
+        &instance_descriptor,
+        power_preference,
+        &device_descriptor,
+    )) {
+        Ok((setup, handles)) => {
+            match HWDevice::from_vulkan(handles) {
+                Ok(device) => HWDevicePool::set_shared(device),
+                Err(err) => warn!("Failed to share the renderer device with the decoder: {err}"),
+            }
+            WgpuSetup::Existing(setup)
+        }
+        Err(err) => {
+            warn!("Failed to create a renderer device shared with the decoder: {err}");
+            WgpuSetup::CreateNew(WgpuSetupCreateNew {
+                instance_descriptor,
+                power_preference,
+                native_adapter_selector: None,
+                device_descriptor,
+            })
+        }
+    };
+
     let native_options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
             .with_inner_size([400.0, 600.0])
@@ -96,37 +153,7 @@ fn main() -> Result<(), anyhow::Error> {
         wgpu_options: WgpuConfiguration {
             present_mode: PresentMode::Mailbox,
             desired_maximum_frame_latency: Some(1),
-            wgpu_setup: WgpuSetup::CreateNew(WgpuSetupCreateNew {
-                instance_descriptor: InstanceDescriptor {
-                    backends: Backends::VULKAN,
-                    flags: InstanceFlags::default(),
-                    memory_budget_thresholds: MemoryBudgetThresholds::default(),
-                    backend_options: BackendOptions::default(),
-                },
-                power_preference: PowerPreference::HighPerformance,
-                native_adapter_selector: None,
-                device_descriptor: Arc::new(|adapter| {
-                    let base_limits = if adapter.get_info().backend == wgpu::Backend::Gl {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    };
-
-                    wgpu::DeviceDescriptor {
-                        label: Some(app_full_name),
-                        required_features: wgpu::Features::empty(),
-                        required_limits: wgpu::Limits {
-                            // When using a depth buffer, we have to be able to create a texture
-                            // large enough for the entire surface, and we want to support 4k+ displays.
-                            max_texture_dimension_2d: 8192,
-                            ..base_limits
-                        },
-                        experimental_features: ExperimentalFeatures::default(),
-                        memory_hints: wgpu::MemoryHints::Performance,
-                        trace: Trace::Off,
-                    }
-                }),
-            }),
+            wgpu_setup,
             ..Default::default()
         },
         persist_window: true,
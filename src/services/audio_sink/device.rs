@@ -0,0 +1,117 @@
+//! Host audio device sink
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+
+use crate::services::audio_sink::jitter::JitterBuffer;
+use crate::services::audio_sink::{AudioFormat, AudioSink, AudioStats};
+
+/// Plays the samples on the default host output device.
+///
+/// The samples are queued in a jitter buffer until the device pulls them.
+pub struct DeviceSink {
+    /// Playing stream, stopped when dropped
+    _stream: cpal::Stream,
+    format: AudioFormat,
+    /// Samples waiting to be played
+    buffer: Arc<parking_lot::Mutex<JitterBuffer>>,
+}
+
+impl DeviceSink {
+    /// Opens the default output device, at its default sample rate and channel count.
+    ///
+    /// # Arguments
+    ///
+    /// - `target_latency`: Jitter buffer duration to maintain.
+    /// - `stats`: Playback statistics.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no output device is available or the stream cannot be started.
+    pub fn new(target_latency: Duration, stats: Arc<AudioStats>) -> io::Result<Self> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No audio output device"))?;
+        let supported = device.default_output_config().map_err(io::Error::other)?;
+        let config = supported.config();
+
+        let format = AudioFormat {
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+        };
+        let buffer = Arc::new(parking_lot::Mutex::new(JitterBuffer::new(
+            format,
+            target_latency,
+            stats,
+        )));
+
+        let stream = match supported.sample_format() {
+            SampleFormat::I8 => Self::build_stream::<i8>(&device, &config, buffer.clone()),
+            SampleFormat::I16 => Self::build_stream::<i16>(&device, &config, buffer.clone()),
+            SampleFormat::I32 => Self::build_stream::<i32>(&device, &config, buffer.clone()),
+            SampleFormat::U8 => Self::build_stream::<u8>(&device, &config, buffer.clone()),
+            SampleFormat::U16 => Self::build_stream::<u16>(&device, &config, buffer.clone()),
+            SampleFormat::U32 => Self::build_stream::<u32>(&device, &config, buffer.clone()),
+            SampleFormat::F32 => Self::build_stream::<f32>(&device, &config, buffer.clone()),
+            SampleFormat::F64 => Self::build_stream::<f64>(&device, &config, buffer.clone()),
+            format => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported audio output sample format: {format}"),
+            )),
+        }?;
+        stream.play().map_err(io::Error::other)?;
+
+        Ok(Self {
+            _stream: stream,
+            format,
+            buffer,
+        })
+    }
+
+    /// Builds an output stream of the device sample type, converting the buffered samples.
+    fn build_stream<T>(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        buffer: Arc<parking_lot::Mutex<JitterBuffer>>,
+    ) -> io::Result<cpal::Stream>
+    where
+        T: SizedSample + FromSample<f32>,
+    {
+        // Reused by the callbacks, sized to the device buffer
+        let mut samples = Vec::new();
+
+        device
+            .build_output_stream(
+                config,
+                move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                    samples.resize(data.len(), 0.0);
+                    buffer.lock().pop(&mut samples);
+                    for (out, &sample) in data.iter_mut().zip(&samples) {
+                        *out = T::from_sample(sample);
+                    }
+                },
+                |err| error!("Audio output: {err}"),
+                None,
+            )
+            .map_err(io::Error::other)
+    }
+}
+
+impl AudioSink for DeviceSink {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        self.buffer.lock().push(samples);
+        Ok(())
+    }
+
+    fn delay(&self) -> Duration {
+        self.buffer.lock().delay()
+    }
+}
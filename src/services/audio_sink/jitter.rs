@@ -0,0 +1,173 @@
+//! Audio jitter buffer
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::services::audio_sink::{AudioFormat, AudioStats};
+
+/// Absorbs the network jitter between the decoded samples and the output device clock.
+///
+/// Playback starts once the target latency is buffered, and restarts the same way after an
+/// underrun. The buffer fill is kept around the target by dropping or repeating single frames,
+/// compensating the drift between the device and host audio clocks.
+#[derive(Debug)]
+pub struct JitterBuffer {
+    format: AudioFormat,
+    /// Interleaved samples waiting to be played
+    queue: VecDeque<f32>,
+    /// Target buffered frames count
+    target: usize,
+    /// Maximum buffered frames count, older frames are dropped beyond it
+    max: usize,
+    /// Whether playback waits for the target to be buffered
+    buffering: bool,
+    /// Smoothed buffered frames count
+    average: f32,
+    stats: Arc<AudioStats>,
+}
+
+impl JitterBuffer {
+    /// Smoothing factor of the buffered frames count, per output callback.
+    const SMOOTHING: f32 = 0.05;
+    /// Allowed deviation from the target, as a fraction of the target.
+    const TOLERANCE: f32 = 0.25;
+
+    /// Creates an empty buffer.
+    ///
+    /// # Arguments
+    ///
+    /// - `format`: Samples format.
+    /// - `target_latency`: Buffered duration to maintain.
+    /// - `stats`: Buffer fill and underruns statistics.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn new(format: AudioFormat, target_latency: Duration, stats: Arc<AudioStats>) -> Self {
+        let target =
+            ((target_latency.as_secs_f64() * f64::from(format.sample_rate)) as usize).max(1);
+
+        Self {
+            format,
+            queue: VecDeque::new(),
+            target,
+            // Leave room for bursts after a network stall.
+            max: target * 4 + format.sample_rate as usize / 10,
+            buffering: true,
+            average: target as f32,
+            stats,
+        }
+    }
+
+    /// Buffered frames count.
+    fn frames(&self) -> usize {
+        self.queue.len() / usize::from(self.format.channels)
+    }
+
+    /// Buffered duration.
+    pub fn delay(&self) -> Duration {
+        let frames = self.frames() as u64;
+        Duration::from_micros(frames * 1_000_000 / u64::from(self.format.sample_rate))
+    }
+
+    /// Queues interleaved samples.
+    pub fn push(&mut self, samples: &[f32]) {
+        self.queue.extend(samples);
+
+        let excess = self.frames().saturating_sub(self.max);
+        self.queue
+            .drain(..excess * usize::from(self.format.channels));
+
+        self.stats.set_fill(self.delay());
+    }
+
+    /// Fills an output buffer with interleaved samples, silence while buffering.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pop(&mut self, out: &mut [f32]) {
+        let channels = usize::from(self.format.channels);
+
+        if self.buffering && self.frames() >= self.target {
+            self.buffering = false;
+            self.average = self.frames() as f32;
+        }
+        if self.buffering {
+            out.fill(0.0);
+            return;
+        }
+
+        self.average += (self.frames() as f32 - self.average) * Self::SMOOTHING;
+        let deviation = self.average - self.target as f32;
+        let tolerance = self.target as f32 * Self::TOLERANCE;
+
+        if deviation > tolerance {
+            // Consumed too slowly: drop a frame.
+            self.queue.drain(..channels.min(self.queue.len()));
+        }
+
+        let mut frames = out.chunks_exact_mut(channels);
+        if deviation < -tolerance
+            && self.frames() > 0
+            && let Some(frame) = frames.next()
+        {
+            // Consumed too fast: repeat a frame.
+            for (sample, queued) in frame.iter_mut().zip(&self.queue) {
+                *sample = *queued;
+            }
+        }
+
+        for frame in frames {
+            if self.queue.len() < channels {
+                if !self.buffering {
+                    self.buffering = true;
+                    self.stats.add_underrun();
+                }
+                frame.fill(0.0);
+                continue;
+            }
+            for sample in frame {
+                *sample = self.queue.pop_front().unwrap_or_default();
+            }
+        }
+
+        self.stats.set_fill(self.delay());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::services::audio_sink::jitter::JitterBuffer;
+    use crate::services::audio_sink::{AudioFormat, AudioStats};
+
+    const FORMAT: AudioFormat = AudioFormat {
+        sample_rate: 1000,
+        channels: 2,
+    };
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_underrun() {
+        let stats = Arc::new(AudioStats::default());
+        let mut buffer = JitterBuffer::new(FORMAT, Duration::from_millis(10), stats.clone());
+        let mut out = [1.0; 8];
+
+        // Silence until the target is buffered
+        buffer.push(&[0.5; 18]);
+        buffer.pop(&mut out);
+        assert_eq!(out, [0.0; 8]);
+
+        buffer.push(&[0.5; 2]);
+        buffer.pop(&mut out);
+        assert_eq!(out, [0.5; 8]);
+        assert_eq!(buffer.delay(), Duration::from_millis(6));
+
+        buffer.pop(&mut out);
+        buffer.pop(&mut out);
+        assert_eq!(out, [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(stats.underruns(), 1);
+    }
+}
@@ -0,0 +1,152 @@
+//! Decoded audio outputs
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+pub use crate::services::audio_sink::device::DeviceSink;
+pub use crate::services::audio_sink::wav::WavSink;
+
+mod device;
+mod jitter;
+mod wav;
+
+/// Destination of decoded audio samples.
+pub trait AudioSink {
+    /// Sample format expected by [`AudioSink::write`].
+    fn format(&self) -> AudioFormat;
+
+    /// Queues interleaved samples for output.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the output is no longer usable.
+    fn write(&mut self, samples: &[f32]) -> io::Result<()>;
+
+    /// Duration of the written samples not played yet.
+    fn delay(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Interleaved `f32` samples format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Channel count
+    pub channels: u16,
+}
+
+/// Audio sink selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioOutput {
+    /// Default host output device, behind a jitter buffer.
+    Device {
+        /// Buffered duration to maintain
+        target_latency: Duration,
+    },
+    /// WAV file, written at the stream format.
+    Wav(PathBuf),
+    /// Discards the samples.
+    Null,
+}
+
+/// Playback statistics, shared with the ui.
+#[derive(Debug)]
+pub struct AudioStats {
+    /// Buffered duration in microseconds
+    fill_us: AtomicU64,
+    /// Buffer underruns count
+    underruns: AtomicU64,
+    /// Stream timestamp of the samples being played, in microseconds. [`i64::MIN`] if unknown.
+    pts_us: AtomicI64,
+}
+
+/// Sink discarding all samples, the stream is still decoded.
+#[derive(Debug)]
+pub struct NullSink;
+
+impl AudioFormat {
+    /// scrcpy audio stream format.
+    pub const STREAM: Self = Self {
+        sample_rate: 48_000,
+        channels: 2,
+    };
+}
+
+impl AudioStats {
+    /// Buffered duration.
+    pub fn fill(&self) -> Duration {
+        Duration::from_micros(self.fill_us.load(Ordering::Relaxed))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn set_fill(&self, fill: Duration) {
+        self.fill_us
+            .store(fill.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Buffer underruns count.
+    pub fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    pub fn add_underrun(&self) {
+        self.underruns.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stream timestamp of the samples being played, in microseconds.
+    pub fn pts(&self) -> Option<i64> {
+        let pts = self.pts_us.load(Ordering::Relaxed);
+        (pts != i64::MIN).then_some(pts)
+    }
+
+    pub fn set_pts(&self, pts: i64) {
+        self.pts_us.store(pts, Ordering::Relaxed);
+    }
+}
+
+impl Default for AudioStats {
+    fn default() -> Self {
+        Self {
+            fill_us: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            pts_us: AtomicI64::new(i64::MIN),
+        }
+    }
+}
+
+impl AudioOutput {
+    /// Opens the selected sink.
+    ///
+    /// Device sinks are bound to the calling thread on some platforms, open them where
+    /// the samples are produced.
+    ///
+    /// # Arguments
+    ///
+    /// - `stats`: Playback statistics, updated by the sink.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the output device or file cannot be opened.
+    pub fn open(&self, stats: Arc<AudioStats>) -> io::Result<Box<dyn AudioSink>> {
+        Ok(match self {
+            Self::Device { target_latency } => Box::new(DeviceSink::new(*target_latency, stats)?),
+            Self::Wav(path) => Box::new(WavSink::create(path, AudioFormat::STREAM)?),
+            Self::Null => Box::new(NullSink),
+        })
+    }
+}
+
+impl AudioSink for NullSink {
+    fn format(&self) -> AudioFormat {
+        AudioFormat::STREAM
+    }
+
+    fn write(&mut self, _samples: &[f32]) -> io::Result<()> {
+        Ok(())
+    }
+}
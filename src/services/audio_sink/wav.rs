@@ -0,0 +1,128 @@
+//! WAV file audio sink
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::services::audio_sink::{AudioFormat, AudioSink};
+
+/// Writes the samples to a 16-bit PCM WAV file.
+///
+/// The header sizes are updated when the sink is dropped.
+#[derive(Debug)]
+pub struct WavSink {
+    writer: BufWriter<File>,
+    format: AudioFormat,
+    /// Written samples data length in bytes
+    len: u32,
+}
+
+impl WavSink {
+    /// RIFF and format chunks, followed by the data chunk header.
+    const HEADER_LENGTH: u32 = 44;
+    const BITS_PER_SAMPLE: u16 = 16;
+
+    /// Creates the file, truncating any existing one.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be created.
+    pub fn create(path: &Path, format: AudioFormat) -> io::Result<Self> {
+        let mut sink = Self {
+            writer: BufWriter::new(File::create(path)?),
+            format,
+            len: 0,
+        };
+        sink.write_header()?;
+
+        Ok(sink)
+    }
+
+    /// Writes the file header at the current position, with the current data length.
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = self.format.channels;
+        let block_align = channels * Self::BITS_PER_SAMPLE / 8;
+        let byte_rate = self.format.sample_rate * u32::from(block_align);
+
+        let w = &mut self.writer;
+        w.write_all(b"RIFF")?;
+        w.write_all(&(Self::HEADER_LENGTH - 8 + self.len).to_le_bytes())?;
+        w.write_all(b"WAVE")?;
+
+        w.write_all(b"fmt ")?;
+        w.write_all(&16_u32.to_le_bytes())?;
+        // PCM
+        w.write_all(&1_u16.to_le_bytes())?;
+        w.write_all(&channels.to_le_bytes())?;
+        w.write_all(&self.format.sample_rate.to_le_bytes())?;
+        w.write_all(&byte_rate.to_le_bytes())?;
+        w.write_all(&block_align.to_le_bytes())?;
+        w.write_all(&Self::BITS_PER_SAMPLE.to_le_bytes())?;
+
+        w.write_all(b"data")?;
+        w.write_all(&self.len.to_le_bytes())?;
+
+        Ok(())
+    }
+
+    /// Flushes the samples and updates the header sizes.
+    fn finalize(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+impl AudioSink for WavSink {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.len = self
+            .len
+            .saturating_add((samples.len() * size_of::<i16>()) as u32);
+
+        Ok(())
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        if let Err(err) = self.finalize() {
+            error!("Failed to finalize the WAV file: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::services::audio_sink::{AudioFormat, AudioSink, WavSink};
+
+    #[test]
+    fn test_header() {
+        let path = std::env::temp_dir().join("octane_test_header.wav");
+
+        let mut sink = WavSink::create(&path, AudioFormat::STREAM).unwrap();
+        sink.write(&[0.0, 1.0, -1.0, 0.5]).unwrap();
+        drop(sink);
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(bytes[4..8], 44_u32.to_le_bytes());
+        assert_eq!(bytes[22..24], 2_u16.to_le_bytes());
+        assert_eq!(bytes[24..28], 48_000_u32.to_le_bytes());
+        assert_eq!(bytes[40..44], 8_u32.to_le_bytes());
+        assert_eq!(bytes[46..48], i16::MAX.to_le_bytes());
+        assert_eq!(bytes[48..50], (-i16::MAX).to_le_bytes());
+    }
+}
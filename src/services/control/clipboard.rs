@@ -0,0 +1,154 @@
+//! Clipboard synchronization between the host and the device
+
+use std::time::Duration;
+use std::{fmt, io, sync};
+
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::services::control::device_message::DeviceMessage;
+use crate::services::control::message::ControlMessage;
+
+pub type ClipboardCallback = Box<dyn Fn(ClipboardDirection, usize) + Send + Sync>;
+
+/// Clipboard synchronization configuration
+pub struct ClipboardConfig {
+    /// Maximum synchronized text length in bytes
+    pub max_size: usize,
+    /// Called on every synchronization, with the text length in bytes
+    pub on_sync_cb: ClipboardCallback,
+}
+
+/// Direction of a clipboard synchronization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardDirection {
+    HostToDevice,
+    DeviceToHost,
+}
+
+/// Keeps the host and device clipboards in sync.
+pub struct ClipboardSync {
+    config: ClipboardConfig,
+    /// Control messages sender channel
+    control_tx: mpsc::Sender<ControlMessage>,
+}
+
+impl ClipboardSync {
+    /// Host clipboard polling interval, the host does not notify changes.
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Creates a new clipboard synchronizer
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: Synchronization configuration.
+    /// - `control_tx`: Control messages sender channel.
+    pub const fn new(config: ClipboardConfig, control_tx: mpsc::Sender<ControlMessage>) -> Self {
+        Self { config, control_tx }
+    }
+
+    /// Spawns background synchronization task.
+    ///
+    /// Applies the device clipboard changes to the host clipboard, and polls the host clipboard
+    /// for changes to send to the device. Ends when the device messages source is closed.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `messages`: Incoming device messages source.
+    pub fn start(
+        self,
+        set: &mut JoinSet<io::Result<()>>,
+        messages: sync::mpsc::Receiver<DeviceMessage>,
+    ) {
+        // The host clipboard API is blocking.
+        set.spawn_blocking(move || {
+            let mut clipboard = arboard::Clipboard::new().map_err(io::Error::other)?;
+
+            // Last synchronized text, to not send back a text to where it came from.
+            let mut last = clipboard.get_text().ok();
+            let mut sequence = 0;
+
+            loop {
+                match messages.recv_timeout(Self::POLL_INTERVAL) {
+                    Ok(DeviceMessage::Clipboard { text }) => {
+                        if last.as_ref() == Some(&text) || !self.fits(&text) {
+                            continue;
+                        }
+
+                        if let Err(err) = clipboard.set_text(&text) {
+                            warn!("Clipboard: cannot set the host clipboard: {err}");
+                            continue;
+                        }
+
+                        (self.config.on_sync_cb)(ClipboardDirection::DeviceToHost, text.len());
+                        last = Some(text);
+                    }
+                    Ok(DeviceMessage::AckClipboard { sequence }) => {
+                        debug!("Clipboard: device acknowledged {sequence}");
+                    }
+                    Ok(DeviceMessage::UhidOutput { .. }) => {}
+                    Err(sync::mpsc::RecvTimeoutError::Timeout) => {
+                        // Non text content is not synchronized.
+                        let Ok(text) = clipboard.get_text() else {
+                            continue;
+                        };
+                        if last.as_ref() == Some(&text) || !self.fits(&text) {
+                            continue;
+                        }
+
+                        sequence += 1;
+                        let len = text.len();
+                        let msg = ControlMessage::SetClipboard {
+                            sequence,
+                            text: text.clone(),
+                            paste: false,
+                        };
+                        if self.control_tx.blocking_send(msg).is_err() {
+                            break;
+                        }
+
+                        (self.config.on_sync_cb)(ClipboardDirection::HostToDevice, len);
+                        last = Some(text);
+                    }
+                    Err(sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    /// Whether a text is small enough to be synchronized.
+    fn fits(&self, text: &str) -> bool {
+        let max_size = self
+            .config
+            .max_size
+            .min(ControlMessage::CLIPBOARD_MAX_LENGTH);
+
+        if text.len() > max_size {
+            debug!("Clipboard: {} bytes text not synchronized", text.len());
+            return false;
+        }
+
+        true
+    }
+}
+
+impl fmt::Display for ClipboardDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HostToDevice => "host to device".fmt(f),
+            Self::DeviceToHost => "device to host".fmt(f),
+        }
+    }
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl fmt::Debug for ClipboardConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClipboardConfig")
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
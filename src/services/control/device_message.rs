@@ -0,0 +1,96 @@
+//! Device to host messages
+//!
+//! Messages are deserialized big-endian, following the scrcpy `device_msg.c` reference client.
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Message received from the device over the control socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceMessage {
+    /// Device clipboard content, sent when it changes or on request.
+    Clipboard { text: String },
+    /// Acknowledges a set clipboard request.
+    AckClipboard { sequence: u64 },
+    /// HID output report of a virtual device.
+    UhidOutput { id: u16, data: Vec<u8> },
+}
+
+impl DeviceMessage {
+    /// Maximum serialized message length in bytes.
+    pub const MAX_LENGTH: usize = 1 << 18;
+
+    /// Reads the next message.
+    ///
+    /// # Errors
+    ///
+    /// Fails on socket errors, unknown message types and oversized messages.
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let kind = reader.read_u8().await?;
+
+        match kind {
+            0 => {
+                let len = reader.read_u32().await? as usize;
+                if len > Self::MAX_LENGTH {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Device clipboard too long: {len} bytes"),
+                    ));
+                }
+
+                let mut buf = vec![0; len];
+                reader.read_exact(&mut buf).await?;
+                let text = String::from_utf8(buf)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+                Ok(Self::Clipboard { text })
+            }
+            1 => {
+                let sequence = reader.read_u64().await?;
+                Ok(Self::AckClipboard { sequence })
+            }
+            2 => {
+                let id = reader.read_u16().await?;
+                let len = reader.read_u16().await?;
+
+                let mut data = vec![0; len.into()];
+                reader.read_exact(&mut data).await?;
+
+                Ok(Self::UhidOutput { id, data })
+            }
+            kind => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown device message type: {kind}"),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::services::control::device_message::DeviceMessage;
+
+    #[tokio::test]
+    async fn test_read() {
+        let mut bytes: &[u8] = &[
+            0x00, // CLIPBOARD
+            0x00, 0x00, 0x00, 0x03, b'a', b'b', b'c', // "abc"
+            0x01, // ACK_CLIPBOARD
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x02, // sequence
+            0x07, // unknown
+        ];
+
+        assert_eq!(
+            DeviceMessage::read(&mut bytes).await.unwrap(),
+            DeviceMessage::Clipboard {
+                text: "abc".to_owned()
+            }
+        );
+        assert_eq!(
+            DeviceMessage::read(&mut bytes).await.unwrap(),
+            DeviceMessage::AckClipboard { sequence: 0x102 }
+        );
+        assert!(DeviceMessage::read(&mut bytes).await.is_err());
+    }
+}
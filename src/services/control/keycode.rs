@@ -0,0 +1,124 @@
+//! Android key codes and meta state
+//!
+//! Values from the Android `KeyEvent` reference.
+
+use std::ops::BitOr;
+
+/// Android `KeyEvent` action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyAction {
+    Down = 0,
+    Up = 1,
+}
+
+/// Android `KEYCODE_*` key code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Keycode {
+    Home = 3,
+    Back = 4,
+    Num0 = 7,
+    Num1 = 8,
+    Num2 = 9,
+    Num3 = 10,
+    Num4 = 11,
+    Num5 = 12,
+    Num6 = 13,
+    Num7 = 14,
+    Num8 = 15,
+    Num9 = 16,
+    DpadUp = 19,
+    DpadDown = 20,
+    DpadLeft = 21,
+    DpadRight = 22,
+    VolumeUp = 24,
+    VolumeDown = 25,
+    Power = 26,
+    A = 29,
+    B = 30,
+    C = 31,
+    D = 32,
+    E = 33,
+    F = 34,
+    G = 35,
+    H = 36,
+    I = 37,
+    J = 38,
+    K = 39,
+    L = 40,
+    M = 41,
+    N = 42,
+    O = 43,
+    P = 44,
+    Q = 45,
+    R = 46,
+    S = 47,
+    T = 48,
+    U = 49,
+    V = 50,
+    W = 51,
+    X = 52,
+    Y = 53,
+    Z = 54,
+    Comma = 55,
+    Period = 56,
+    Tab = 61,
+    Space = 62,
+    Enter = 66,
+    Del = 67,
+    Grave = 68,
+    Minus = 69,
+    Equals = 70,
+    LeftBracket = 71,
+    RightBracket = 72,
+    Backslash = 73,
+    Semicolon = 74,
+    Apostrophe = 75,
+    Slash = 76,
+    Plus = 81,
+    Menu = 82,
+    PageUp = 92,
+    PageDown = 93,
+    Escape = 111,
+    ForwardDel = 112,
+    MoveHome = 122,
+    MoveEnd = 123,
+    Insert = 124,
+    F1 = 131,
+    F2 = 132,
+    F3 = 133,
+    F4 = 134,
+    F5 = 135,
+    F6 = 136,
+    F7 = 137,
+    F8 = 138,
+    F9 = 139,
+    F10 = 140,
+    F11 = 141,
+    F12 = 142,
+    AppSwitch = 187,
+    Cut = 277,
+    Copy = 278,
+    Paste = 279,
+}
+
+/// Android `KeyEvent` meta state flags
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MetaState(pub u32);
+
+impl MetaState {
+    pub const NONE: Self = Self(0);
+    pub const SHIFT: Self = Self(0x01 | 0x40);
+    pub const ALT: Self = Self(0x02 | 0x10);
+    pub const CTRL: Self = Self(0x1000 | 0x2000);
+    pub const META: Self = Self(0x1_0000 | 0x2_0000);
+}
+
+impl BitOr for MetaState {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
@@ -0,0 +1,457 @@
+//! Host to device control messages
+//!
+//! Messages are serialized big-endian, following the scrcpy `control_msg.c` reference client.
+
+use crate::services::control::keycode::{KeyAction, Keycode, MetaState};
+
+/// Android `MotionEvent` action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TouchAction {
+    Down = 0,
+    Up = 1,
+    Move = 2,
+}
+
+/// Identifier of an injected pointer.
+///
+/// The device tracks each identifier independently, allowing multi-touch gestures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PointerId(pub u64);
+
+impl PointerId {
+    /// Mouse pointer (`-1`), injected as a mouse event.
+    pub const MOUSE: Self = Self(u64::MAX);
+    /// Generic finger (`-2`), injected as a touch screen event.
+    pub const GENERIC_FINGER: Self = Self(u64::MAX - 1);
+    /// Secondary finger (`-3`), used by the emulated multi-touch gestures.
+    pub const VIRTUAL_FINGER: Self = Self(u64::MAX - 2);
+}
+
+/// Android `MotionEvent` buttons state
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MotionButtons(pub u32);
+
+impl MotionButtons {
+    pub const NONE: Self = Self(0);
+    pub const PRIMARY: Self = Self(1 << 0);
+    pub const SECONDARY: Self = Self(1 << 1);
+    pub const TERTIARY: Self = Self(1 << 2);
+    pub const BACK: Self = Self(1 << 3);
+    pub const FORWARD: Self = Self(1 << 4);
+}
+
+/// Point on the device screen, with the screen size it was computed for.
+///
+/// The device ignores events whose screen size does not match the current video size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Device pixel coordinates (x, y)
+    pub point: (i32, i32),
+    /// Device screen size in pixels (width, height)
+    pub screen_size: (u16, u16),
+}
+
+/// Key the device presses before sending its clipboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum CopyKey {
+    None = 0,
+    Copy = 1,
+    Cut = 2,
+}
+
+/// Message sent to the device over the control socket.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlMessage {
+    /// Injects a key event.
+    InjectKeycode {
+        action: KeyAction,
+        keycode: Keycode,
+        /// Number of key repeats, `0` for the initial press
+        repeat: u32,
+        meta_state: MetaState,
+    },
+    /// Injects text, as typed by an input method.
+    ///
+    /// At most [`ControlMessage::TEXT_MAX_LENGTH`] bytes long.
+    InjectText {
+        text: String,
+    },
+    /// Injects a touch or mouse event.
+    InjectTouch {
+        action: TouchAction,
+        pointer_id: PointerId,
+        position: Position,
+        /// Normalized pressure in `[0, 1]`
+        pressure: f32,
+        /// Button responsible for the action, for mouse pointers
+        action_button: MotionButtons,
+        /// Buttons currently pressed, for mouse pointers
+        buttons: MotionButtons,
+    },
+    /// Injects a scroll event.
+    InjectScroll {
+        position: Position,
+        /// Horizontal scroll in notches, positive to the right, clamped to `[-16, 16]`
+        hscroll: f32,
+        /// Vertical scroll in notches, positive upwards, clamped to `[-16, 16]`
+        vscroll: f32,
+        buttons: MotionButtons,
+    },
+    /// Presses Back, or turns the screen on if it is off.
+    BackOrScreenOn {
+        action: KeyAction,
+    },
+    ExpandNotificationPanel,
+    ExpandSettingsPanel,
+    CollapsePanels,
+    /// Requests the device clipboard, after pressing `copy_key`.
+    GetClipboard {
+        copy_key: CopyKey,
+    },
+    /// Sets the device clipboard, and pastes it if `paste` is set.
+    ///
+    /// At most [`ControlMessage::CLIPBOARD_MAX_LENGTH`] bytes long.
+    SetClipboard {
+        /// Acknowledgement sequence number, `0` for none
+        sequence: u64,
+        text: String,
+        paste: bool,
+    },
+    /// Turns the device screen on or off, without interrupting the mirroring.
+    SetDisplayPower {
+        on: bool,
+    },
+    RotateDevice,
+}
+
+impl ControlMessage {
+    /// Maximum injected text length in bytes.
+    pub const TEXT_MAX_LENGTH: usize = 300;
+    /// Maximum clipboard text length in bytes, fitting the device message buffer.
+    pub const CLIPBOARD_MAX_LENGTH: usize = (1 << 18) - 14;
+
+    /// Message type identifier on the wire.
+    const fn kind(&self) -> u8 {
+        match self {
+            Self::InjectKeycode { .. } => 0,
+            Self::InjectText { .. } => 1,
+            Self::InjectTouch { .. } => 2,
+            Self::InjectScroll { .. } => 3,
+            Self::BackOrScreenOn { .. } => 4,
+            Self::ExpandNotificationPanel => 5,
+            Self::ExpandSettingsPanel => 6,
+            Self::CollapsePanels => 7,
+            Self::GetClipboard { .. } => 8,
+            Self::SetClipboard { .. } => 9,
+            Self::SetDisplayPower { .. } => 10,
+            Self::RotateDevice => 11,
+        }
+    }
+
+    /// Key event.
+    pub const fn key(
+        action: KeyAction,
+        keycode: Keycode,
+        repeat: u32,
+        meta_state: MetaState,
+    ) -> Self {
+        Self::InjectKeycode {
+            action,
+            keycode,
+            repeat,
+            meta_state,
+        }
+    }
+
+    /// Key press and release events.
+    pub const fn key_tap(keycode: Keycode) -> [Self; 2] {
+        [
+            Self::key(KeyAction::Down, keycode, 0, MetaState::NONE),
+            Self::key(KeyAction::Up, keycode, 0, MetaState::NONE),
+        ]
+    }
+
+    /// Text events, split on char boundaries to fit the maximum text length.
+    pub fn texts(text: &str) -> impl Iterator<Item = Self> + '_ {
+        let mut rest = text;
+
+        std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+
+            let mut end = rest.len().min(Self::TEXT_MAX_LENGTH);
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            let (chunk, tail) = rest.split_at(end);
+            rest = tail;
+
+            Some(Self::InjectText {
+                text: chunk.to_owned(),
+            })
+        })
+    }
+
+    /// Finger touch event, without mouse buttons.
+    pub const fn touch(
+        action: TouchAction,
+        pointer_id: PointerId,
+        position: Position,
+        pressure: f32,
+    ) -> Self {
+        Self::InjectTouch {
+            action,
+            pointer_id,
+            position,
+            pressure,
+            action_button: MotionButtons::NONE,
+            buttons: MotionButtons::NONE,
+        }
+    }
+
+    /// Serializes the message into `buf`.
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.push(self.kind());
+
+        match self {
+            Self::InjectKeycode {
+                action,
+                keycode,
+                repeat,
+                meta_state,
+            } => {
+                buf.push(*action as u8);
+                buf.extend_from_slice(&(*keycode as u32).to_be_bytes());
+                buf.extend_from_slice(&repeat.to_be_bytes());
+                buf.extend_from_slice(&meta_state.0.to_be_bytes());
+            }
+            Self::InjectText { text } => {
+                write_string(buf, text, Self::TEXT_MAX_LENGTH);
+            }
+            Self::InjectTouch {
+                action,
+                pointer_id,
+                position,
+                pressure,
+                action_button,
+                buttons,
+            } => {
+                buf.push(*action as u8);
+                buf.extend_from_slice(&pointer_id.0.to_be_bytes());
+                write_position(buf, position);
+                buf.extend_from_slice(&float_to_u16fp(*pressure).to_be_bytes());
+                buf.extend_from_slice(&action_button.0.to_be_bytes());
+                buf.extend_from_slice(&buttons.0.to_be_bytes());
+            }
+            Self::InjectScroll {
+                position,
+                hscroll,
+                vscroll,
+                buttons,
+            } => {
+                write_position(buf, position);
+                buf.extend_from_slice(&float_to_i16fp(*hscroll / 16.0).to_be_bytes());
+                buf.extend_from_slice(&float_to_i16fp(*vscroll / 16.0).to_be_bytes());
+                buf.extend_from_slice(&buttons.0.to_be_bytes());
+            }
+            Self::BackOrScreenOn { action } => {
+                buf.push(*action as u8);
+            }
+            Self::ExpandNotificationPanel
+            | Self::ExpandSettingsPanel
+            | Self::CollapsePanels
+            | Self::RotateDevice => {}
+            Self::SetDisplayPower { on } => {
+                buf.push(u8::from(*on));
+            }
+            Self::GetClipboard { copy_key } => {
+                buf.push(*copy_key as u8);
+            }
+            Self::SetClipboard {
+                sequence,
+                text,
+                paste,
+            } => {
+                buf.extend_from_slice(&sequence.to_be_bytes());
+                buf.push(u8::from(*paste));
+                write_string(buf, text, Self::CLIPBOARD_MAX_LENGTH);
+            }
+        }
+    }
+}
+
+/// Writes a screen position: `x: i32, y: i32, width: u16, height: u16`.
+fn write_position(buf: &mut Vec<u8>, position: &Position) {
+    buf.extend_from_slice(&position.point.0.to_be_bytes());
+    buf.extend_from_slice(&position.point.1.to_be_bytes());
+    buf.extend_from_slice(&position.screen_size.0.to_be_bytes());
+    buf.extend_from_slice(&position.screen_size.1.to_be_bytes());
+}
+
+/// Writes a length prefixed UTF-8 string, truncated to `max_length` bytes on a char boundary.
+#[allow(clippy::cast_possible_truncation)]
+fn write_string(buf: &mut Vec<u8>, text: &str, max_length: usize) {
+    let mut end = text.len().min(max_length);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    buf.extend_from_slice(&(end as u32).to_be_bytes());
+    buf.extend_from_slice(&text.as_bytes()[..end]);
+}
+
+/// Converts a float in `[0, 1]` to a 16-bit unsigned fixed-point value.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn float_to_u16fp(value: f32) -> u16 {
+    let value = (value.clamp(0.0, 1.0) * 65536.0) as u32;
+    value.min(0xffff) as u16
+}
+
+/// Converts a float in `[-1, 1]` to a 16-bit signed fixed-point value.
+#[allow(clippy::cast_possible_truncation)]
+fn float_to_i16fp(value: f32) -> i16 {
+    let value = (value.clamp(-1.0, 1.0) * 32768.0) as i32;
+    value.min(0x7fff) as i16
+}
+
+#[cfg(test)]
+mod test {
+    use crate::services::control::keycode::{KeyAction, Keycode, MetaState};
+    use crate::services::control::message::{
+        ControlMessage, CopyKey, MotionButtons, PointerId, Position, TouchAction,
+    };
+
+    #[test]
+    fn test_serialize_keycode() {
+        let msg = ControlMessage::key(KeyAction::Up, Keycode::Enter, 5, MetaState::SHIFT);
+
+        let mut buf = Vec::new();
+        msg.serialize(&mut buf);
+
+        assert_eq!(
+            buf,
+            [
+                0x00, // INJECT_KEYCODE
+                0x01, // UP
+                0x00, 0x00, 0x00, 0x42, // ENTER
+                0x00, 0x00, 0x00, 0x05, // repeat
+                0x00, 0x00, 0x00, 0x41, // META_SHIFT_ON | META_SHIFT_LEFT_ON
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_text() {
+        let text = "é".repeat(200);
+        let chunks = ControlMessage::texts(&text).collect::<Vec<_>>();
+
+        assert_eq!(chunks.len(), 2);
+        for chunk in chunks {
+            let ControlMessage::InjectText { text } = &chunk else {
+                panic!("Expected a text message");
+            };
+            assert!(text.len() <= ControlMessage::TEXT_MAX_LENGTH);
+
+            let mut buf = Vec::new();
+            chunk.serialize(&mut buf);
+            assert_eq!(buf.len(), 1 + 4 + text.len());
+        }
+    }
+
+    #[test]
+    fn test_serialize_touch() {
+        let msg = ControlMessage::InjectTouch {
+            action: TouchAction::Down,
+            pointer_id: PointerId(0x1234_5678_8765_4321),
+            position: Position {
+                point: (100, 200),
+                screen_size: (1080, 1920),
+            },
+            pressure: 1.0,
+            action_button: MotionButtons::PRIMARY,
+            buttons: MotionButtons::PRIMARY,
+        };
+
+        let mut buf = Vec::new();
+        msg.serialize(&mut buf);
+
+        assert_eq!(
+            buf,
+            [
+                0x02, // INJECT_TOUCH_EVENT
+                0x00, // DOWN
+                0x12, 0x34, 0x56, 0x78, 0x87, 0x65, 0x43, 0x21, // pointer id
+                0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00, 0xc8, // 100 200
+                0x04, 0x38, 0x07, 0x80, // 1080 1920
+                0xff, 0xff, // pressure
+                0x00, 0x00, 0x00, 0x01, // action button
+                0x00, 0x00, 0x00, 0x01, // buttons
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_scroll() {
+        let msg = ControlMessage::InjectScroll {
+            position: Position {
+                point: (260, 1026),
+                screen_size: (1080, 1920),
+            },
+            hscroll: 16.0,
+            vscroll: -32.0,
+            buttons: MotionButtons::PRIMARY,
+        };
+
+        let mut buf = Vec::new();
+        msg.serialize(&mut buf);
+
+        assert_eq!(
+            buf,
+            [
+                0x03, // INJECT_SCROLL_EVENT
+                0x00, 0x00, 0x01, 0x04, 0x00, 0x00, 0x04, 0x02, // 260 1026
+                0x04, 0x38, 0x07, 0x80, // 1080 1920
+                0x7f, 0xff, // 1
+                0x80, 0x00, // -1
+                0x00, 0x00, 0x00, 0x01, // buttons
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serialize_clipboard() {
+        let mut buf = Vec::new();
+        ControlMessage::GetClipboard {
+            copy_key: CopyKey::Cut,
+        }
+        .serialize(&mut buf);
+        ControlMessage::SetClipboard {
+            sequence: 0x0102_0304_0506_0708,
+            text: "hi".to_owned(),
+            paste: true,
+        }
+        .serialize(&mut buf);
+
+        assert_eq!(
+            buf,
+            [
+                0x08, // GET_CLIPBOARD
+                0x02, // CUT
+                0x09, // SET_CLIPBOARD
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // sequence
+                0x01, // paste
+                0x00, 0x00, 0x00, 0x02, b'h', b'i', // "hi"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_ids() {
+        assert_eq!(PointerId::MOUSE.0.to_be_bytes(), [0xff; 8]);
+        assert_eq!(PointerId::GENERIC_FINGER.0, 0xffff_ffff_ffff_fffe);
+    }
+}
@@ -0,0 +1,17 @@
+//! Device control stream, implementing the scrcpy control protocol
+
+pub use crate::services::control::clipboard::{
+    ClipboardCallback, ClipboardConfig, ClipboardDirection, ClipboardSync,
+};
+pub use crate::services::control::device_message::DeviceMessage;
+pub use crate::services::control::keycode::{KeyAction, Keycode, MetaState};
+pub use crate::services::control::message::{
+    ControlMessage, CopyKey, MotionButtons, PointerId, Position, TouchAction,
+};
+pub use crate::services::control::stream::{ControlStream, DeviceStream};
+
+mod clipboard;
+mod device_message;
+mod keycode;
+mod message;
+mod stream;
@@ -0,0 +1,97 @@
+use std::{io, sync};
+
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::services::control::device_message::DeviceMessage;
+use crate::services::control::message::ControlMessage;
+
+/// Control socket writer
+pub struct ControlStream {
+    /// Control socket write half
+    writer: OwnedWriteHalf,
+}
+
+impl ControlStream {
+    /// Serialization buffer initial capacity, fits any input event.
+    const BUFFER: usize = 64;
+
+    /// Creates a new control socket writer
+    ///
+    /// # Arguments
+    ///
+    /// - `writer`: Control socket write half.
+    pub const fn new(writer: OwnedWriteHalf) -> Self {
+        Self { writer }
+    }
+
+    /// Spawns background writing task.
+    ///
+    /// Serializes each incoming control message onto the control socket, in order.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `messages`: Incoming control messages source.
+    pub fn start(
+        mut self,
+        set: &mut JoinSet<io::Result<()>>,
+        mut messages: mpsc::Receiver<ControlMessage>,
+    ) {
+        set.spawn(async move {
+            let mut buffer = Vec::with_capacity(Self::BUFFER);
+
+            while let Some(msg) = messages.recv().await {
+                buffer.clear();
+                msg.serialize(&mut buffer);
+
+                self.writer.write_all(&buffer).await?;
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Control socket reader
+pub struct DeviceStream {
+    /// Control socket read half
+    reader: OwnedReadHalf,
+}
+
+impl DeviceStream {
+    /// Creates a new control socket reader
+    ///
+    /// # Arguments
+    ///
+    /// - `reader`: Control socket read half.
+    pub const fn new(reader: OwnedReadHalf) -> Self {
+        Self { reader }
+    }
+
+    /// Spawns background reading task.
+    ///
+    /// Deserializes each device message, in order. The socket is drained even if
+    /// nothing listens to the messages anymore, so the device never blocks on it.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `messages`: Outgoing device messages sink.
+    pub fn start(
+        self,
+        set: &mut JoinSet<io::Result<()>>,
+        messages: sync::mpsc::Sender<DeviceMessage>,
+    ) {
+        set.spawn(async move {
+            let mut reader = BufReader::new(self.reader);
+
+            loop {
+                let msg = DeviceMessage::read(&mut reader).await?;
+                let _ = messages.send(msg);
+            }
+        });
+    }
+}
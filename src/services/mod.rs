@@ -2,6 +2,11 @@
 //! used by the Shell
 
 pub mod adb;
+pub mod audio_sink;
+pub mod control;
+pub mod playback;
+pub mod recorder;
+pub mod screenshot;
 pub mod session;
 mod utils;
 // pub mod session_manager;
@@ -0,0 +1,264 @@
+//! Recorded video playback, standing in for a device video stream
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{io, thread};
+
+use ffmpeg_next::codec;
+use scrcpy_launcher::video::{FrameMetadata, PacketType};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::services::recorder::CaptureReader;
+use crate::services::stream_decoder::StreamPacket;
+use crate::transcoding::demuxer::Demuxer;
+use crate::transcoding::muxer::StreamParameters;
+
+/// Packets delivery pace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// At the recorded frame rate
+    RealTime,
+    /// As fast as the decoder consumes them
+    Unpaced,
+}
+
+/// Playback configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaybackConfig {
+    /// Recorded Annex-B stream, MP4 or Matroska file
+    pub path: PathBuf,
+    pub pacing: Pacing,
+    /// Whether the playback restarts at the end of the file
+    pub looping: bool,
+}
+
+/// Stops the playback reader when dropped.
+///
+/// The reader runs on a blocking thread that aborting its task does not stop. It checks the
+/// flag on every packet and while pacing them.
+#[derive(Debug)]
+pub struct PlaybackGuard {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for PlaybackGuard {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Feeds the packets of a recorded video, as a device video stream would.
+pub struct FileSource {
+    config: PlaybackConfig,
+    /// Stream description, and packets of the files other than captures
+    demuxer: Demuxer,
+    /// Packets of a raw stream capture. `None` for other files.
+    capture: Option<CaptureReader>,
+}
+
+impl FileSource {
+    /// Longest sleep between two checks of the stop flag
+    const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+    /// Opens a recorded video.
+    ///
+    /// Raw stream captures are read packet by packet with the timestamps of their sidecar file,
+    /// other files are demuxed.
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: Playback configuration.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened or holds no video stream.
+    pub fn open(config: PlaybackConfig) -> io::Result<Self> {
+        let demuxer = Demuxer::open(&config.path).map_err(io::Error::other)?;
+        let capture = CaptureReader::open(&config.path)?;
+
+        Ok(Self {
+            config,
+            demuxer,
+            capture,
+        })
+    }
+
+    /// Video codec ID
+    pub const fn codec(&self) -> codec::Id {
+        self.demuxer.parameters().codec()
+    }
+
+    /// Video frames size in pixels (width, height)
+    pub const fn size(&self) -> (i32, i32) {
+        match self.demuxer.parameters() {
+            StreamParameters::Video { width, height, .. } => (*width, *height),
+            StreamParameters::Audio { .. } => (0, 0),
+        }
+    }
+
+    /// Spawns background reading task.
+    ///
+    /// Sends the codec header as a config packet, then the video packets with their
+    /// timestamps in microseconds. Ends at the end of the file unless looping, when the
+    /// stream receiver is closed, or when the returned guard is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `stream`: Outgoing video packets, as received from a device.
+    #[must_use]
+    pub fn start(
+        mut self,
+        set: &mut JoinSet<io::Result<()>>,
+        stream: mpsc::Sender<StreamPacket>,
+    ) -> PlaybackGuard {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let guard = PlaybackGuard {
+            stopped: stopped.clone(),
+        };
+
+        // Demuxing reads the file synchronously.
+        set.spawn_blocking(move || {
+            // Captures hold their own config packets.
+            if self.capture.is_none()
+                && let StreamParameters::Video {
+                    extradata: Some(extradata),
+                    ..
+                } = self.demuxer.parameters()
+            {
+                let metadata = new_metadata(PacketType::Config, extradata.len());
+                if stream
+                    .blocking_send((metadata, extradata.clone(), Instant::now()))
+                    .is_err()
+                {
+                    return Ok(());
+                }
+            }
+
+            // Playback start time and timestamp
+            let mut clock = None;
+            // Timestamps shift of the current loop, and end of the previous loops
+            let (mut offset, mut end) = (0, 0);
+            // Timestamp of the previous packet of the loop
+            let mut previous = None;
+
+            while !stopped.load(Ordering::Relaxed) {
+                let (kind, data) = match self.read()? {
+                    Some(packet) => packet,
+                    None if self.config.looping => {
+                        self.rewind()?;
+                        previous = None;
+                        continue;
+                    }
+                    None => {
+                        info!("Playback of {} ended", self.config.path.display());
+                        break;
+                    }
+                };
+
+                let kind = match kind {
+                    PacketType::Config => PacketType::Config,
+                    PacketType::KeyFrame(pts) | PacketType::Regular(pts) => {
+                        // Timestamps keep increasing across loops.
+                        if previous.is_none() {
+                            offset = end - pts;
+                        }
+                        let interval = previous
+                            .map(|previous| pts - previous)
+                            .filter(|&interval| interval > 0)
+                            .unwrap_or_else(|| self.demuxer.frame_duration());
+                        previous = Some(pts);
+                        let pts = pts + offset;
+                        end = end.max(pts + interval);
+
+                        if self.config.pacing == Pacing::RealTime {
+                            let (start, start_pts) = *clock.get_or_insert((Instant::now(), pts));
+                            let elapsed = u64::try_from(pts - start_pts).unwrap_or_default();
+                            let due = start + Duration::from_micros(elapsed);
+                            if !Self::sleep_until(due, &stopped) {
+                                break;
+                            }
+                        }
+
+                        if matches!(kind, PacketType::KeyFrame(_)) {
+                            PacketType::KeyFrame(pts)
+                        } else {
+                            PacketType::Regular(pts)
+                        }
+                    }
+                };
+
+                let metadata = new_metadata(kind, data.len());
+                if stream
+                    .blocking_send((metadata, data, Instant::now()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+
+            Ok(())
+        });
+
+        guard
+    }
+
+    /// Reads the next packet, from the capture or the demuxer. `None` at the end of the file.
+    fn read(&mut self) -> io::Result<Option<(PacketType, Vec<u8>)>> {
+        if let Some(capture) = &mut self.capture {
+            return capture.read();
+        }
+
+        loop {
+            let Some(packet) = self.demuxer.read().map_err(io::Error::other)? else {
+                return Ok(None);
+            };
+            let (Some(pts), Some(data)) = (packet.pts(), packet.data()) else {
+                continue;
+            };
+
+            let kind = if packet.is_key() {
+                PacketType::KeyFrame(pts)
+            } else {
+                PacketType::Regular(pts)
+            };
+            return Ok(Some((kind, data.to_vec())));
+        }
+    }
+
+    /// Seeks back to the start of the file.
+    fn rewind(&mut self) -> io::Result<()> {
+        match &mut self.capture {
+            Some(capture) => capture.rewind(),
+            None => self.demuxer.rewind().map_err(io::Error::other),
+        }
+    }
+
+    /// Sleeps until an instant, waking up to check the stop flag.
+    ///
+    /// Returns `false` if the playback was stopped meanwhile.
+    fn sleep_until(due: Instant, stopped: &AtomicBool) -> bool {
+        loop {
+            if stopped.load(Ordering::Relaxed) {
+                return false;
+            }
+            let remaining = due.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return true;
+            }
+            thread::sleep(remaining.min(Self::STOP_CHECK_INTERVAL));
+        }
+    }
+}
+
+/// Creates the metadata of a packet, as sent by the device.
+#[allow(clippy::cast_possible_truncation)]
+const fn new_metadata(packet: PacketType, size: usize) -> FrameMetadata {
+    FrameMetadata {
+        packet,
+        size: size as u32,
+    }
+}
@@ -0,0 +1,174 @@
+//! Raw video stream capture, for bug reports
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use ffmpeg_next::codec;
+use scrcpy_launcher::video::{FrameMetadata, PacketType};
+
+/// Sidecar line describing a captured packet
+#[derive(Debug, Serialize, Deserialize)]
+struct PacketRecord {
+    #[serde(rename = "type")]
+    kind: PacketKind,
+    /// Stream timestamp in microseconds. `None` for config packets.
+    pts: Option<i64>,
+    /// Packet size in bytes
+    size: usize,
+    /// Host arrival time, in microseconds since the Unix epoch
+    arrival_us: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PacketKind {
+    Config,
+    Key,
+    Regular,
+}
+
+/// Writes the video packets as received from the device.
+///
+/// The packets are concatenated into an elementary stream, playable as a virtual device, and
+/// described in a JSON Lines sidecar file next to it.
+pub struct StreamDump {
+    path: PathBuf,
+    stream: BufWriter<File>,
+    sidecar: BufWriter<File>,
+}
+
+impl StreamDump {
+    /// Elementary stream file extension of a codec. `None` if not supported.
+    pub const fn extension(codec: codec::Id) -> Option<&'static str> {
+        match codec {
+            codec::Id::H264 => Some("h264"),
+            codec::Id::H265 => Some("h265"),
+            codec::Id::AV1 => Some("obu"),
+            _ => None,
+        }
+    }
+
+    /// Creates the stream file and its `.jsonl` sidecar, truncating any existing ones.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a file cannot be created.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_owned(),
+            stream: BufWriter::new(File::create(path)?),
+            sidecar: BufWriter::new(File::create(path.with_extension("jsonl"))?),
+        })
+    }
+
+    /// Appends a packet.
+    ///
+    /// # Arguments
+    ///
+    /// - `metadata`: Packet metadata, as received.
+    /// - `data`: Packet data.
+    /// - `arrival_us`: Host arrival time, in microseconds since the Unix epoch.
+    pub fn write(
+        &mut self,
+        metadata: &FrameMetadata,
+        data: &[u8],
+        arrival_us: i64,
+    ) -> io::Result<()> {
+        let (kind, pts) = match metadata.packet {
+            PacketType::Config => (PacketKind::Config, None),
+            PacketType::KeyFrame(pts) => (PacketKind::Key, Some(pts)),
+            PacketType::Regular(pts) => (PacketKind::Regular, Some(pts)),
+        };
+        let record = PacketRecord {
+            kind,
+            pts,
+            size: data.len(),
+            arrival_us,
+        };
+
+        self.stream.write_all(data)?;
+        serde_json::to_writer(&mut self.sidecar, &record)?;
+        self.sidecar.write_all(b"\n")
+    }
+
+    /// Flushes the files, and returns the stream file path.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the buffered data cannot be written.
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        self.stream.flush()?;
+        self.sidecar.flush()?;
+        Ok(self.path)
+    }
+}
+
+/// Reads a raw video stream capture back, packet by packet, as received from the device.
+///
+/// The packet boundaries and timestamps come from the sidecar file, raw streams having none.
+pub struct CaptureReader {
+    sidecar_path: PathBuf,
+    stream: BufReader<File>,
+    sidecar: io::Lines<BufReader<File>>,
+}
+
+impl CaptureReader {
+    /// Opens a stream capture. `None` if the stream has no sidecar file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if a file cannot be opened.
+    pub fn open(path: &Path) -> io::Result<Option<Self>> {
+        let sidecar_path = path.with_extension("jsonl");
+        if !sidecar_path.is_file() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            stream: BufReader::new(File::open(path)?),
+            sidecar: BufReader::new(File::open(&sidecar_path)?).lines(),
+            sidecar_path,
+        }))
+    }
+
+    /// Seeks back to the start of the capture.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files cannot be read again.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.stream.rewind()?;
+        self.sidecar = BufReader::new(File::open(&self.sidecar_path)?).lines();
+        Ok(())
+    }
+
+    /// Reads the next packet. `None` at the end of the capture.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files cannot be read, or the sidecar does not describe the stream.
+    pub fn read(&mut self) -> io::Result<Option<(PacketType, Vec<u8>)>> {
+        let Some(line) = self.sidecar.next().transpose()? else {
+            return Ok(None);
+        };
+        let record: PacketRecord = serde_json::from_str(&line)?;
+
+        let packet = match (record.kind, record.pts) {
+            (PacketKind::Config, _) => PacketType::Config,
+            (PacketKind::Key, Some(pts)) => PacketType::KeyFrame(pts),
+            (PacketKind::Regular, Some(pts)) => PacketType::Regular(pts),
+            (_, None) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Captured media packet without timestamp",
+                ));
+            }
+        };
+
+        let mut data = vec![0; record.size];
+        self.stream.read_exact(&mut data)?;
+
+        Ok(Some((packet, data)))
+    }
+}
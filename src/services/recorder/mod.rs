@@ -0,0 +1,615 @@
+//! Session recording, remuxing the encoded streams without transcoding
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use ffmpeg_next::codec;
+use scrcpy_launcher::video::{FrameMetadata, PacketType};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinSet;
+
+pub use crate::services::recorder::dump::CaptureReader;
+use crate::services::recorder::dump::StreamDump;
+use crate::services::recorder::replay::{BufferedPacket, ReplayBuffer};
+use crate::services::stream_decoder::new_packet;
+use crate::services::utils::output::create_output_path;
+use crate::transcoding::error::FFmpegError;
+use crate::transcoding::muxer::{Muxer, StreamParameters};
+
+mod dump;
+mod replay;
+
+pub type RecordingCallback = Arc<dyn Fn(PathBuf) + Send + Sync>;
+/// Raw stream capture input: packet metadata, data, and host arrival time in microseconds since
+/// the Unix epoch
+pub type CapturedPacket = (FrameMetadata, Vec<u8>, i64);
+
+/// Recording container format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerFormat {
+    Mp4,
+    Matroska,
+}
+
+/// Recording configuration
+pub struct RecorderConfig {
+    /// Recordings output directory, created if missing
+    pub directory: PathBuf,
+    pub format: ContainerFormat,
+    /// Duration kept in memory for instant replays, zero disables them
+    pub replay_duration: Duration,
+    /// Whether the raw video stream is captured for the whole session
+    pub capture_stream: bool,
+    /// Called when a recording or replay is finalized, with its path
+    pub on_saved_cb: RecordingCallback,
+}
+
+/// Recorded stream of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedStream {
+    Video,
+    Audio,
+}
+
+/// Recorder input
+#[derive(Debug)]
+pub enum RecorderMessage {
+    /// Encoded packet of a session stream, recorded or not.
+    Packet {
+        stream: RecordedStream,
+        metadata: FrameMetadata,
+        data: Vec<u8>,
+        /// Whether packets of the stream were dropped before this one
+        discontinuity: bool,
+    },
+    /// Starts a new recording, from the next video keyframe.
+    Start,
+    /// Finalizes the current recording.
+    Stop,
+    /// Saves the buffered last seconds of the session to a new file.
+    SaveReplay,
+}
+
+/// Forwards a copy of the packets of a stream to the recorder, and to the raw stream capture.
+///
+/// The decoding never waits for the recorder: packets are dropped while it is busy, except the
+/// codec headers. The capture gets every packet the decoder gets, waiting for its writer.
+#[derive(Debug)]
+pub struct PacketTee {
+    stream: RecordedStream,
+    tx: mpsc::Sender<RecorderMessage>,
+    /// Packets dropped since the last forwarded one
+    dropped: u64,
+    /// Raw stream capture writer. `None` if not captured.
+    capture: Option<mpsc::Sender<CapturedPacket>>,
+}
+
+/// Records the session streams on demand.
+///
+/// All packets are received, recording or not, to know the codec headers sent at the start
+/// of the streams, and to keep the last seconds of the session for instant replays.
+pub struct Recorder {
+    config: RecorderConfig,
+    /// Device serial, prefixing the file names
+    device_id: String,
+    /// Video stream description. `None` if no video stream is enabled.
+    video: Option<StreamParameters>,
+    /// Audio stream description. `None` if no audio stream is enabled.
+    audio: Option<StreamParameters>,
+    /// Latest packets, for instant replays
+    replay: ReplayBuffer,
+    /// Whether a recording is in progress, shared with the ui
+    recording: Arc<AtomicBool>,
+    /// Whether video packets were dropped, the stream resumes from the next keyframe
+    video_gap: bool,
+}
+
+/// Recording in progress
+struct Recording {
+    path: PathBuf,
+    /// Opened on the first recorded packet
+    muxer: Option<Muxer>,
+    /// Muxer stream index of the video. `None` if not recorded.
+    video: Option<usize>,
+    /// Muxer stream index of the audio. `None` if not recorded.
+    audio: Option<usize>,
+    /// Timestamp of the first recorded packet, the recording starts at zero
+    base_pts: i64,
+}
+
+impl ContainerFormat {
+    /// `FFmpeg` muxer name
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Matroska => "matroska",
+        }
+    }
+
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Mp4 => "mp4",
+            Self::Matroska => "mkv",
+        }
+    }
+
+    /// Whether a stream can be stored in the container.
+    fn supports(self, parameters: &StreamParameters) -> bool {
+        match self {
+            // MP4 has no raw PCM audio support.
+            Self::Mp4 => parameters.codec() != ffmpeg_next::codec::Id::PCM_S16LE,
+            Self::Matroska => true,
+        }
+    }
+}
+
+impl PacketTee {
+    pub const fn new(stream: RecordedStream, tx: mpsc::Sender<RecorderMessage>) -> Self {
+        Self {
+            stream,
+            tx,
+            dropped: 0,
+            capture: None,
+        }
+    }
+
+    /// Forwards the packets to a raw stream capture writer as well.
+    #[must_use]
+    pub fn with_capture(mut self, capture: Option<mpsc::Sender<CapturedPacket>>) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Forwards a copy of a packet, dropping it if the recorder is busy.
+    ///
+    /// Codec headers are never dropped, they are required by all the following packets. The
+    /// capture never drops packets.
+    ///
+    /// Returns `false` once the recorder and the capture have ended.
+    ///
+    /// # Arguments
+    ///
+    /// - `metadata`: Packet metadata, as received.
+    /// - `data`: Packet data.
+    /// - `arrival`: Host instant the packet was received at.
+    pub fn send(&mut self, metadata: &FrameMetadata, data: &[u8], arrival: Instant) -> bool {
+        if let Some(capture) = &self.capture {
+            // The arrival instant is carried over to the wall clock.
+            let elapsed = i64::try_from(arrival.elapsed().as_micros()).unwrap_or_default();
+            let arrival_us = chrono::Utc::now().timestamp_micros() - elapsed;
+            if capture
+                .blocking_send((metadata.clone(), data.to_vec(), arrival_us))
+                .is_err()
+            {
+                self.capture = None;
+            }
+        }
+
+        let msg = RecorderMessage::Packet {
+            stream: self.stream,
+            metadata: metadata.clone(),
+            data: data.to_vec(),
+            discontinuity: self.dropped > 0,
+        };
+
+        let result = if metadata.packet == PacketType::Config {
+            self.tx.blocking_send(msg).map_err(|_| ())
+        } else {
+            match self.tx.try_send(msg) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => {
+                    if self.dropped == 0 {
+                        warn!("Recorder is busy, dropping {:?} packets", self.stream);
+                    }
+                    self.dropped += 1;
+                    return true;
+                }
+                Err(TrySendError::Closed(_)) => Err(()),
+            }
+        };
+
+        if result.is_ok() && self.dropped > 0 {
+            warn!(
+                "Recorder dropped {} {:?} packets",
+                self.dropped, self.stream
+            );
+            self.dropped = 0;
+        }
+        result.is_ok() || self.capture.is_some()
+    }
+}
+
+/// Spawns the raw video stream capture writer, if enabled. The capture covers the whole
+/// session, it is finalized when the packets source is closed.
+///
+/// Returns the packets sender, `None` if disabled, unsupported or on failure.
+///
+/// # Arguments
+///
+/// - `set`: Task lifecycle manager.
+/// - `config`: Recording configuration.
+/// - `device_id`: Device serial, prefixing the file names.
+/// - `codec`: Video codec.
+pub fn start_capture(
+    set: &mut JoinSet<io::Result<()>>,
+    config: &RecorderConfig,
+    device_id: &str,
+    codec: codec::Id,
+) -> Option<mpsc::Sender<CapturedPacket>> {
+    /// Packets buffered for the writer, the decoding waits beyond it
+    const CAPTURE_BUFFER: usize = 64;
+
+    if !config.capture_stream {
+        return None;
+    }
+    let extension = StreamDump::extension(codec)?;
+    let result = create_output_path(&config.directory, device_id, "_capture", extension)
+        .and_then(|path| StreamDump::create(&path));
+    let mut dump = match result {
+        Ok(dump) => dump,
+        Err(err) => {
+            error!("Cannot create the stream capture: {err}");
+            return None;
+        }
+    };
+
+    let (tx, mut rx) = mpsc::channel::<CapturedPacket>(CAPTURE_BUFFER);
+    let on_saved_cb = config.on_saved_cb.clone();
+    // Writes to the files synchronously.
+    set.spawn_blocking(move || {
+        while let Some((metadata, data, arrival_us)) = rx.blocking_recv() {
+            if let Err(err) = dump.write(&metadata, &data, arrival_us) {
+                error!("Stream capture failed: {err}");
+                break;
+            }
+        }
+
+        match dump.finish() {
+            Ok(path) => on_saved_cb(path),
+            Err(err) => error!("Cannot finalize the stream capture: {err}"),
+        }
+        Ok(())
+    });
+
+    Some(tx)
+}
+
+impl Recorder {
+    /// Creates a new session recorder
+    ///
+    /// # Arguments
+    ///
+    /// - `config`: Recording configuration.
+    /// - `device_id`: Device serial, prefixing the file names.
+    /// - `video`: Video stream description, without codec header.
+    /// - `audio`: Audio stream description, without codec header.
+    /// - `recording`: Whether a recording is in progress, updated by the recorder.
+    pub fn new(
+        config: RecorderConfig,
+        device_id: String,
+        video: Option<StreamParameters>,
+        audio: Option<StreamParameters>,
+        recording: Arc<AtomicBool>,
+    ) -> Self {
+        let replay = ReplayBuffer::new(config.replay_duration, video.is_some());
+
+        Self {
+            config,
+            device_id,
+            video,
+            audio,
+            replay,
+            recording,
+            video_gap: false,
+        }
+    }
+
+    /// Spawns background recording task.
+    ///
+    /// The recording in progress is finalized on [`RecorderMessage::Stop`], or when the
+    /// messages source is closed.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `messages`: Incoming packets and commands source.
+    pub fn start(
+        mut self,
+        set: &mut JoinSet<io::Result<()>>,
+        mut messages: mpsc::Receiver<RecorderMessage>,
+    ) {
+        // Muxing writes to the file synchronously.
+        set.spawn_blocking(move || {
+            let mut recording = None;
+
+            while let Some(msg) = messages.blocking_recv() {
+                match msg {
+                    RecorderMessage::Packet {
+                        stream,
+                        metadata,
+                        data,
+                        discontinuity,
+                    } => {
+                        // Packets following a gap cannot be decoded until the next keyframe.
+                        if stream == RecordedStream::Video {
+                            self.video_gap |= discontinuity;
+                            match metadata.packet {
+                                PacketType::KeyFrame(_) => self.video_gap = false,
+                                PacketType::Regular(_) if self.video_gap => continue,
+                                _ => {}
+                            }
+                        }
+
+                        if metadata.packet == PacketType::Config {
+                            if let Some(parameters) = self.parameters_mut(stream) {
+                                parameters.set_extradata(data);
+                            }
+                            continue;
+                        }
+
+                        if let Some(current) = &mut recording
+                            && let Err(err) = self.write(current, stream, &metadata, &data)
+                        {
+                            error!("Recording failed: {err}");
+                            recording = None;
+                            self.recording.store(false, Ordering::Relaxed);
+                        }
+
+                        self.replay.push(BufferedPacket {
+                            stream,
+                            metadata,
+                            data: Arc::new(data),
+                        });
+                    }
+                    RecorderMessage::Start => {
+                        if recording.is_some() {
+                            continue;
+                        }
+                        match self.create_path("") {
+                            Ok(path) => {
+                                info!("Recording \"{}\" to {}", self.device_id, path.display());
+                                recording = Some(Recording::new(path));
+                                self.recording.store(true, Ordering::Relaxed);
+                            }
+                            Err(err) => error!("Cannot create the recordings directory: {err}"),
+                        }
+                    }
+                    RecorderMessage::Stop => {
+                        if let Some(recording) = recording.take() {
+                            self.finish(recording);
+                        }
+                        self.recording.store(false, Ordering::Relaxed);
+                    }
+                    RecorderMessage::SaveReplay => self.save_replay(),
+                }
+            }
+
+            // The session has ended.
+            if let Some(recording) = recording.take() {
+                self.finish(recording);
+            }
+            self.recording.store(false, Ordering::Relaxed);
+
+            Ok(())
+        });
+    }
+
+    const fn parameters_mut(&mut self, stream: RecordedStream) -> Option<&mut StreamParameters> {
+        match stream {
+            RecordedStream::Video => self.video.as_mut(),
+            RecordedStream::Audio => self.audio.as_mut(),
+        }
+    }
+
+    /// Creates the recordings directory, and returns a new recording file path in it.
+    ///
+    /// # Arguments
+    ///
+    /// - `suffix`: File name suffix, before the extension.
+    fn create_path(&self, suffix: &str) -> io::Result<PathBuf> {
+        create_output_path(
+            &self.config.directory,
+            &self.device_id,
+            suffix,
+            self.config.format.extension(),
+        )
+    }
+
+    /// Writes a packet to the recording in progress.
+    fn write(
+        &self,
+        recording: &mut Recording,
+        stream: RecordedStream,
+        metadata: &FrameMetadata,
+        data: &[u8],
+    ) -> Result<(), FFmpegError> {
+        recording.write(
+            self.config.format,
+            self.video.as_ref(),
+            self.audio.as_ref(),
+            stream,
+            metadata,
+            data,
+        )
+    }
+
+    /// Writes the buffered packets to a new file on its own thread, the live packets keep being
+    /// received meanwhile.
+    fn save_replay(&self) {
+        if self.replay.is_empty() {
+            warn!("No replay buffered for \"{}\"", self.device_id);
+            return;
+        }
+
+        let path = match self.create_path("_replay") {
+            Ok(path) => path,
+            Err(err) => {
+                error!("Cannot create the recordings directory: {err}");
+                return;
+            }
+        };
+
+        let packets = self.replay.snapshot();
+        let format = self.config.format;
+        let (video, audio) = (self.video.clone(), self.audio.clone());
+        let device_id = self.device_id.clone();
+        let on_saved_cb = self.config.on_saved_cb.clone();
+
+        std::thread::spawn(move || {
+            let mut replay = Recording::new(path);
+            for packet in &packets {
+                if let Err(err) = replay.write(
+                    format,
+                    video.as_ref(),
+                    audio.as_ref(),
+                    packet.stream,
+                    &packet.metadata,
+                    &packet.data,
+                ) {
+                    error!("Replay saving failed: {err}");
+                    return;
+                }
+            }
+
+            replay.finish(&device_id, &on_saved_cb);
+        });
+    }
+
+    /// Finalizes a recording.
+    fn finish(&self, recording: Recording) {
+        recording.finish(&self.device_id, &self.config.on_saved_cb);
+    }
+}
+
+impl Recording {
+    const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            muxer: None,
+            video: None,
+            audio: None,
+            base_pts: 0,
+        }
+    }
+
+    /// Writes a packet, opening the file on the first video keyframe.
+    ///
+    /// # Arguments
+    ///
+    /// - `format`: Container format.
+    /// - `video`: Video stream description. `None` if no video stream is enabled.
+    /// - `audio`: Audio stream description. `None` if no audio stream is enabled.
+    /// - `stream`: Stream of the packet.
+    /// - `metadata`: Packet type and timestamp.
+    /// - `data`: Encoded packet.
+    fn write(
+        &mut self,
+        format: ContainerFormat,
+        video: Option<&StreamParameters>,
+        audio: Option<&StreamParameters>,
+        stream: RecordedStream,
+        metadata: &FrameMetadata,
+        data: &[u8],
+    ) -> Result<(), FFmpegError> {
+        let pts = match metadata.packet {
+            PacketType::Config => return Ok(()),
+            PacketType::KeyFrame(pts) | PacketType::Regular(pts) => pts,
+        };
+
+        if self.muxer.is_none() {
+            // The recording must be decodable from its first frame.
+            let starts = match stream {
+                RecordedStream::Video => matches!(metadata.packet, PacketType::KeyFrame(_)),
+                RecordedStream::Audio => video.is_none(),
+            };
+            if !starts {
+                return Ok(());
+            }
+
+            self.open(format, video, audio, pts)?;
+        }
+
+        let index = match stream {
+            RecordedStream::Video => self.video,
+            RecordedStream::Audio => self.audio,
+        };
+        // Audio captured before the first video frame is dropped.
+        let (Some(index), Some(muxer)) = (index, &mut self.muxer) else {
+            return Ok(());
+        };
+        if pts < self.base_pts {
+            return Ok(());
+        }
+
+        let mut packet = new_packet(metadata, data);
+        packet.set_pts(Some(pts - self.base_pts));
+        packet.set_dts(Some(pts - self.base_pts));
+
+        muxer.write(index, packet)
+    }
+
+    /// Finalizes the file.
+    ///
+    /// # Arguments
+    ///
+    /// - `device_id`: Recorded device serial.
+    /// - `on_saved_cb`: Called with the path once finalized.
+    fn finish(self, device_id: &str, on_saved_cb: &RecordingCallback) {
+        let Some(muxer) = self.muxer else {
+            warn!("Recording of \"{device_id}\" stopped before any frame");
+            return;
+        };
+
+        match muxer.finish() {
+            Ok(()) => on_saved_cb(self.path),
+            Err(err) => error!("Cannot finalize {}: {err}", self.path.display()),
+        }
+    }
+
+    /// Creates the file, with the streams supported by the container.
+    fn open(
+        &mut self,
+        format: ContainerFormat,
+        video: Option<&StreamParameters>,
+        audio: Option<&StreamParameters>,
+        base_pts: i64,
+    ) -> Result<(), FFmpegError> {
+        let mut streams = Vec::new();
+        for (parameters, index) in [(video, &mut self.video), (audio, &mut self.audio)] {
+            let Some(parameters) = parameters else {
+                continue;
+            };
+            if !format.supports(parameters) {
+                warn!(
+                    "{:?} is not supported by {format:?}, not recorded",
+                    parameters.codec()
+                );
+                continue;
+            }
+
+            *index = Some(streams.len());
+            streams.push(parameters.clone());
+        }
+
+        self.muxer = Some(Muxer::create(&self.path, format.name(), &streams)?);
+        self.base_pts = base_pts;
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl fmt::Debug for RecorderConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecorderConfig")
+            .field("directory", &self.directory)
+            .field("format", &self.format)
+            .field("replay_duration", &self.replay_duration)
+            .field("capture_stream", &self.capture_stream)
+            .finish()
+    }
+}
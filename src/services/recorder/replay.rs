@@ -0,0 +1,159 @@
+//! Rolling buffer of the latest session packets
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use scrcpy_launcher::video::{FrameMetadata, PacketType};
+
+use crate::services::recorder::RecordedStream;
+
+/// Encoded packet kept for a replay, cheap to clone
+#[derive(Debug, Clone)]
+pub struct BufferedPacket {
+    pub stream: RecordedStream,
+    pub metadata: FrameMetadata,
+    pub data: Arc<Vec<u8>>,
+}
+
+/// Keeps the encoded packets of the last seconds of a session in memory.
+///
+/// The buffer always starts at a point a replay can be decoded from: a video keyframe, or any
+/// audio packet in audio only sessions. Older packets are dropped once the next starting point
+/// is beyond the buffered duration.
+#[derive(Debug)]
+pub struct ReplayBuffer {
+    /// Buffered duration in microseconds, `0` if disabled
+    duration: i64,
+    /// Whether the session has a video stream, replays then start on keyframes
+    video: bool,
+    packets: VecDeque<BufferedPacket>,
+    /// Sequence number of the oldest packet
+    first: u64,
+    /// Replay starting points (sequence number, timestamp), oldest first
+    starts: VecDeque<(u64, i64)>,
+}
+
+impl BufferedPacket {
+    /// Packet timestamp, in microseconds. `None` for config packets.
+    const fn pts(&self) -> Option<i64> {
+        match self.metadata.packet {
+            PacketType::Config => None,
+            PacketType::KeyFrame(pts) | PacketType::Regular(pts) => Some(pts),
+        }
+    }
+
+    /// Whether a replay can start with this packet.
+    const fn starts(&self, video: bool) -> bool {
+        match self.stream {
+            RecordedStream::Video => matches!(self.metadata.packet, PacketType::KeyFrame(_)),
+            RecordedStream::Audio => !video,
+        }
+    }
+}
+
+impl ReplayBuffer {
+    /// Creates an empty buffer.
+    ///
+    /// # Arguments
+    ///
+    /// - `duration`: Minimal buffered duration, zero disables the buffer.
+    /// - `video`: Whether the session has a video stream.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn new(duration: Duration, video: bool) -> Self {
+        Self {
+            duration: duration.as_micros() as i64,
+            video,
+            packets: VecDeque::new(),
+            first: 0,
+            starts: VecDeque::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+
+    /// Copy of the buffered packets, oldest first. The packet data is shared.
+    pub fn snapshot(&self) -> Vec<BufferedPacket> {
+        self.packets.iter().cloned().collect()
+    }
+
+    /// Appends a media packet, and drops the packets no longer needed.
+    pub fn push(&mut self, packet: BufferedPacket) {
+        if self.duration == 0 {
+            return;
+        }
+        let Some(pts) = packet.pts() else {
+            return;
+        };
+        if packet.starts(self.video) {
+            self.starts
+                .push_back((self.first + self.packets.len() as u64, pts));
+        }
+        self.packets.push_back(packet);
+
+        // The buffer is cut at the latest starting point old enough, other than its first packet.
+        let cutoff = pts - self.duration;
+        while let Some(&(next, start_pts)) = self
+            .starts
+            .iter()
+            .find(|&&(sequence, _)| sequence > self.first)
+        {
+            if start_pts > cutoff {
+                break;
+            }
+            #[allow(clippy::cast_possible_truncation)]
+            self.packets.drain(..(next - self.first) as usize);
+            self.first = next;
+            while self
+                .starts
+                .front()
+                .is_some_and(|&(sequence, _)| sequence < next)
+            {
+                self.starts.pop_front();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use scrcpy_launcher::video::{FrameMetadata, PacketType};
+
+    use crate::services::recorder::RecordedStream;
+    use crate::services::recorder::replay::{BufferedPacket, ReplayBuffer};
+
+    fn packet(packet: PacketType) -> BufferedPacket {
+        BufferedPacket {
+            stream: RecordedStream::Video,
+            metadata: FrameMetadata { packet, size: 0 },
+            data: Arc::new(Vec::new()),
+        }
+    }
+
+    #[test]
+    fn test_replay_buffer() {
+        let mut buffer = ReplayBuffer::new(Duration::from_secs(1), true);
+
+        // A keyframe per second, 10 frames per second
+        for pts in (0..=30).map(|i| i * 100_000) {
+            buffer.push(packet(if pts % 1_000_000 == 0 {
+                PacketType::KeyFrame(pts)
+            } else {
+                PacketType::Regular(pts)
+            }));
+        }
+
+        // Cut at the keyframe at least a second older than the latest packet
+        let packets = buffer.snapshot();
+        assert_eq!(packets.len(), 11);
+        assert!(matches!(
+            packets[0].metadata.packet,
+            PacketType::KeyFrame(2_000_000)
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+//! Video frame screenshots
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use ffmpeg_next::frame;
+
+use crate::services::utils::output::create_output_path;
+use crate::transcoding::video::frame_buffer::FrameBuffer;
+use crate::transcoding::video::rgba::to_rgba;
+
+/// Host clipboard owning the copied screenshots.
+///
+/// On X11 and Wayland, the clipboard data is served by its owner: the image is lost once the
+/// clipboard is dropped, it is kept for the process lifetime.
+static CLIPBOARD: Mutex<Option<arboard::Clipboard>> = Mutex::new(None);
+
+/// Screenshot configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenshotConfig {
+    /// Screenshots output directory, created if missing
+    pub directory: PathBuf,
+    /// Whether the screenshots are also copied to the host clipboard
+    pub copy_to_clipboard: bool,
+}
+
+/// Saves a decoded frame as a PNG image, at the device resolution.
+///
+/// Blocks on the conversion and file writing.
+///
+/// # Arguments
+///
+/// - `config`: Screenshot configuration.
+/// - `device_id`: Device serial, prefixing the file name.
+/// - `frame`: Video frame, downloaded from the GPU if needed.
+///
+/// # Errors
+///
+/// Fails if the frame cannot be converted or the image cannot be written. A clipboard failure
+/// is only logged, the image being saved.
+pub fn save(
+    config: &ScreenshotConfig,
+    device_id: &str,
+    frame: &frame::Video,
+) -> io::Result<PathBuf> {
+    let mut frame = FrameBuffer::from(frame.clone());
+    frame.download_to_cpu().map_err(io::Error::other)?;
+
+    let (width, height) = (frame.width(), frame.height());
+    let pixels = to_rgba(&frame).map_err(io::Error::other)?;
+
+    let path = create_output_path(&config.directory, device_id, "", "png")?;
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(&path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)?;
+
+    if config.copy_to_clipboard {
+        let image = arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: Cow::Owned(pixels),
+        };
+        if let Err(err) = copy_to_clipboard(image) {
+            warn!("Screenshot: cannot copy to the host clipboard: {err}");
+        }
+    }
+
+    Ok(path)
+}
+
+/// Copies an image to the host clipboard, opened on the first copy.
+fn copy_to_clipboard(image: arboard::ImageData<'_>) -> Result<(), arboard::Error> {
+    let mut clipboard = CLIPBOARD
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let clipboard = match &mut *clipboard {
+        Some(clipboard) => clipboard,
+        None => clipboard.insert(arboard::Clipboard::new()?),
+    };
+
+    clipboard.set_image(image)
+}
@@ -0,0 +1,249 @@
+//! Presentation queue of the decoded frames
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::services::stream_decoder::FrameTiming;
+use crate::transcoding::video::frame_buffer::FrameBuffer;
+
+/// Presentation of the decoded frames, trading latency for smoothness
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentationMode {
+    /// Latest frame only, the frames not displayed yet are dropped
+    #[default]
+    LowestLatency,
+    /// Frames paced by their timestamps, after a short buffering absorbing the network jitter
+    Smooth,
+    /// All the frames in order, one per repaint
+    EveryFrame,
+}
+
+/// Decoded frames waiting to be displayed, shared by the decoder and the ui.
+///
+/// Timestamps are in microseconds.
+pub struct FrameQueue {
+    mode: PresentationMode,
+    state: parking_lot::Mutex<QueueState>,
+    /// Frames dropped without being displayed
+    dropped: AtomicU64,
+    /// Queued frames count
+    depth: AtomicUsize,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// Frames, their timing and presentation instant
+    frames: VecDeque<(FrameBuffer, FrameTiming, Instant)>,
+    /// Timestamp paced from, and its presentation instant
+    origin: Option<(i64, Instant)>,
+}
+
+impl FrameQueue {
+    /// Queued frames of the buffering modes, the oldest are dropped beyond it.
+    /// Hardware frames hold decoder surfaces, it is kept small.
+    const CAPACITY: usize = 8;
+    /// Hardware frames held out of the decoder: the queued ones and the ones being copied to the
+    /// renderer.
+    pub const HELD_FRAMES: usize = Self::CAPACITY + 2;
+    /// Buffering of the smooth mode
+    const SMOOTH_DELAY: Duration = Duration::from_millis(50);
+    /// Deviation from the pace restarting it, after a stall or a timestamp jump
+    const MAX_DEVIATION: Duration = Duration::from_millis(200);
+
+    pub fn new(mode: PresentationMode) -> Self {
+        Self {
+            mode,
+            state: parking_lot::Mutex::new(QueueState::default()),
+            dropped: AtomicU64::new(0),
+            depth: AtomicUsize::new(0),
+        }
+    }
+
+    pub const fn mode(&self) -> PresentationMode {
+        self.mode
+    }
+
+    /// Frames dropped without being displayed.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Queued frames count.
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    /// Queues a decoded frame.
+    ///
+    /// # Arguments
+    ///
+    /// - `frame`: Decoded frame.
+    /// - `timing`: Frame reception and decoding instants.
+    pub fn push(&self, frame: FrameBuffer, timing: FrameTiming) {
+        let now = timing.decoded;
+        let mut state = self.state.lock();
+
+        let capacity = match self.mode {
+            PresentationMode::LowestLatency => 1,
+            PresentationMode::Smooth | PresentationMode::EveryFrame => Self::CAPACITY,
+        };
+        while state.frames.len() >= capacity {
+            state.frames.pop_front();
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let due = match (self.mode, frame.pts()) {
+            (PresentationMode::Smooth, Some(pts)) => state.pace(pts, now),
+            _ => now,
+        };
+        state.frames.push_back((frame, timing, due));
+        self.depth.store(state.frames.len(), Ordering::Relaxed);
+    }
+
+    /// Frame to display, if any.
+    ///
+    /// The smooth mode returns the latest due frame, dropping the older ones.
+    pub fn pop(&self, now: Instant) -> Option<(FrameBuffer, FrameTiming)> {
+        let mut state = self.state.lock();
+
+        let frame = match self.mode {
+            PresentationMode::LowestLatency | PresentationMode::EveryFrame => state
+                .frames
+                .pop_front()
+                .map(|(frame, timing, _)| (frame, timing)),
+            PresentationMode::Smooth => {
+                let mut latest = None;
+                while state.frames.front().is_some_and(|(_, _, due)| *due <= now) {
+                    if latest.is_some() {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                    latest = state
+                        .frames
+                        .pop_front()
+                        .map(|(frame, timing, _)| (frame, timing));
+                }
+                latest
+            }
+        };
+        self.depth.store(state.frames.len(), Ordering::Relaxed);
+
+        frame
+    }
+
+    /// Delay until the next frame is due. `None` if the queue is empty.
+    pub fn next_due(&self, now: Instant) -> Option<Duration> {
+        self.state
+            .lock()
+            .frames
+            .front()
+            .map(|(_, _, due)| due.saturating_duration_since(now))
+    }
+}
+
+impl QueueState {
+    /// Presentation instant of a timestamp, restarting the pace if it deviates too much.
+    fn pace(&mut self, pts: i64, now: Instant) -> Instant {
+        let due = self.origin.and_then(|(origin_pts, origin)| {
+            let elapsed = u64::try_from(pts - origin_pts).ok()?;
+            Some(origin + Duration::from_micros(elapsed))
+        });
+
+        match due {
+            Some(due)
+                if due + FrameQueue::MAX_DEVIATION >= now
+                    && due <= now + FrameQueue::SMOOTH_DELAY + FrameQueue::MAX_DEVIATION =>
+            {
+                due
+            }
+            _ => {
+                let due = now + FrameQueue::SMOOTH_DELAY;
+                self.origin = Some((pts, due));
+                due
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use ffmpeg_next::frame;
+
+    use crate::services::session::frame_queue::{FrameQueue, PresentationMode};
+    use crate::services::stream_decoder::FrameTiming;
+    use crate::transcoding::video::frame_buffer::FrameBuffer;
+
+    fn frame(pts: i64) -> FrameBuffer {
+        let mut frame = frame::Video::empty();
+        frame.set_pts(Some(pts));
+        frame.into()
+    }
+
+    fn timing(now: Instant) -> FrameTiming {
+        FrameTiming {
+            pts: None,
+            sent: now,
+            arrival: now,
+            decoded: now,
+        }
+    }
+
+    #[test]
+    fn test_lowest_latency() {
+        let queue = FrameQueue::new(PresentationMode::LowestLatency);
+        let now = Instant::now();
+
+        queue.push(frame(0), timing(now));
+        queue.push(frame(1000), timing(now));
+        assert_eq!(queue.dropped(), 1);
+        assert_eq!(queue.depth(), 1);
+
+        assert_eq!(
+            queue.pop(now).and_then(|(frame, _)| frame.pts()),
+            Some(1000)
+        );
+        assert!(queue.pop(now).is_none());
+    }
+
+    #[test]
+    fn test_every_frame() {
+        let queue = FrameQueue::new(PresentationMode::EveryFrame);
+        let now = Instant::now();
+
+        for pts in 0..10 {
+            queue.push(frame(pts), timing(now));
+        }
+        assert_eq!(queue.dropped(), 2);
+        assert_eq!(queue.depth(), FrameQueue::CAPACITY);
+
+        assert_eq!(queue.pop(now).and_then(|(frame, _)| frame.pts()), Some(2));
+        assert_eq!(queue.pop(now).and_then(|(frame, _)| frame.pts()), Some(3));
+    }
+
+    #[test]
+    fn test_smooth() {
+        let queue = FrameQueue::new(PresentationMode::Smooth);
+        let now = Instant::now();
+        let at = |ms| now + FrameQueue::SMOOTH_DELAY + Duration::from_millis(ms);
+
+        queue.push(frame(0), timing(now));
+        queue.push(frame(10_000), timing(now));
+        queue.push(frame(20_000), timing(now));
+        assert!(queue.pop(now).is_none());
+        assert_eq!(queue.next_due(now), Some(FrameQueue::SMOOTH_DELAY));
+
+        assert_eq!(queue.pop(at(0)).and_then(|(frame, _)| frame.pts()), Some(0));
+        // Late frames are skipped.
+        assert_eq!(
+            queue.pop(at(25)).and_then(|(frame, _)| frame.pts()),
+            Some(20_000)
+        );
+        assert_eq!(queue.dropped(), 1);
+
+        // Timestamp jumps restart the pace.
+        queue.push(frame(10_000_000), timing(at(30)));
+        assert_eq!(queue.next_due(at(30)), Some(FrameQueue::SMOOTH_DELAY));
+    }
+}
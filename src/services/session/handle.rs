@@ -1,32 +1,60 @@
 use std::fmt;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
 
+use scrcpy_launcher::options::{AudioCodec, AudioSource};
 use tokio::sync::{mpsc, oneshot};
 
 use crate::services::adb::{AdbHandle, DeviceId};
-use crate::services::session::{SessionActor, SessionCommand, SessionConfig, SharedFrame};
+use crate::services::control::ControlMessage;
+use crate::services::recorder::RecorderMessage;
+use crate::services::screenshot::ScreenshotConfig;
+use crate::services::session::{
+    FrameQueue, SessionActor, SessionCommand, SessionConfig, SharedAudioStats, SharedDecoderStats,
+    SharedFrameQueue, SharedRecording, ToneMapping,
+};
 
 /// Control configuration
 #[derive(Debug, Clone)]
-pub struct SessionControl;
+pub struct SessionControl {
+    /// Whether the device and host clipboards are synchronized
+    pub clipboard_sync: bool,
+}
 
 /// Audio configuration
 #[derive(Debug, Clone)]
-pub struct SessionAudio;
+pub struct SessionAudio {
+    pub codec: AudioCodec,
+    /// Captured device audio
+    pub source: AudioSource,
+}
 
 /// Video configuration
 #[derive(Debug, Clone)]
-pub struct SessionVideo;
+pub struct SessionVideo {
+    /// HDR to SDR conversion of the displayed video
+    pub tone_mapping: ToneMapping,
+}
 
 /// A thread-safe handle for interacting with the Session service.
 #[derive(Clone)]
 pub struct SessionHandle {
     pub device_id: DeviceId,
 
-    /// Control events sender channel
-    pub control_tx: mpsc::Sender<()>,
-    /// Current video frame
-    pub shared_frame: SharedFrame,
+    /// Control messages sender channel
+    pub control_tx: mpsc::Sender<ControlMessage>,
+    /// Recorder commands sender channel
+    record_tx: mpsc::Sender<RecorderMessage>,
+    /// Whether a recording is in progress
+    recording: SharedRecording,
+    /// Audio playback statistics
+    pub audio_stats: SharedAudioStats,
+    /// Video decoding statistics
+    pub decoder_stats: SharedDecoderStats,
+    /// Decoded frames waiting to be displayed
+    pub frames: SharedFrameQueue,
+    /// Screenshots of the session video
+    pub screenshot: ScreenshotConfig,
 
     /// Control configuration. `None` if no control stream is enabled.
     pub control: Option<SessionControl>,
@@ -40,6 +68,10 @@ pub struct SessionHandle {
 
 impl SessionHandle {
     const BUFFER: usize = 32;
+    /// Pointer moves are emitted at the display rate, leave room for bursts.
+    const CONTROL_BUFFER: usize = 256;
+    /// Packets of all streams go through the recorder, recording or not.
+    const RECORD_BUFFER: usize = 256;
 
     /// Spawns the Session actor and returns a communication handle.
     pub fn new(
@@ -48,24 +80,64 @@ impl SessionHandle {
         exit_tx: oneshot::Sender<Option<anyhow::Error>>,
     ) -> Self {
         let (tx, rx) = mpsc::channel(Self::BUFFER);
-        let (control_tx, control_rx) = mpsc::channel(32);
-        let shared_frame = Arc::new(parking_lot::RwLock::new(None));
+        let (control_tx, control_rx) = mpsc::channel(Self::CONTROL_BUFFER);
+        let (record_tx, record_rx) = mpsc::channel(Self::RECORD_BUFFER);
+        let audio_stats = SharedAudioStats::default();
+        let decoder_stats = SharedDecoderStats::default();
+        let recording = SharedRecording::default();
+        let frames = Arc::new(FrameQueue::new(
+            config
+                .video
+                .as_ref()
+                .map(|video| video.presentation)
+                .unwrap_or_default(),
+        ));
 
         let device_id = config.device_id.clone();
-        let control = config.control.as_ref().map(|()| SessionControl);
-        let audio = config.audio.as_ref().map(|()| SessionAudio);
-        let video = config.video.as_ref().map(|_| SessionVideo);
-
-        let session = SessionActor::new(adb, config, control_rx, shared_frame.clone(), rx);
-        tokio::spawn(async move {
-            let result = session.run().await;
-            let _ = exit_tx.send(result.err());
+        let control = config.control.as_ref().map(|control| SessionControl {
+            clipboard_sync: control.clipboard.is_some(),
+        });
+        let audio = config.audio.as_ref().map(|audio| SessionAudio {
+            codec: audio.codec,
+            source: audio.source,
+        });
+        let video = config.video.as_ref().map(|video| SessionVideo {
+            tone_mapping: video.tone_mapping,
+        });
+        let screenshot = config.screenshot.clone();
+
+        let session = SessionActor::new(
+            adb,
+            config,
+            control_tx.clone(),
+            control_rx,
+            record_tx.clone(),
+            record_rx,
+            audio_stats.clone(),
+            decoder_stats.clone(),
+            frames.clone(),
+            recording.clone(),
+            rx,
+        );
+        tokio::spawn({
+            let record_tx = record_tx.clone();
+            async move {
+                let result = session.run().await;
+                // Finalizes the recording in progress, whatever the session outcome.
+                let _ = record_tx.send(RecorderMessage::Stop).await;
+                let _ = exit_tx.send(result.err());
+            }
         });
 
         Self {
             device_id,
             control_tx,
-            shared_frame,
+            record_tx,
+            recording,
+            audio_stats,
+            decoder_stats,
+            frames,
+            screenshot,
             control,
             audio,
             video,
@@ -73,6 +145,41 @@ impl SessionHandle {
         }
     }
 
+    /// Queues a control message for the device.
+    ///
+    /// Never blocks the UI: the message is dropped if the queue is full or the session ended.
+    pub fn send_control(&self, msg: ControlMessage) {
+        if let Err(err) = self.control_tx.try_send(msg) {
+            warn!("Control message dropped for \"{}\": {err}", self.device_id);
+        }
+    }
+
+    /// Starts recording the session streams, from the next video keyframe.
+    pub fn start_recording(&self) {
+        if let Err(err) = self.record_tx.try_send(RecorderMessage::Start) {
+            warn!("Recording not started for \"{}\": {err}", self.device_id);
+        }
+    }
+
+    /// Whether a recording is in progress. Reset when the recording fails or the session ends.
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    /// Finalizes the recording in progress.
+    pub fn stop_recording(&self) {
+        if let Err(err) = self.record_tx.try_send(RecorderMessage::Stop) {
+            warn!("Recording not stopped for \"{}\": {err}", self.device_id);
+        }
+    }
+
+    /// Saves the last seconds of the session to a new file.
+    pub fn save_replay(&self) {
+        if let Err(err) = self.record_tx.try_send(RecorderMessage::SaveReplay) {
+            warn!("Replay not saved for \"{}\": {err}", self.device_id);
+        }
+    }
+
     /// Signals the Session service to exit.
     pub async fn exit(&self) {
         let _ = self.sender.send(SessionCommand::Exit).await;
@@ -1,21 +1,40 @@
 use std::sync::Arc;
-use std::{fmt, io};
+use std::sync::atomic::AtomicBool;
+use std::{fmt, io, sync};
 
-use ffmpeg_next::codec;
+use ffmpeg_next::{codec, ffi};
 use scrcpy_launcher::ScrcpyLauncher;
-use scrcpy_launcher::options::{Options, ServerId, VideoCodec};
+use scrcpy_launcher::options::{AudioCodec, AudioSource, Options, ServerId, VideoCodec};
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::sync::mpsc;
 use tokio::task::JoinSet;
 
 use crate::services::adb::{AdbHandle, DeviceId};
+use crate::services::audio_sink::{AudioFormat, AudioOutput, AudioStats};
+use crate::services::control::{
+    ClipboardConfig, ClipboardSync, ControlMessage, ControlStream, DeviceStream,
+};
+use crate::services::playback::{FileSource, PlaybackConfig};
+use crate::services::recorder::{
+    PacketTee, RecordedStream, Recorder, RecorderConfig, RecorderMessage, start_capture,
+};
+use crate::services::screenshot::ScreenshotConfig;
+pub use crate::services::session::frame_queue::{FrameQueue, PresentationMode};
 pub use crate::services::session::handle::SessionHandle;
-use crate::services::stream_decoder::VideoStreamDecoder;
-use crate::transcoding::video::frame_buffer::FrameBuffer;
+use crate::services::stream_decoder::{
+    AudioStreamDecoder, DecoderStats, FallbackCallback, VideoStreamDecoder, stamp_packets,
+};
+use crate::transcoding::hw::device_index::HWDeviceIndex;
+use crate::transcoding::muxer::StreamParameters;
+use crate::transcoding::video::decoder::DecoderConfig;
 
+mod frame_queue;
 mod handle;
 
-pub type SharedFrame = Arc<parking_lot::RwLock<Option<FrameBuffer>>>;
+pub type SharedFrameQueue = Arc<FrameQueue>;
+pub type SharedAudioStats = Arc<AudioStats>;
+pub type SharedDecoderStats = Arc<DecoderStats>;
+pub type SharedRecording = Arc<AtomicBool>;
 pub type FrameCallback = Box<dyn Fn() + Send + Sync>;
 pub type SessionStoppedCallback = Box<dyn FnOnce(Option<anyhow::Error>) + Send + Sync>;
 
@@ -30,9 +49,16 @@ pub struct SessionActor {
 
     adb: AdbHandle,
 
-    control_rx: mpsc::Receiver<()>,
+    control_tx: mpsc::Sender<ControlMessage>,
+    control_rx: mpsc::Receiver<ControlMessage>,
+    record_tx: mpsc::Sender<RecorderMessage>,
+    record_rx: mpsc::Receiver<RecorderMessage>,
+    audio_tx: Option<OwnedWriteHalf>,
     video_tx: Option<OwnedWriteHalf>,
-    shared_frame: SharedFrame,
+    audio_stats: SharedAudioStats,
+    decoder_stats: SharedDecoderStats,
+    frames: SharedFrameQueue,
+    recording: SharedRecording,
 
     rx: mpsc::Receiver<SessionCommand>,
     set: JoinSet<io::Result<()>>,
@@ -41,9 +67,28 @@ pub struct SessionActor {
 #[derive(Debug)]
 pub struct SessionConfig {
     pub device_id: DeviceId,
-    pub control: Option<()>,
-    pub audio: Option<()>,
+    pub control: Option<SessionControlConfig>,
+    pub audio: Option<SessionAudioConfig>,
     pub video: Option<SessionVideoConfig>,
+    pub record: RecorderConfig,
+    pub screenshot: ScreenshotConfig,
+    /// Recorded video played back instead of a device stream. `None` for devices.
+    pub playback: Option<PlaybackConfig>,
+}
+
+#[derive(Debug)]
+pub struct SessionControlConfig {
+    /// Clipboard synchronization. `None` if disabled.
+    pub clipboard: Option<ClipboardConfig>,
+}
+
+#[derive(Debug)]
+pub struct SessionAudioConfig {
+    pub codec: AudioCodec,
+    /// Captured device audio
+    pub source: AudioSource,
+    /// Decoded audio destination
+    pub output: AudioOutput,
 }
 
 pub struct SessionVideoConfig {
@@ -52,25 +97,66 @@ pub struct SessionVideoConfig {
     pub bitrate: i32,
     pub max_fps: f32,
     pub hw_decoder: bool,
+    /// Hardware decoding device and its physical interface. `None` for the renderer device.
+    pub hw_device: Option<(HWDeviceIndex, ffi::AVHWDeviceType)>,
+    /// Decoder threading and options
+    pub decoder: DecoderConfig,
+    /// HDR to SDR conversion of the displayed video
+    pub tone_mapping: ToneMapping,
+    /// Pacing of the displayed frames
+    pub presentation: PresentationMode,
     pub on_frame_cb: FrameCallback,
+    /// Called when hardware decoding fails and software decoding takes over
+    pub on_fallback_cb: FallbackCallback,
+}
+
+/// HDR (PQ, HLG) to SDR conversion of the displayed video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapping {
+    /// HDR signal displayed as is, with washed out colors
+    Disabled,
+    /// Highlights clipped to the SDR white
+    Clip,
+    /// Highlights compressed with the extended Reinhard curve
+    Reinhard,
+    /// Highlights compressed with the Hable filmic curve
+    #[default]
+    Hable,
 }
 
 impl SessionActor {
     const PORT: u16 = 3333;
+    /// Played back packets waiting to be decoded
+    const PLAYBACK_BUFFER: usize = 8;
+    /// Received packets waiting to be decoded, per stream
+    const STREAM_BUFFER: usize = 32;
 
     pub fn new(
         adb: AdbHandle,
         config: SessionConfig,
-        control_rx: mpsc::Receiver<()>,
-        shared_frame: SharedFrame,
+        control_tx: mpsc::Sender<ControlMessage>,
+        control_rx: mpsc::Receiver<ControlMessage>,
+        record_tx: mpsc::Sender<RecorderMessage>,
+        record_rx: mpsc::Receiver<RecorderMessage>,
+        audio_stats: SharedAudioStats,
+        decoder_stats: SharedDecoderStats,
+        frames: SharedFrameQueue,
+        recording: SharedRecording,
         rx: mpsc::Receiver<SessionCommand>,
     ) -> Self {
         Self {
             config,
             adb,
+            control_tx,
             control_rx,
+            record_tx,
+            record_rx,
+            audio_tx: None,
             video_tx: None,
-            shared_frame,
+            audio_stats,
+            decoder_stats,
+            frames,
+            recording,
             rx,
             set: JoinSet::new(),
         }
@@ -81,6 +167,10 @@ impl SessionActor {
     /// Processes the session audio, video and control streams, until
     /// the channel is closed or a [`SessionCommand::Exit`] message is received.
     async fn run(mut self) -> Result<(), anyhow::Error> {
+        if let Some(config) = self.config.playback.take() {
+            return self.run_playback(config).await;
+        }
+
         let device = self.adb.get_device(self.config.device_id.clone()).await?;
 
         let mut options = Options::new()
@@ -92,8 +182,11 @@ impl SessionActor {
         if let Some(_config) = self.config.control.as_ref() {
             options = options.set_control(true);
         }
-        if let Some(_config) = self.config.audio.as_ref() {
-            options = options.set_audio(true);
+        if let Some(config) = self.config.audio.as_ref() {
+            options = options
+                .set_audio(true)
+                .set_audio_codec(config.codec)
+                .set_audio_source(config.source);
         }
         if let Some(config) = self.config.video.as_ref() {
             options = options
@@ -112,12 +205,65 @@ impl SessionActor {
         let (connection, _process) = launcher.start(options).await?;
         let mut session = connection.start().await?;
 
+        match (self.config.control, session.get_control_mut().take()) {
+            (None, None) => {}
+            (Some(config), Some(control)) => {
+                let (device_tx, device_rx) = sync::mpsc::channel();
+
+                ControlStream::new(control.state.tx).start(&mut self.set, self.control_rx);
+                DeviceStream::new(control.state.rx).start(&mut self.set, device_tx);
+
+                if let Some(clipboard) = config.clipboard {
+                    ClipboardSync::new(clipboard, self.control_tx.clone())
+                        .start(&mut self.set, device_rx);
+                }
+            }
+            _ => unreachable!("Control configuration mismatch"),
+        }
+
+        // Recorded streams, without their codec header received later
+        let mut recorded_audio = None;
+        let mut recorded_video = None;
+
+        match (self.config.audio, session.get_audio_mut().take()) {
+            (None, None) => {}
+            (Some(config), Some(audio)) => {
+                // NOTE: the writer must not be dropped.
+                self.audio_tx.replace(audio.state.tx);
+
+                let codec = match config.codec {
+                    AudioCodec::Opus => codec::Id::OPUS,
+                    AudioCodec::Aac => codec::Id::AAC,
+                    AudioCodec::Flac => codec::Id::FLAC,
+                    AudioCodec::Raw => codec::Id::PCM_S16LE,
+                };
+                recorded_audio = Some(StreamParameters::Audio {
+                    codec,
+                    sample_rate: AudioFormat::STREAM.sample_rate.cast_signed(),
+                    channels: AudioFormat::STREAM.channels.into(),
+                    extradata: None,
+                });
+
+                let audio_rx = stamp_packets(&mut self.set, audio.state.rx, Self::STREAM_BUFFER);
+                AudioStreamDecoder::new(codec, config.output).start(
+                    &mut self.set,
+                    audio_rx,
+                    self.audio_stats.clone(),
+                    Some(PacketTee::new(
+                        RecordedStream::Audio,
+                        self.record_tx.clone(),
+                    )),
+                );
+            }
+            _ => unreachable!("Audio configuration mismatch"),
+        }
+
         match (self.config.video, session.get_video_mut().take()) {
             (None, None) => {}
             (Some(config), Some(video)) => {
                 // NOTE: the writer must not be dropped.
                 self.video_tx.replace(video.state.tx);
-                let video_rx = video.state.rx;
+                let video_rx = stamp_packets(&mut self.set, video.state.rx, Self::STREAM_BUFFER);
                 let metadata = video.state.metadata;
 
                 let codec = match config.codec {
@@ -126,28 +272,65 @@ impl SessionActor {
                     VideoCodec::AV1 => codec::Id::AV1,
                 };
                 let size = (metadata.width, metadata.height);
-                let decoder = VideoStreamDecoder::new(codec, size, config.hw_decoder);
+                recorded_video = Some(StreamParameters::Video {
+                    codec,
+                    width: metadata.width,
+                    height: metadata.height,
+                    extradata: None,
+                });
+
+                // The capture has its own writer, it gets every packet the decoder gets.
+                let capture = start_capture(
+                    &mut self.set,
+                    &self.config.record,
+                    &self.config.device_id,
+                    codec,
+                );
+
+                let decoder = VideoStreamDecoder::new(
+                    codec,
+                    size,
+                    config.hw_decoder,
+                    config.hw_device,
+                    DecoderConfig {
+                        extra_hw_frames: FrameQueue::HELD_FRAMES,
+                        ..config.decoder
+                    },
+                );
                 decoder.start(
                     &mut self.set,
                     video_rx,
-                    Box::new(move |frame| {
-                        self.shared_frame.write().replace(frame);
+                    Box::new(move |frame, timing| {
+                        self.frames.push(frame, timing);
                         (config.on_frame_cb)();
                     }),
+                    config.on_fallback_cb,
+                    self.decoder_stats.clone(),
+                    Some(
+                        PacketTee::new(RecordedStream::Video, self.record_tx.clone())
+                            .with_capture(capture),
+                    ),
                 );
             }
             _ => unreachable!("Video configuration mismatch"),
         }
 
+        if recorded_audio.is_some() || recorded_video.is_some() {
+            Recorder::new(
+                self.config.record,
+                self.config.device_id.clone(),
+                recorded_video,
+                recorded_audio,
+                self.recording.clone(),
+            )
+            .start(&mut self.set, self.record_rx);
+        }
+
         tokio::select! {
-            // Internal join set has ended. Likely a failure in the consumer pipelines.
-            results = self.set.join_all() => {
-                error!("Session unexpectedly ended for \"{}\" {results:?}", self.config.device_id);
-                for result in results {
-                    if let Err(err) = result {
-                        return Err(err.into());
-                    }
-                }
+            // A consumer pipeline failed, or all of them ended.
+            result = Self::join_tasks(&mut self.set) => {
+                error!("Session unexpectedly ended for \"{}\" {result:?}", self.config.device_id);
+                result?;
             }
             // Session has ended, likely a user device disconnect.
             results = session.join() => {
@@ -166,6 +349,75 @@ impl SessionActor {
 
         Ok(())
     }
+
+    /// Waits for the first task failure, or for all the tasks to end.
+    ///
+    /// Some tasks never end on their own, a failing task must not wait for them.
+    async fn join_tasks(set: &mut JoinSet<io::Result<()>>) -> Result<(), anyhow::Error> {
+        while let Some(result) = set.join_next().await {
+            result??;
+        }
+        Ok(())
+    }
+
+    /// Runs a virtual device session, decoding a recorded video instead of a device stream.
+    ///
+    /// Only the video configuration is used. The session ends with the playback.
+    async fn run_playback(mut self, config: PlaybackConfig) -> Result<(), anyhow::Error> {
+        let Some(video) = self.config.video.take() else {
+            return Err(anyhow::anyhow!(
+                "Video is required to play a recording back"
+            ));
+        };
+
+        let path = config.path.clone();
+        let source = tokio::task::spawn_blocking(move || FileSource::open(config)).await??;
+        info!(
+            "Playing {} back as \"{}\" ({:?})",
+            path.display(),
+            self.config.device_id,
+            source.codec()
+        );
+
+        let (tx, rx) = mpsc::channel(Self::PLAYBACK_BUFFER);
+        let decoder = VideoStreamDecoder::new(
+            source.codec(),
+            source.size(),
+            video.hw_decoder,
+            video.hw_device,
+            DecoderConfig {
+                extra_hw_frames: FrameQueue::HELD_FRAMES,
+                ..video.decoder
+            },
+        );
+        decoder.start(
+            &mut self.set,
+            rx,
+            Box::new(move |frame, timing| {
+                self.frames.push(frame, timing);
+                (video.on_frame_cb)();
+            }),
+            video.on_fallback_cb,
+            self.decoder_stats.clone(),
+            None,
+        );
+        // The reader is stopped when the session exits, or when the actor is dropped.
+        let _playback = source.start(&mut self.set, tx);
+
+        tokio::select! {
+            // Both tasks end with the playback, unless one fails.
+            result = Self::join_tasks(&mut self.set) => {
+                info!("Playback ended for \"{}\" {result:?}", self.config.device_id);
+                result?;
+            }
+            // User stop signal from the ui.
+            command = self.rx.recv() => if command == Some(SessionCommand::Exit) {
+                info!("Stopping session for \"{}\"", self.config.device_id);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[allow(clippy::missing_fields_in_debug)]
@@ -176,6 +428,11 @@ impl fmt::Debug for SessionVideoConfig {
             .field("width", &self.width)
             .field("bitrate", &self.bitrate)
             .field("max_fps", &self.max_fps)
+            .field("hw_decoder", &self.hw_decoder)
+            .field("hw_device", &self.hw_device)
+            .field("decoder", &self.decoder)
+            .field("tone_mapping", &self.tone_mapping)
+            .field("presentation", &self.presentation)
             .finish()
     }
 }
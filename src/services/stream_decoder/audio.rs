@@ -1 +1,152 @@
+use std::io;
+use std::sync::Arc;
 
+use ffmpeg_next::codec;
+use scrcpy_launcher::video::PacketType;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::services::audio_sink::{AudioFormat, AudioOutput, AudioStats};
+use crate::services::recorder::PacketTee;
+use crate::services::stream_decoder::utils::{StreamPacket, new_packet};
+use crate::transcoding::audio::decoder::AudioDecoder;
+use crate::transcoding::audio::resampler::AudioResampler;
+
+/// Audio stream decoder
+pub struct AudioStreamDecoder {
+    /// Decoding codec ID
+    codec: codec::Id,
+    /// Decoded samples destination
+    output: AudioOutput,
+}
+
+impl AudioStreamDecoder {
+    /// Creates a new audio stream decoder
+    ///
+    /// # Arguments
+    ///
+    /// - `codec`: Decoding codec ID
+    /// - `output`: Decoded samples destination
+    pub const fn new(codec: codec::Id, output: AudioOutput) -> Self {
+        Self { codec, output }
+    }
+
+    /// Spawns background decoding task.
+    ///
+    /// Consumes an audio packet stream, writing the decoded samples to the output sink.
+    /// The decoder is opened on the first media packet, with the preceding config packet
+    /// as codec header.
+    ///
+    /// The playback stops if the output or the decoder fails, the packets keep being recorded.
+    ///
+    /// # Arguments
+    ///
+    /// - `set`: Task lifecycle manager.
+    /// - `stream`: Incoming audio packet source, with the packets arrival instant.
+    /// - `stats`: Playback statistics, with the timestamp of the samples being played.
+    /// - `tee`: Recorder receiving a copy of the packets.
+    pub fn start(
+        self,
+        set: &mut JoinSet<io::Result<()>>,
+        mut stream: mpsc::Receiver<StreamPacket>,
+        stats: Arc<AudioStats>,
+        mut tee: Option<PacketTee>,
+    ) {
+        set.spawn_blocking(move || {
+            // Device sinks may not be moved across threads, open it on the decoding one.
+            let mut playback = match self.output.open(stats.clone()) {
+                Ok(sink) => {
+                    let AudioFormat {
+                        sample_rate,
+                        channels,
+                    } = sink.format();
+                    Some((sink, AudioResampler::new(sample_rate, channels)))
+                }
+                Err(err) => {
+                    error!("Failed to open the audio output: {err}");
+                    None
+                }
+            };
+
+            let mut decoder = None;
+            let mut extradata = None;
+
+            // The stream keeps being read, and recorded, once the playback stopped.
+            while let Some((metadata, buffer, arrival)) = stream.blocking_recv() {
+                if tee
+                    .as_mut()
+                    .is_some_and(|tee| !tee.send(&metadata, &buffer, arrival))
+                {
+                    tee = None;
+                }
+
+                if metadata.packet == PacketType::Config {
+                    // Raw PCM streams have no config packet.
+                    if decoder.is_none() {
+                        extradata = Some(buffer);
+                    }
+                    continue;
+                }
+
+                let Some((sink, resampler)) = &mut playback else {
+                    continue;
+                };
+
+                if decoder.is_none() {
+                    let stream_format = AudioFormat::STREAM;
+                    let result = AudioDecoder::from_codec(
+                        self.codec,
+                        extradata.take().as_deref(),
+                        stream_format.sample_rate.cast_signed(),
+                        stream_format.channels.into(),
+                    );
+                    match result {
+                        Ok(result) => decoder = Some(result),
+                        Err(err) => {
+                            error!("Failed to start the audio decoder: {err}");
+                            playback = None;
+                            continue;
+                        }
+                    }
+                }
+                let Some(decoder) = &mut decoder else {
+                    continue;
+                };
+
+                let packet = new_packet(&metadata, &buffer);
+                if let Err(err) = decoder.send_packet(&packet) {
+                    error!("Send audio packet: {err}");
+                    continue;
+                }
+
+                // A packet may hold several frames.
+                while let Ok(frame) = decoder.receive_frame() {
+                    let samples = match resampler.run(&frame) {
+                        Ok(samples) => samples,
+                        Err(err) => {
+                            error!("Resample audio frame: {err}");
+                            continue;
+                        }
+                    };
+
+                    if let Err(err) = sink.write(samples) {
+                        error!("Audio output closed: {err}");
+                        playback = None;
+                        break;
+                    }
+
+                    // Timestamps are in microseconds.
+                    if let Some(pts) = frame.pts() {
+                        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                        let played = pts
+                            + frame.samples() as i64 * 1_000_000 / i64::from(frame.rate().max(1))
+                            - sink.delay().as_micros() as i64;
+                        stats.set_pts(played);
+                    }
+                }
+            }
+
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,83 @@
+//! Device to host clock mapping of the packet timestamps
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Maps the device timestamps onto the host clock.
+///
+/// The clocks are not synchronized: the offset between them is estimated from the fastest packet
+/// of the last [`Self::WINDOW`], assumed to have no transit delay. The network latency is then
+/// relative to that packet, following the clock drift and the network changes.
+///
+/// Timestamps are in microseconds.
+pub struct ClockOffset {
+    /// Host reference of the transits
+    epoch: Instant,
+    /// Transit candidates (arrival, host time - timestamp), the fastest first.
+    ///
+    /// Each one is faster than the later ones, the slower packets can never become the fastest.
+    transits: VecDeque<(Instant, i64)>,
+}
+
+impl ClockOffset {
+    /// Estimation window
+    const WINDOW: Duration = Duration::from_secs(10);
+
+    pub fn new(epoch: Instant) -> Self {
+        Self {
+            epoch,
+            transits: VecDeque::new(),
+        }
+    }
+
+    /// Host instant the packet was sent at.
+    ///
+    /// # Arguments
+    ///
+    /// - `pts`: Device timestamp of the packet.
+    /// - `arrival`: Host instant the packet was received at.
+    pub fn sent(&mut self, pts: i64, arrival: Instant) -> Instant {
+        let host = i64::try_from(arrival.saturating_duration_since(self.epoch).as_micros())
+            .unwrap_or(i64::MAX);
+        let transit = host.saturating_sub(pts);
+
+        while self.transits.back().is_some_and(|&(_, t)| t >= transit) {
+            self.transits.pop_back();
+        }
+        self.transits.push_back((arrival, transit));
+
+        let limit = arrival.checked_sub(Self::WINDOW).unwrap_or(arrival);
+        while self.transits.len() > 1 && self.transits.front().is_some_and(|&(at, _)| at < limit) {
+            self.transits.pop_front();
+        }
+
+        let offset = self.transits.front().map_or(transit, |&(_, t)| t);
+        u64::try_from(pts.saturating_add(offset))
+            .map_or(self.epoch, |host| self.epoch + Duration::from_micros(host))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::services::stream_decoder::clock::ClockOffset;
+
+    #[test]
+    fn test_clock_offset() {
+        let epoch = Instant::now();
+        let at = |ms| epoch + Duration::from_millis(ms);
+        let mut clock = ClockOffset::new(epoch);
+
+        // Device clock 1 s ahead, the first packet is the fastest one.
+        assert_eq!(clock.sent(1_000_000, at(5)), at(5));
+        assert_eq!(clock.sent(1_010_000, at(25)), at(15));
+
+        // A faster packet lowers the offset.
+        assert_eq!(clock.sent(1_020_000, at(22)), at(22));
+        assert_eq!(clock.sent(1_030_000, at(40)), at(32));
+
+        // The fastest packet expires with the window.
+        assert_eq!(clock.sent(11_030_000, at(10_040)), at(10_040));
+    }
+}
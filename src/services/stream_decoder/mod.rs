@@ -1,5 +1,10 @@
-pub use crate::services::stream_decoder::video::VideoStreamDecoder;
+pub use crate::services::stream_decoder::audio::AudioStreamDecoder;
+pub use crate::services::stream_decoder::utils::{StreamPacket, new_packet, stamp_packets};
+pub use crate::services::stream_decoder::video::{
+    DecoderStats, FallbackCallback, FrameTiming, VideoStreamDecoder,
+};
 
 mod audio;
+mod clock;
 mod utils;
 mod video;
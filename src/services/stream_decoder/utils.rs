@@ -1 +1,62 @@
+use std::io;
+use std::time::Instant;
 
+use ffmpeg_next::Packet;
+use scrcpy_launcher::video::{FrameMetadata, PacketType};
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+/// Encoded packet of a device stream, with the host instant it was received at
+pub type StreamPacket = (FrameMetadata, Vec<u8>, Instant);
+
+/// Spawns a task stamping the packets of a device stream with their arrival instant.
+///
+/// The packets are forwarded by the socket reader through a channel, waking this task as soon
+/// as they are received: the decoding and recording backpressure stays out of the instants.
+///
+/// # Arguments
+///
+/// - `set`: Task lifecycle manager.
+/// - `packets`: Packets from the socket reader.
+/// - `capacity`: Stamped packets waiting to be decoded.
+pub fn stamp_packets(
+    set: &mut JoinSet<io::Result<()>>,
+    mut packets: mpsc::Receiver<(FrameMetadata, Vec<u8>)>,
+    capacity: usize,
+) -> mpsc::Receiver<StreamPacket> {
+    let (tx, rx) = mpsc::channel(capacity);
+    set.spawn(async move {
+        while let Some((metadata, data)) = packets.recv().await {
+            let arrival = Instant::now();
+            if tx.send((metadata, data, arrival)).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    rx
+}
+
+/// Creates an owned `FFmpeg` packet
+pub fn new_packet(metadata: &FrameMetadata, data: &[u8]) -> Packet {
+    assert_eq!(metadata.size as usize, data.len(), "Invalid data");
+
+    // let mut packet = Packet::new(metadata.size as usize);
+    // packet.data_mut().unwrap().write_all(data).unwrap();
+
+    let mut packet = Packet::copy(data);
+
+    let pts = match metadata.packet {
+        PacketType::Config => None,
+        PacketType::KeyFrame(pts) => {
+            packet.set_flags(ffmpeg_next::packet::flag::Flags::KEY);
+            Some(pts)
+        }
+        PacketType::Regular(pts) => Some(pts),
+    };
+
+    packet.set_pts(pts);
+    packet.set_dts(pts);
+
+    packet
+}
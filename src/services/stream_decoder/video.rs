@@ -1,15 +1,69 @@
+use std::collections::VecDeque;
 use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 
-use ffmpeg_next::{Dictionary, Packet, codec, ffi};
-use scrcpy_launcher::video::{FrameMetadata, PacketType};
+use ffmpeg_next::{codec, ffi};
+use scrcpy_launcher::video::PacketType;
 use tokio::sync::mpsc;
 use tokio::task::JoinSet;
 
+use crate::services::recorder::PacketTee;
+use crate::services::stream_decoder::clock::ClockOffset;
+use crate::services::stream_decoder::utils::{StreamPacket, new_packet};
+use crate::transcoding::error::FFmpegError;
+use crate::transcoding::hw::device::HWDevice;
+use crate::transcoding::hw::device_index::HWDeviceIndex;
 use crate::transcoding::hw::device_pool::HWDevicePool;
-use crate::transcoding::video::decoder::VideoDecoder;
+use crate::transcoding::hw::device_type::HWDeviceType;
+use crate::transcoding::video::decoder::{DecoderConfig, VideoDecoder};
 use crate::transcoding::video::frame_buffer::FrameBuffer;
 
-pub type FrameCallback = Box<dyn Fn(FrameBuffer) + Send + Sync + 'static>;
+pub type FrameCallback = Box<dyn Fn(FrameBuffer, FrameTiming) + Send + Sync + 'static>;
+pub type FallbackCallback = Box<dyn Fn(String) + Send + Sync + 'static>;
+
+/// Instants of a frame on its way to the decoder output
+#[derive(Debug, Clone, Copy)]
+pub struct FrameTiming {
+    /// Device timestamp, in microseconds
+    pub pts: Option<i64>,
+    /// Host instant the device sent the packet at, estimated from the timestamps
+    pub sent: Instant,
+    /// Host instant the packet was received at
+    pub arrival: Instant,
+    /// Decoding completion
+    pub decoded: Instant,
+}
+
+/// Decoding statistics, shared with the ui.
+#[derive(Debug, Default)]
+pub struct DecoderStats {
+    /// Decoded frames count
+    frames: AtomicU64,
+    /// Packets failed to decode count
+    errors: AtomicU64,
+}
+
+impl DecoderStats {
+    /// Decoded frames count.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    pub fn add_frame(&self) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Packets failed to decode count.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn add_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
 
 /// NAL video stream decoder
 pub struct VideoStreamDecoder {
@@ -17,105 +71,236 @@ pub struct VideoStreamDecoder {
     codec: codec::Id,
     /// Video frames size in pixels (width, height)
     size: (i32, i32),
-    /// Whether to use Vulkan hardware decoder
+    /// Whether to use a hardware decoder
     hw_decoder: bool,
+    /// Hardware decoding device. `None` for the renderer device, or the first Vulkan one.
+    hw_device: Option<(HWDeviceIndex, ffi::AVHWDeviceType)>,
+    /// Decoder tuning
+    config: DecoderConfig,
 }
 
 impl VideoStreamDecoder {
+    /// Packets failed in a row before the decoder is replaced
+    const MAX_FAILURES: u32 = 8;
+    /// Timings of the packets being decoded, matched with the frames by timestamp
+    const PENDING_PACKETS: usize = 32;
+
     /// Creates a new NAL video stream decoder
     ///
     /// # Arguments
     ///
     /// - `codec`: Decoding codec ID
     /// - `size`: Video frames size in pixels (width, height)
-    pub const fn new(codec: codec::Id, size: (i32, i32), hw_decoder: bool) -> Self {
+    /// - `hw_decoder`: Whether to use a hardware decoder
+    /// - `hw_device`: Hardware decoding device and its physical interface
+    /// - `config`: Decoder tuning
+    pub const fn new(
+        codec: codec::Id,
+        size: (i32, i32),
+        hw_decoder: bool,
+        hw_device: Option<(HWDeviceIndex, ffi::AVHWDeviceType)>,
+        config: DecoderConfig,
+    ) -> Self {
         Self {
             codec,
             size,
             hw_decoder,
+            hw_device,
+            config,
         }
     }
 
     /// Spawns background decoding task.
     ///
-    /// Consumes a NAL stream, overwriting mailbox with latest frame and triggering update signal.
+    /// Consumes a NAL stream, handing every decoded frame to the callback.
+    ///
+    /// A hardware decoder failing to open, or to decode [`Self::MAX_FAILURES`] packets in a row, is
+    /// replaced by a software decoder from the next keyframe. A software decoder failing as many
+    /// packets, e.g. on a lossy link, is restarted from the next keyframe. The task fails if the
+    /// software decoder cannot be opened, ending the session.
     ///
     /// # Arguments
     ///
     /// - `set`: Task lifecycle manager.
-    /// - `stream`: Incoming NAL unit source.
-    /// - `on_frame`: Decoded frame callback.
+    /// - `stream`: Incoming NAL unit source, with the packets arrival instant.
+    /// - `on_frame`: Decoded frame callback, with its timing.
+    /// - `on_fallback`: Software fallback callback, with the hardware decoding failure.
+    /// - `stats`: Decoding statistics, counting frames and errors.
+    /// - `tee`: Recorder receiving a copy of the packets.
     pub fn start(
         self,
         set: &mut JoinSet<io::Result<()>>,
-        mut stream: mpsc::Receiver<(FrameMetadata, Vec<u8>)>,
+        mut stream: mpsc::Receiver<StreamPacket>,
         on_frame: FrameCallback,
+        on_fallback: FallbackCallback,
+        stats: Arc<DecoderStats>,
+        mut tee: Option<PacketTee>,
     ) {
         set.spawn_blocking(move || {
-            // Vulkan is expected to be supported since it is used by the ui.
-            let Some(hw_device) = HWDevicePool::first(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN)
-            else {
-                error!("No Vulkan hardware device available");
-                return Ok(());
-            };
-
-            let result = VideoDecoder::from_codec(
-                self.codec,
-                &Dictionary::default(),
-                self.hw_decoder.then_some((hw_device, self.size)),
-            );
-            let mut decoder = match result {
-                Ok(decoder) => decoder,
-                Err(err) => {
-                    error!("Failed to start the video decoder: {err}");
-                    return Ok(());
+            let mut hw_device = None;
+            if self.hw_decoder {
+                hw_device = match self.hw_device {
+                    Some((index, device_type)) => HWDevicePool::find(index, device_type),
+                    // Vulkan is expected to be supported since it is used by the ui.
+                    // The renderer device is preferred, its frames do not need to be copied.
+                    None => HWDevicePool::shared().or_else(|| {
+                        HWDevicePool::first(ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN)
+                    }),
+                };
+                if hw_device.is_none() {
+                    let reason = self.hw_device.map_or_else(
+                        || "No Vulkan hardware device available".to_owned(),
+                        |(index, device_type)| {
+                            format!(
+                                "Hardware device {} {index} is not available",
+                                HWDeviceType::name_of(device_type)
+                            )
+                        },
+                    );
+                    Self::fall_back(&mut hw_device, &on_fallback, reason);
                 }
-            };
+            }
 
-            while let Some((metadata, buffer)) = stream.blocking_recv() {
-                let packet = new_packet(&metadata, &buffer);
+            let process_frame =
+                |frame: FrameBuffer, pending: &VecDeque<FrameTiming>| -> Result<(), FFmpegError> {
+                    let decoded = Instant::now();
+                    // Frames without a matching packet are attributed to the latest one.
+                    let timing = frame
+                        .pts()
+                        .and_then(|pts| pending.iter().rev().find(|timing| timing.pts == Some(pts)))
+                        .or_else(|| pending.back())
+                        .map_or(
+                            FrameTiming {
+                                pts: frame.pts(),
+                                sent: decoded,
+                                arrival: decoded,
+                                decoded,
+                            },
+                            |&timing| FrameTiming { decoded, ..timing },
+                        );
 
-                if let Err(err) = decoder.send_packet(&packet) {
-                    error!("Send packet: {err}");
-                    continue;
+                    stats.add_frame();
+                    on_frame(frame, timing);
+                    Ok(())
+                };
+            let mut clock = ClockOffset::new(Instant::now());
+            // Timings of the packets sent to the decoder
+            let mut pending = VecDeque::with_capacity(Self::PENDING_PACKETS);
+
+            let mut decoder = Some(self.open(&mut hw_device, &on_fallback)?);
+            // Codec header, sent again to the replacing decoder
+            let mut config = None;
+            // Packets failed in a row
+            let mut failures = 0;
+
+            while let Some((metadata, buffer, arrival)) = stream.blocking_recv() {
+                if tee
+                    .as_mut()
+                    .is_some_and(|tee| !tee.send(&metadata, &buffer, arrival))
+                {
+                    tee = None;
                 }
 
-                // Config packet are not frames
+                let packet = new_packet(&metadata, &buffer);
                 if metadata.packet == PacketType::Config {
-                    continue;
+                    config = Some(packet.clone());
+                }
+                if let Some(pts) = packet.pts() {
+                    if pending.len() == Self::PENDING_PACKETS {
+                        pending.pop_front();
+                    }
+                    pending.push_back(FrameTiming {
+                        pts: Some(pts),
+                        sent: clock.sent(pts, arrival),
+                        arrival,
+                        decoded: arrival,
+                    });
+                }
+
+                // The replacing decoder starts from a keyframe.
+                if decoder.is_none() {
+                    if !matches!(metadata.packet, PacketType::KeyFrame(_)) {
+                        continue;
+                    }
+                    let mut replacing = self.open(&mut hw_device, &on_fallback)?;
+                    if let Some(config) = &config
+                        && let Err(err) = replacing.send_packet(config)
+                    {
+                        error!("Send config packet: {err}");
+                    }
+                    decoder = Some(replacing);
                 }
+                let Some(active) = &mut decoder else {
+                    continue;
+                };
+
+                // A packet may hold several frames, or none for config packets.
+                let result = active
+                    .send_packet(&packet)
+                    .and_then(|()| active.receive_frames(|frame| process_frame(frame, &pending)));
 
-                match decoder.receive_frame() {
-                    Ok(frame) => on_frame(frame),
-                    Err(err) => error!("Receive frame: {err}"),
+                match result {
+                    Ok(()) => failures = 0,
+                    Err(err) => {
+                        error!("Decode packet: {err}");
+                        stats.add_error();
+                        failures += 1;
+                        if failures < Self::MAX_FAILURES {
+                            continue;
+                        }
+                        // Decoding restarts from the next keyframe, in software.
+                        if hw_device.is_some() {
+                            Self::fall_back(&mut hw_device, &on_fallback, err.to_string());
+                        }
+                        decoder = None;
+                        failures = 0;
+                    }
                 }
             }
 
+            // The last frames are held by the decoder until the end of the stream.
+            if let Some(decoder) = &mut decoder
+                && let Err(err) = decoder.flush(|frame| process_frame(frame, &pending))
+            {
+                error!("Flush decoder: {err}");
+                stats.add_error();
+            }
+
             Ok(())
         });
     }
-}
-
-/// Creates an owned `FFmpeg` video packet
-pub fn new_packet(metadata: &FrameMetadata, data: &[u8]) -> Packet {
-    assert_eq!(metadata.size as usize, data.len(), "Invalid data");
-
-    // let mut packet = Packet::new(metadata.size as usize);
-    // packet.data_mut().unwrap().write_all(data).unwrap();
 
-    let mut packet = Packet::copy(data);
-
-    let pts = match metadata.packet {
-        PacketType::Config => None,
-        PacketType::KeyFrame(pts) => {
-            packet.set_flags(ffmpeg_next::packet::flag::Flags::KEY);
-            Some(pts)
+    /// Opens the hardware decoder if any, falling back to a software decoder.
+    fn open(
+        &self,
+        hw_device: &mut Option<&'static HWDevice>,
+        on_fallback: &FallbackCallback,
+    ) -> io::Result<VideoDecoder<'static>> {
+        if let Some(device) = *hw_device {
+            match VideoDecoder::from_codec(self.codec, &self.config, Some((device, self.size))) {
+                Ok(decoder) => return Ok(decoder),
+                // Software decoders reject the same options.
+                Err(err @ FFmpegError::InvalidOption(..)) => {
+                    return Err(io::Error::other(format!(
+                        "Failed to start the video decoder: {err}"
+                    )));
+                }
+                Err(err) => Self::fall_back(hw_device, on_fallback, err.to_string()),
+            }
         }
-        PacketType::Regular(pts) => Some(pts),
-    };
 
-    packet.set_pts(pts);
-    packet.set_dts(pts);
+        VideoDecoder::from_codec(self.codec, &self.config, None)
+            .map_err(|err| io::Error::other(format!("Failed to start the video decoder: {err}")))
+    }
 
-    packet
+    /// Switches to software decoding, reporting the hardware failure.
+    fn fall_back(
+        hw_device: &mut Option<&'static HWDevice>,
+        on_fallback: &FallbackCallback,
+        reason: String,
+    ) {
+        warn!("Hardware decoding failed, falling back to software: {reason}");
+        hw_device.take();
+        on_fallback(reason);
+    }
 }
@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// Creates an output directory, and returns a new timestamped file path in it.
+///
+/// # Arguments
+///
+/// - `directory`: Output directory, created if missing.
+/// - `device_id`: Device serial, prefixing the file name.
+/// - `suffix`: File name suffix, before the extension.
+/// - `extension`: File extension.
+pub fn create_output_path(
+    directory: &Path,
+    device_id: &str,
+    suffix: &str,
+    extension: &str,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(directory)?;
+
+    // Network device serials hold an address and port.
+    let device: String = device_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+
+    Ok(directory.join(format!("{device}_{timestamp}{suffix}.{extension}")))
+}
@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 use eframe::egui;
-use eframe::egui::{Context, Memory, MenuBar, RawInput, RichText, TextStyle, warn_if_debug_build};
+use eframe::egui::{
+    Context, Memory, MenuBar, RawInput, RichText, TextStyle, ViewportId, warn_if_debug_build,
+};
 use eframe::egui_wgpu::RenderState;
 use tokio::sync::{Notify, mpsc};
 
@@ -14,22 +17,44 @@ use crate::core::msg::Msg;
 use crate::services::adb::AdbHandle;
 use crate::ui::context::{OwnedViewContext, ViewContext};
 use crate::ui::pages::home::HomePage;
-use crate::ui::pages::settings::{SessionSettings, SettingsPage};
+use crate::ui::pages::settings::{Keymaps, SessionSettings, SettingsPage};
 use crate::ui::pages::{CurrentPage, Page};
 use crate::ui::perf::fps_counter::{FpsCounter, FrameStats};
 use crate::ui::views::session::SessionViewport;
+use crate::ui::views::session::state::DevicePreferences;
 use crate::utils::math::round_magnitude;
 
 pub type MsgSender = mpsc::UnboundedSender<Msg>;
 
+/// Persisted application data.
+///
+/// Missing fields, added since the data was persisted, take their default value instead of
+/// discarding the whole data.
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppData {
     pub label: String,
     pub value: f32,
     pub session_settings: SessionSettings,
+    pub keymaps: Keymaps,
+    /// Session viewport preferences, by device serial
+    pub device_preferences: HashMap<String, DevicePreferences>,
     pub memory: Memory,
 }
 
+impl Default for AppData {
+    fn default() -> Self {
+        Self {
+            label: "Hello World!".to_owned(),
+            value: 2.7,
+            session_settings: SessionSettings::default(),
+            keymaps: Keymaps::default(),
+            device_preferences: HashMap::new(),
+            memory: Memory::default(),
+        }
+    }
+}
+
 pub struct Octane {
     /// Logic
     core: Model,
@@ -82,18 +107,10 @@ impl Octane {
 
         // Persistence
 
-        let data = cc
+        let data: AppData = cc
             .storage
             .and_then(|s| eframe::get_value(s, eframe::APP_KEY))
-            .map_or_else(
-                || AppData {
-                    label: "Hello World!".to_owned(),
-                    value: 2.7,
-                    session_settings: SessionSettings::default(),
-                    memory: Memory::default(),
-                },
-                |data| data,
-            );
+            .unwrap_or_default();
         cc.egui_ctx.memory_mut(|m| *m = data.memory.clone());
 
         // Styles
@@ -191,6 +208,11 @@ impl eframe::App for Octane {
         self.process_messages();
 
         for session in self.core.sessions.values().cloned() {
+            {
+                let state = &mut *session.write();
+                state.sync_preferences(&mut self.data.device_preferences);
+                state.sync_keymaps(&self.data.keymaps);
+            }
             SessionViewport::new(session).show(ctx, self.owned_ctx());
         }
 
@@ -274,7 +296,66 @@ impl eframe::App for Octane {
         visuals.window_fill().to_normalized_gamma_f32()
     }
 
-    fn raw_input_hook(&mut self, _ctx: &Context, _raw_input: &mut RawInput) {
-        // println!("Raw: {:?}", _raw_input);
+    fn raw_input_hook(&mut self, ctx: &Context, raw_input: &mut RawInput) {
+        // println!("Raw: {:?}", raw_input);
+
+        if raw_input.viewport_id == ViewportId::ROOT {
+            return;
+        }
+
+        // Keyboard events are intercepted before egui interprets them as shortcuts.
+        for session in self.core.sessions.values() {
+            let state = &mut *session.write();
+            if state.viewport_id() != raw_input.viewport_id {
+                continue;
+            }
+
+            // The editor lets the keys through to egui, to assign them.
+            state
+                .keymap_editor
+                .sync(&mut state.keymap_target, &mut self.data.keymaps);
+            if state.keymap_editor.is_open() {
+                break;
+            }
+
+            if SessionViewport::take_hotkey(raw_input, SessionViewport::REPLAY_KEY) {
+                state.session.save_replay();
+            }
+            if SessionViewport::take_hotkey(raw_input, SessionViewport::SCREENSHOT_KEY)
+                && let Some(msg) = state.screenshot()
+            {
+                self.capabilities.send(msg);
+            }
+            if state.session.control.is_none() {
+                break;
+            }
+
+            let mut messages = Vec::new();
+
+            // Keymap bindings take precedence over the keys they are bound to.
+            if let Some(mapping) = state.mapping {
+                if let Some(profile) = self.data.keymaps.profile(&state.keymap_target) {
+                    state
+                        .keymap
+                        .handle(raw_input, profile, &mapping, &mut messages);
+                }
+                if state.keymap.tick(Instant::now(), &mapping, &mut messages) {
+                    ctx.request_repaint_of(raw_input.viewport_id);
+                }
+            }
+
+            let clipboard_sync = state
+                .session
+                .control
+                .as_ref()
+                .is_some_and(|control| control.clipboard_sync);
+            state
+                .keyboard
+                .handle(raw_input, clipboard_sync, &mut messages);
+            for msg in messages {
+                state.session.send_control(msg);
+            }
+            break;
+        }
     }
 }
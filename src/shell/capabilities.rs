@@ -6,6 +6,7 @@ use crate::core::effect::{Effect, LogLevel};
 use crate::core::msg::Msg;
 use crate::core::primitives::async_state::AsyncResult;
 use crate::services::adb::AdbHandle;
+use crate::services::screenshot;
 use crate::services::session::SessionHandle;
 use crate::shell::app::MsgSender;
 
@@ -60,6 +61,20 @@ impl Capabilities {
                     session.exit().await;
                 });
             }
+
+            Effect::SaveScreenshot {
+                device_id,
+                config,
+                frame,
+            } => {
+                let tx = self.tx.clone();
+
+                // Image encoding is blocking.
+                tokio::task::spawn_blocking(move || {
+                    let result = screenshot::save(&config, &device_id, &frame);
+                    let _ = tx.send(Msg::ScreenshotSaved { device_id, result });
+                });
+            }
         }
     }
 
@@ -0,0 +1,67 @@
+#![allow(unsafe_code)]
+
+use ffmpeg_next::{Packet, codec, decoder, ffi, frame};
+
+use crate::transcoding::copy_extradata;
+use crate::transcoding::error::FFmpegError;
+
+pub struct AudioDecoder {
+    /// Audio decoder context
+    pub decoder: decoder::Audio,
+}
+
+impl AudioDecoder {
+    /// Create a new audio decoder from the specified codec ID.
+    ///
+    /// # Arguments
+    /// - `codec`: The codec ID to use for decoding.
+    /// - `extradata`: Codec specific header, required by `Opus`, `AAC` and `FLAC`.
+    /// - `sample_rate`: Stream sample rate in Hz, for codecs not carrying it.
+    /// - `channels`: Stream channel count, for codecs not carrying it.
+    pub fn from_codec(
+        codec: codec::Id,
+        extradata: Option<&[u8]>,
+        sample_rate: i32,
+        channels: i32,
+    ) -> Result<Self, FFmpegError> {
+        // Find the decoder for the specified codec ID
+        let decoder = unsafe {
+            let decoder = ffi::avcodec_find_decoder(codec.into());
+            if decoder.is_null() {
+                return Err(FFmpegError::DecoderNotFound);
+            }
+            decoder
+        };
+
+        // Create decoder context
+        let mut decoder_ctx =
+            unsafe { codec::context::Context::wrap(ffi::avcodec_alloc_context3(decoder), None) };
+        decoder_ctx.set_flags(codec::Flags::LOW_DELAY);
+
+        unsafe {
+            let ctx = decoder_ctx.as_mut_ptr();
+            (*ctx).sample_rate = sample_rate;
+            ffi::av_channel_layout_default(&raw mut (*ctx).ch_layout, channels);
+
+            // Freed with the context
+            if let Some(extradata) = extradata {
+                ((*ctx).extradata, (*ctx).extradata_size) = copy_extradata(extradata)?;
+            }
+        }
+
+        Ok(Self {
+            decoder: decoder_ctx.decoder().open()?.audio()?,
+        })
+    }
+
+    pub fn send_packet(&mut self, packet: &Packet) -> Result<(), FFmpegError> {
+        self.decoder.send_packet(packet).map_err(Into::into)
+    }
+
+    pub fn receive_frame(&mut self) -> Result<frame::Audio, FFmpegError> {
+        let mut frame = frame::Audio::empty();
+        self.decoder.receive_frame(&mut frame)?;
+
+        Ok(frame)
+    }
+}
@@ -0,0 +1,6 @@
+//! Audio decoding implementation
+
+#![allow(unsafe_code)]
+
+pub mod decoder;
+pub mod resampler;
@@ -0,0 +1,82 @@
+use ffmpeg_next::software::resampling;
+use ffmpeg_next::util::format;
+use ffmpeg_next::{ChannelLayout, frame};
+
+use crate::transcoding::error::FFmpegError;
+
+/// Converts decoded audio frames into interleaved `f32` samples of a fixed format.
+pub struct AudioResampler {
+    /// Resampling context, created from the first frame format
+    context: Option<resampling::Context>,
+    /// Output sample rate in Hz
+    sample_rate: u32,
+    /// Output channel layout
+    layout: ChannelLayout,
+    /// Output frame, reused between frames
+    output: frame::Audio,
+    /// Allocated output frame samples count
+    capacity: usize,
+}
+
+impl AudioResampler {
+    /// Output sample format
+    const FORMAT: format::Sample = format::Sample::F32(format::sample::Type::Packed);
+
+    /// Creates a new resampler.
+    ///
+    /// # Arguments
+    ///
+    /// - `sample_rate`: Output sample rate in Hz.
+    /// - `channels`: Output channel count.
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            context: None,
+            sample_rate,
+            layout: ChannelLayout::default(channels.into()),
+            output: frame::Audio::empty(),
+            capacity: 0,
+        }
+    }
+
+    /// Converts a decoded frame, returning its interleaved samples.
+    ///
+    /// The conversion context is recreated when the input format changes.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn run(&mut self, input: &frame::Audio) -> Result<&[f32], FFmpegError> {
+        let definition = resampling::context::Definition {
+            format: input.format(),
+            channel_layout: input.channel_layout(),
+            rate: input.rate(),
+        };
+
+        let context = match &mut self.context {
+            Some(context) if *context.input() == definition => context,
+            context => context.insert(resampling::Context::get(
+                definition.format,
+                definition.channel_layout,
+                definition.rate,
+                Self::FORMAT,
+                self.layout,
+                self.sample_rate,
+            )?),
+        };
+
+        // Room for the whole converted frame, plus the samples buffered by the previous one.
+        let capacity = (input.samples() as u64 * u64::from(self.sample_rate))
+            .div_ceil(u64::from(input.rate().max(1))) as usize
+            + 256;
+        if self.capacity < capacity {
+            self.output = frame::Audio::new(Self::FORMAT, capacity, self.layout);
+            self.capacity = capacity;
+        }
+        // The conversion shrinks the frame to the converted samples count.
+        self.output.set_samples(self.capacity);
+
+        context.run(input, &mut self.output)?;
+
+        let len = self.output.samples() * self.layout.channels() as usize;
+        let data = &self.output.data(0)[..len * size_of::<f32>()];
+
+        Ok(bytemuck::cast_slice(data))
+    }
+}
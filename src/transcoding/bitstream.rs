@@ -0,0 +1,95 @@
+//! Bitstream filtering of encoded packets
+
+#![allow(unsafe_code)]
+
+use std::ffi::CString;
+use std::ptr;
+
+use ffmpeg_next::packet::Mut;
+use ffmpeg_next::{Packet, Rational, codec, ffi};
+
+use crate::transcoding::error::FFmpegError;
+
+/// Rewrites encoded packets without decoding them, such as converting their NAL framing.
+pub struct BitstreamFilter {
+    context: *mut ffi::AVBSFContext,
+}
+
+// The context is only accessed through `&mut self`.
+unsafe impl Send for BitstreamFilter {}
+
+impl BitstreamFilter {
+    /// Creates a filter for a stream.
+    ///
+    /// # Arguments
+    /// - `name`: `FFmpeg` filter name, such as `h264_mp4toannexb`.
+    /// - `parameters`: Filtered stream parameters.
+    /// - `time_base`: Filtered packets time base.
+    pub fn new(
+        name: &str,
+        parameters: &codec::Parameters,
+        time_base: Rational,
+    ) -> Result<Self, FFmpegError> {
+        let name = CString::new(name).map_err(|_| FFmpegError::FormatNotSupported)?;
+
+        unsafe {
+            let filter = ffi::av_bsf_get_by_name(name.as_ptr());
+            if filter.is_null() {
+                return Err(ffmpeg_next::Error::BsfNotFound.into());
+            }
+
+            let mut context = ptr::null_mut();
+            check(ffi::av_bsf_alloc(filter, &raw mut context))?;
+            // Freed on drop, including on the following errors
+            let bsf = Self { context };
+
+            check(ffi::avcodec_parameters_copy(
+                (*bsf.context).par_in,
+                parameters.as_ptr(),
+            ))?;
+            (*bsf.context).time_base_in = time_base.into();
+            check(ffi::av_bsf_init(bsf.context))?;
+
+            Ok(bsf)
+        }
+    }
+
+    /// Output stream parameters, valid while the filter lives.
+    pub const fn parameters_out(&self) -> *const ffi::AVCodecParameters {
+        unsafe { (*self.context).par_out }
+    }
+
+    /// Queues a packet to filter, taking its data.
+    pub fn send(&mut self, packet: &mut Packet) -> Result<(), FFmpegError> {
+        unsafe { check(ffi::av_bsf_send_packet(self.context, packet.as_mut_ptr())) }
+    }
+
+    /// Returns the next filtered packet.
+    ///
+    /// Fails with [`ffmpeg_next::Error::Other`] `EAGAIN` when more input is needed.
+    pub fn receive(&mut self) -> Result<Packet, FFmpegError> {
+        let mut packet = Packet::empty();
+        unsafe {
+            check(ffi::av_bsf_receive_packet(
+                self.context,
+                packet.as_mut_ptr(),
+            ))?
+        };
+
+        Ok(packet)
+    }
+}
+
+impl Drop for BitstreamFilter {
+    fn drop(&mut self) {
+        unsafe { ffi::av_bsf_free(&raw mut self.context) };
+    }
+}
+
+/// Maps a negative `FFmpeg` return code to an error.
+fn check(code: i32) -> Result<(), FFmpegError> {
+    if code < 0 {
+        return Err(ffmpeg_next::Error::from(code).into());
+    }
+    Ok(())
+}
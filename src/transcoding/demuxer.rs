@@ -0,0 +1,170 @@
+//! Encoded video packets demuxing from a container or raw stream file
+
+#![allow(unsafe_code)]
+
+use std::path::Path;
+use std::slice;
+
+use ffmpeg_next::util::error::EAGAIN;
+use ffmpeg_next::{Packet, Rational, codec, format, media};
+
+use crate::transcoding::bitstream::BitstreamFilter;
+use crate::transcoding::error::FFmpegError;
+use crate::transcoding::muxer::StreamParameters;
+
+/// Reads the video packets of a file, framed as a scrcpy video stream.
+///
+/// Packets are converted to the Annex-B framing when stored length prefixed, as in MP4 and
+/// Matroska files, and their timestamps are rescaled to microseconds.
+pub struct Demuxer {
+    input: format::context::Input,
+    /// Demuxed stream index
+    stream: usize,
+    /// Time base of the demuxed stream
+    time_base: Rational,
+    /// Nominal frame duration in microseconds
+    frame_duration: i64,
+    /// Next timestamp of raw streams, in microseconds
+    next_pts: i64,
+    /// NAL framing conversion, for H.264 and H.265
+    filter: Option<BitstreamFilter>,
+    parameters: StreamParameters,
+}
+
+impl Demuxer {
+    /// Packets time base: scrcpy timestamps are in microseconds.
+    const TIME_BASE: Rational = Rational(1, 1_000_000);
+    /// Frame rate assumed by `FFmpeg` for raw streams.
+    const DEFAULT_FRAME_RATE: Rational = Rational(25, 1);
+
+    /// Opens a file and selects its best video stream.
+    ///
+    /// # Arguments
+    /// - `path`: Container or raw stream file path.
+    pub fn open(path: &Path) -> Result<Self, FFmpegError> {
+        let input = format::input(path)?;
+
+        let stream = input
+            .streams()
+            .best(media::Type::Video)
+            .ok_or(FFmpegError::FormatNotSupported)?;
+        let index = stream.index();
+        let time_base = stream.time_base();
+        let frame_rate = match stream.avg_frame_rate() {
+            rate if rate.numerator() > 0 && rate.denominator() > 0 => rate,
+            _ => Self::DEFAULT_FRAME_RATE,
+        };
+
+        let parameters = stream.parameters();
+        let codec = parameters.id();
+        let filter = match codec {
+            codec::Id::H264 => Some("h264_mp4toannexb"),
+            codec::Id::H265 => Some("hevc_mp4toannexb"),
+            _ => None,
+        }
+        .map(|name| BitstreamFilter::new(name, &parameters, time_base))
+        .transpose()?;
+
+        let (width, height, extradata) = unsafe {
+            // The filter rewrites the codec header to its output framing.
+            let par = match &filter {
+                Some(filter) => filter.parameters_out(),
+                None => parameters.as_ptr(),
+            };
+            let size = usize::try_from((*par).extradata_size).unwrap_or_default();
+            let extradata = (!(*par).extradata.is_null() && size > 0)
+                .then(|| slice::from_raw_parts((*par).extradata, size).to_vec());
+            ((*par).width, (*par).height, extradata)
+        };
+
+        Ok(Self {
+            input,
+            stream: index,
+            time_base,
+            frame_duration: i64::from(frame_rate.denominator()) * 1_000_000
+                / i64::from(frame_rate.numerator()),
+            next_pts: 0,
+            filter,
+            parameters: StreamParameters::Video {
+                codec,
+                width,
+                height,
+                extradata: extradata.map(|data| Self::codec_header(codec, data)),
+            },
+        })
+    }
+
+    /// Demuxed stream description, with its Annex-B codec header.
+    pub const fn parameters(&self) -> &StreamParameters {
+        &self.parameters
+    }
+
+    /// Nominal frame duration in microseconds
+    pub const fn frame_duration(&self) -> i64 {
+        self.frame_duration
+    }
+
+    /// Seeks back to the start of the file.
+    pub fn rewind(&mut self) -> Result<(), FFmpegError> {
+        self.input.seek(0, ..)?;
+        self.next_pts = 0;
+        Ok(())
+    }
+
+    /// Reads the next packet, with timestamps in microseconds.
+    ///
+    /// Returns `None` at the end of the file.
+    pub fn read(&mut self) -> Result<Option<Packet>, FFmpegError> {
+        loop {
+            if let Some(filter) = &mut self.filter {
+                match filter.receive() {
+                    Ok(packet) => return Ok(Some(self.rescale(packet))),
+                    Err(FFmpegError::InternalError(ffmpeg_next::Error::Other { errno }))
+                        if errno == EAGAIN => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            let mut packet = Packet::empty();
+            match packet.read(&mut self.input) {
+                Ok(()) => {}
+                Err(ffmpeg_next::Error::Eof) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+            if packet.stream() != self.stream {
+                continue;
+            }
+
+            match &mut self.filter {
+                Some(filter) => filter.send(&mut packet)?,
+                None => return Ok(Some(self.rescale(packet))),
+            }
+        }
+    }
+
+    /// Rescales the packet timestamps to microseconds, generating them for raw streams.
+    fn rescale(&mut self, mut packet: Packet) -> Packet {
+        packet.rescale_ts(self.time_base, Self::TIME_BASE);
+
+        let pts = packet
+            .pts()
+            .or_else(|| packet.dts())
+            .unwrap_or(self.next_pts);
+        self.next_pts = pts + self.frame_duration;
+
+        packet.set_pts(Some(pts));
+        packet.set_dts(Some(pts));
+        packet
+    }
+
+    /// Converts a codec header to the form sent in scrcpy config packets.
+    fn codec_header(codec: codec::Id, data: Vec<u8>) -> Vec<u8> {
+        match codec {
+            // The `av1C` header precedes the configuration OBUs.
+            codec::Id::AV1 if data.first().is_some_and(|byte| byte & 0x80 != 0) => {
+                data.get(4..).unwrap_or_default().to_vec()
+            }
+            _ => data,
+        }
+    }
+}
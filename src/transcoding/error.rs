@@ -18,6 +18,9 @@ pub enum FFmpegError {
     HWDeviceConstraintsUnavailable,
     HWFramesContextCreationError,
     HWFramesContextMissing,
+    ExtradataAllocationError,
+    /// Unknown option or invalid value, by option name
+    InvalidOption(String, i32),
     // GPUDecodingFailed,
     // ToHWBufferError(i32),
     // PixelFormatNotSupported((format::Pixel, Vec<format::Pixel>, Option<format::Pixel>)),
@@ -76,6 +79,14 @@ impl fmt::Display for FFmpegError {
             Self::HWDeviceConstraintsUnavailable => "HW device constraints are unavailable",
             Self::HWFramesContextCreationError => "HW frames context creation error",
             Self::HWFramesContextMissing => "HW frames context missing",
+            Self::ExtradataAllocationError => "Codec extradata allocation error",
+            Self::InvalidOption(key, i) => {
+                return write!(
+                    f,
+                    "Invalid option \"{key}\": {}",
+                    ffmpeg_next::Error::from(*i)
+                );
+            }
             // Self::UnknownPixelFormat(v) => return write!(f, "Unknown pixel format: {v}"),
             // Self::PixelFormatNotSupported(v) => {
             //     return write!(
@@ -1,11 +1,15 @@
 use std::ffi::{CStr, CString};
 use std::ptr;
 
+use ffmpeg_next::util::error::ENOMEM;
 use ffmpeg_next::{codec, ffi};
 
 use crate::transcoding::error::FFmpegError;
 use crate::transcoding::ffmpeg_result;
 use crate::transcoding::hw::device_index::HWDeviceIndex;
+use crate::transcoding::hw::vulkan::{
+    AVVulkanDeviceContext, VulkanDeviceDescription, VulkanDeviceHandles,
+};
 
 /// Instantiated hardware device for video transcoding
 #[derive(Debug)]
@@ -25,6 +29,9 @@ pub struct HWDevice {
     pub min_size: (i32, i32),
     /// Maximum size supported (width, height)
     pub max_size: (i32, i32),
+
+    /// Description of the external Vulkan device, referenced by the device context
+    vulkan: Option<Box<VulkanDeviceDescription>>,
 }
 
 unsafe impl Sync for HWDevice {}
@@ -52,9 +59,48 @@ impl HWDevice {
             )}; HWDeviceCreateError);
         assert!(!device_ref.is_null(), "Device reference is null");
 
+        Self::from_device_ref(index, device_ref, device_type)
+    }
+
+    /// Creates a Vulkan device over an existing one, such as the renderer device.
+    ///
+    /// The decoded frames then belong to the given device, and can be copied by it without leaving the GPU.
+    pub fn from_vulkan(handles: VulkanDeviceHandles) -> Result<Self, FFmpegError> {
+        let device_type = ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN;
+        let mut description = VulkanDeviceDescription::new(handles);
+
+        let mut device_ref = unsafe { ffi::av_hwdevice_ctx_alloc(device_type) };
+        if device_ref.is_null() {
+            return Err(FFmpegError::HWDeviceCreateError(ffi::AVERROR(ENOMEM)));
+        }
+
+        let err = unsafe {
+            let device_ctx = (*device_ref).data.cast::<ffi::AVHWDeviceContext>();
+            description.apply(&mut *(*device_ctx).hwctx.cast::<AVVulkanDeviceContext>());
+
+            ffi::av_hwdevice_ctx_init(device_ref)
+        };
+        if err < 0 {
+            unsafe { ffi::av_buffer_unref(&raw mut device_ref) };
+            return Err(FFmpegError::HWDeviceCreateError(err));
+        }
+
+        let mut device = Self::from_device_ref(HWDeviceIndex::Global, device_ref, device_type)?;
+        device.vulkan = Some(description);
+
+        Ok(device)
+    }
+
+    /// Reads the constraints of an initialized device context, taking its ownership.
+    fn from_device_ref(
+        index: HWDeviceIndex,
+        mut device_ref: *mut ffi::AVBufferRef,
+        device_type: ffi::AVHWDeviceType,
+    ) -> Result<Self, FFmpegError> {
         let mut constraints_ref =
             unsafe { ffi::av_hwdevice_get_hwframe_constraints(device_ref, ptr::null()) };
         if constraints_ref.is_null() {
+            unsafe { ffi::av_buffer_unref(&raw mut device_ref) };
             return Err(FFmpegError::HWDeviceConstraintsUnavailable);
         }
 
@@ -70,6 +116,7 @@ impl HWDevice {
             },
             min_size: unsafe { ((*constraints_ref).min_width, (*constraints_ref).min_height) },
             max_size: unsafe { ((*constraints_ref).max_width, (*constraints_ref).max_height) },
+            vulkan: None,
         };
         let _: () = unsafe { ffi::av_hwframe_constraints_free(&raw mut constraints_ref) };
 
@@ -87,10 +134,13 @@ impl HWDevice {
 
     /// Attaches the hardware device to a transcoder context using `hw_frames_ctx`.
     ///
+    /// Decoders allocate their frames from the device once the stream is parsed, with its
+    /// software format (NV12 for 8-bit, P010 for 10-bit streams). Encoders get a frames context
+    /// of their `sw_pix_fmt`, NV12 if unset.
+    ///
     /// # Arguments
     ///
     /// - `ctx`: The transcoder.
-    /// - `pixel_format`: The sw pixel format.
     /// - `size`: The size of video to process in pixels (width, height).
     pub fn bind_transcoder(
         &self,
@@ -103,11 +153,13 @@ impl HWDevice {
         unsafe {
             let ctx_ref = ctx.as_mut_ptr();
 
-            // todo: choose sw format ?
-            // let sw_pixel_format = ffi::avcodec_default_get_format(ctx_ref, &hw_pixel_format);
-            let sw_pixel_format = ffi::AVPixelFormat::AV_PIX_FMT_NV12;
+            let sw_pixel_format = match (*ctx_ref).sw_pix_fmt {
+                ffi::AVPixelFormat::AV_PIX_FMT_NONE => ffi::AVPixelFormat::AV_PIX_FMT_NV12,
+                sw_pixel_format => sw_pixel_format,
+            };
+            let is_decoder = ffi::av_codec_is_decoder((*ctx_ref).codec) != 0;
 
-            if (*ctx_ref).hw_frames_ctx.is_null() {
+            if !is_decoder && (*ctx_ref).hw_frames_ctx.is_null() {
                 let device_ref = ffi::av_buffer_ref(self.device_ref);
                 let mut frames_ctx_ref = ffi::av_hwframe_ctx_alloc(device_ref);
                 if frames_ctx_ref.is_null() {
@@ -144,32 +196,15 @@ impl HWDevice {
                 (*ctx_ref).hw_frames_ctx = frames_ctx_ref;
             }
 
-            let frames_ctx_ref = (*ctx_ref).hw_frames_ctx;
-            let hw_frames_ctx_ref = (*frames_ctx_ref).data.cast::<ffi::AVHWFramesContext>();
-
-            // if self.device_type == ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN {
-            //     let vulkan_frames_ctx_ref =
-            //         (*hw_frames_ctx_ref).hwctx.cast::<VulkanFramesContext>();
-            //
-            //     (*vulkan_frames_ctx_ref).tiling = ImageTiling::LINEAR;
-            //     (*vulkan_frames_ctx_ref).format = [
-            //         vk::Format::R4G4_UNORM_PACK8,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //         vk::Format::UNDEFINED,
-            //     ];
-            //     (*vulkan_frames_ctx_ref).flags = VkFrameFlags::None;
-            // }
-
-            // info!("frames_ctx.format: {:?}", (*hw_frames_ctx).format);
-            // info!("frames_ctx.sw_format: {:?}", (*hw_frames_ctx).sw_format);
-
             // Apply the parameters to the transcoder context.
-            (*ctx_ref).pix_fmt = (*hw_frames_ctx_ref).format;
+            (*ctx_ref).pix_fmt = if (*ctx_ref).hw_frames_ctx.is_null() {
+                hw_pixel_format
+            } else {
+                (*(*(*ctx_ref).hw_frames_ctx)
+                    .data
+                    .cast::<ffi::AVHWFramesContext>())
+                .format
+            };
             if !(*ctx_ref).hw_device_ctx.is_null() {
                 ffi::av_buffer_unref(&raw mut (*ctx_ref).hw_device_ctx);
             }
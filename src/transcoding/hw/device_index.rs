@@ -1,3 +1,5 @@
+use std::fmt;
+
 /// Physical hardware device identifier
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum HWDeviceIndex {
@@ -12,3 +14,12 @@ impl From<u8> for HWDeviceIndex {
         Self::Index(index)
     }
 }
+
+impl fmt::Display for HWDeviceIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => "global".fmt(f),
+            Self::Index(index) => write!(f, "#{index}"),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::LazyLock;
+use std::sync::OnceLock;
 
 use ffmpeg_next::ffi;
 
@@ -6,6 +6,11 @@ use crate::transcoding::hw::device::HWDevice;
 use crate::transcoding::hw::device_index::HWDeviceIndex;
 use crate::transcoding::hw::device_type::HWDeviceType;
 
+/// Vulkan device shared with the renderer
+static SHARED: OnceLock<HWDevice> = OnceLock::new();
+/// Probed devices, initialized by the first access
+static POOL: OnceLock<HWDevicePool> = OnceLock::new();
+
 /// Pool of hardware devices containing only valid hardware devices and ready to use
 #[derive(Debug)]
 pub struct HWDevicePool {
@@ -14,9 +19,9 @@ pub struct HWDevicePool {
 }
 
 impl HWDevicePool {
-    /// All the supported hw devices
+    /// All the supported hw devices. Blocks while the devices are probed.
     pub fn all() -> &'static Self {
-        static POOL: LazyLock<HWDevicePool> = LazyLock::new(|| {
+        POOL.get_or_init(|| {
             /// Maximum number of devices to check for
             const DEVICES_COUNT: u8 = 4;
 
@@ -32,13 +37,50 @@ impl HWDevicePool {
                 interfaces.push((index, devices));
             }
 
-            HWDevicePool {
+            Self {
                 global,
                 devices: interfaces.into_boxed_slice(),
             }
-        });
+        })
+    }
+
+    /// All the supported hw devices, `None` until they are probed.
+    pub fn probed() -> Option<&'static Self> {
+        POOL.get()
+    }
+
+    /// Registers the Vulkan device shared with the renderer. Ignored once set.
+    pub fn set_shared(device: HWDevice) {
+        if SHARED.set(device).is_err() {
+            warn!("A shared hardware device is already registered");
+        }
+    }
 
-        &POOL
+    /// Vulkan device shared with the renderer, its frames are displayed without a copy.
+    pub fn shared() -> Option<&'static HWDevice> {
+        SHARED.get()
+    }
+
+    /// All the devices, global ones first.
+    pub fn devices(&self) -> impl Iterator<Item = &HWDevice> {
+        self.global
+            .iter()
+            .chain(self.devices.iter().flat_map(|(_, devices)| devices.iter()))
+    }
+
+    /// Get the [`HWDevice`] of a device type on a physical interface.
+    ///
+    /// # Arguments
+    ///
+    /// - `index`: The physical interface of the device.
+    /// - `device_type`: The device type to find.
+    pub fn find(
+        index: HWDeviceIndex,
+        device_type: ffi::AVHWDeviceType,
+    ) -> Option<&'static HWDevice> {
+        Self::all()
+            .devices()
+            .find(|d| d.device_index() == index && d.device_type() == device_type)
     }
 
     /// Get the first [`HWDevice`] satisfying the device type.
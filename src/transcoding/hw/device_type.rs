@@ -91,6 +91,18 @@ impl HWDeviceType {
         self.display_name
     }
 
+    /// Display name of a device type, "unknown" if it is not available.
+    ///
+    /// # Arguments
+    ///
+    /// - `device_type`: The device type to name.
+    pub fn name_of(device_type: ffi::AVHWDeviceType) -> &'static str {
+        Self::all()
+            .iter()
+            .find(|dt| dt.device_type == device_type)
+            .map_or("unknown", Self::display_name)
+    }
+
     /// Whether the device type is available.
     ///
     /// # Arguments
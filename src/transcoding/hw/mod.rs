@@ -6,3 +6,4 @@ pub mod device;
 pub mod device_index;
 pub mod device_pool;
 pub mod device_type;
+pub mod vulkan;
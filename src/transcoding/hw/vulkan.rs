@@ -0,0 +1,156 @@
+//! `FFmpeg` Vulkan device context over an existing Vulkan device
+//!
+//! `hwcontext_vulkan.h` is not part of the generated bindings, the structures are mirrored
+//! from `FFmpeg` 7.1. They are only accessed when the loaded libavutil has the same layout, see
+//! [`is_layout_supported`].
+
+use std::ffi::{CStr, c_char, c_int};
+use std::ptr;
+
+use ash::vk;
+use ffmpeg_next::ffi;
+
+/// libavutil version (major, minor) of `FFmpeg` 7.1, the structures are mirrored from
+const LAYOUT_VERSION: (u32, u32) = (59, 39);
+
+/// Whether the loaded libavutil matches the mirrored Vulkan structures.
+///
+/// The library may be another version than the bindings, sharing Vulkan devices and frames
+/// with `FFmpeg` must be disabled otherwise.
+pub fn is_layout_supported() -> bool {
+    avutil_version() == LAYOUT_VERSION
+}
+
+/// Loaded libavutil version (major, minor)
+pub fn avutil_version() -> (u32, u32) {
+    let version = ffmpeg_next::util::version();
+    (version >> 16, (version >> 8) & 0xff)
+}
+
+/// `AVVulkanDeviceQueueFamily`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AVVulkanDeviceQueueFamily {
+    /// Queue family index
+    pub idx: c_int,
+    /// Number of queues usable by `FFmpeg`, starting at index 0
+    pub num: c_int,
+    pub flags: vk::QueueFlags,
+    /// Video codec operations supported by the queue family
+    pub video_caps: vk::VideoCodecOperationFlagsKHR,
+}
+
+/// `AVVulkanDeviceContext`
+#[repr(C)]
+pub struct AVVulkanDeviceContext {
+    pub alloc: *const vk::AllocationCallbacks<'static>,
+    pub get_proc_addr: Option<vk::PFN_vkGetInstanceProcAddr>,
+    pub inst: vk::Instance,
+    pub phys_dev: vk::PhysicalDevice,
+    pub act_dev: vk::Device,
+    pub device_features: vk::PhysicalDeviceFeatures2<'static>,
+    pub enabled_inst_extensions: *const *const c_char,
+    pub nb_enabled_inst_extensions: c_int,
+    pub enabled_dev_extensions: *const *const c_char,
+    pub nb_enabled_dev_extensions: c_int,
+    // Deprecated fixed queues, superseded by `qf`
+    pub queue_family_index: c_int,
+    pub nb_graphics_queues: c_int,
+    pub queue_family_tx_index: c_int,
+    pub nb_tx_queues: c_int,
+    pub queue_family_comp_index: c_int,
+    pub nb_comp_queues: c_int,
+    pub queue_family_encode_index: c_int,
+    pub nb_encode_queues: c_int,
+    pub queue_family_decode_index: c_int,
+    pub nb_decode_queues: c_int,
+    pub lock_queue: Option<unsafe extern "C" fn(*mut ffi::AVHWDeviceContext, u32, u32)>,
+    pub unlock_queue: Option<unsafe extern "C" fn(*mut ffi::AVHWDeviceContext, u32, u32)>,
+    pub qf: [AVVulkanDeviceQueueFamily; 64],
+    pub nb_qf: c_int,
+}
+
+/// Handles of a Vulkan device created outside `FFmpeg`
+#[derive(Debug, Clone)]
+pub struct VulkanDeviceHandles {
+    pub get_proc_addr: vk::PFN_vkGetInstanceProcAddr,
+    pub instance: vk::Instance,
+    pub physical_device: vk::PhysicalDevice,
+    pub device: vk::Device,
+    /// Enabled core features
+    pub features: vk::PhysicalDeviceFeatures,
+    pub instance_extensions: Vec<&'static CStr>,
+    pub device_extensions: Vec<&'static CStr>,
+    /// Queues reserved to `FFmpeg`. They must not be used by the device owner.
+    pub queue_families: Vec<AVVulkanDeviceQueueFamily>,
+}
+
+/// Device description referenced by an `FFmpeg` device context, to be kept alive with it.
+#[derive(Debug)]
+pub struct VulkanDeviceDescription {
+    handles: VulkanDeviceHandles,
+    instance_extensions: Box<[*const c_char]>,
+    device_extensions: Box<[*const c_char]>,
+    vulkan_1_2: vk::PhysicalDeviceVulkan12Features<'static>,
+    vulkan_1_3: vk::PhysicalDeviceVulkan13Features<'static>,
+}
+
+impl VulkanDeviceDescription {
+    /// Describes a device created with Vulkan 1.3, timeline semaphores and synchronization2
+    /// enabled.
+    pub fn new(handles: VulkanDeviceHandles) -> Box<Self> {
+        let names = |extensions: &[&CStr]| extensions.iter().map(|name| name.as_ptr()).collect();
+
+        let mut description = Box::new(Self {
+            instance_extensions: names(&handles.instance_extensions),
+            device_extensions: names(&handles.device_extensions),
+            handles,
+            vulkan_1_2: vk::PhysicalDeviceVulkan12Features::default().timeline_semaphore(true),
+            vulkan_1_3: vk::PhysicalDeviceVulkan13Features::default().synchronization2(true),
+        });
+        // The features chain is boxed, its addresses are stable.
+        description.vulkan_1_2.p_next = (&raw mut description.vulkan_1_3).cast();
+
+        description
+    }
+
+    /// Fills a device context allocated by `av_hwdevice_ctx_alloc`.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    pub fn apply(&mut self, hwctx: &mut AVVulkanDeviceContext) {
+        let handles = &self.handles;
+
+        hwctx.alloc = ptr::null();
+        hwctx.get_proc_addr = Some(handles.get_proc_addr);
+        hwctx.inst = handles.instance;
+        hwctx.phys_dev = handles.physical_device;
+        hwctx.act_dev = handles.device;
+
+        hwctx.device_features = vk::PhysicalDeviceFeatures2::default().features(handles.features);
+        hwctx.device_features.p_next = (&raw mut self.vulkan_1_2).cast();
+
+        hwctx.enabled_inst_extensions = self.instance_extensions.as_ptr();
+        hwctx.nb_enabled_inst_extensions = self.instance_extensions.len() as c_int;
+        hwctx.enabled_dev_extensions = self.device_extensions.as_ptr();
+        hwctx.nb_enabled_dev_extensions = self.device_extensions.len() as c_int;
+
+        let find = |flags: vk::QueueFlags| {
+            handles
+                .queue_families
+                .iter()
+                .find(|family| family.flags.contains(flags))
+                .map_or((-1, 0), |family| (family.idx, family.num))
+        };
+        (hwctx.queue_family_index, hwctx.nb_graphics_queues) = find(vk::QueueFlags::GRAPHICS);
+        (hwctx.queue_family_tx_index, hwctx.nb_tx_queues) = find(vk::QueueFlags::TRANSFER);
+        (hwctx.queue_family_comp_index, hwctx.nb_comp_queues) = find(vk::QueueFlags::COMPUTE);
+        (hwctx.queue_family_encode_index, hwctx.nb_encode_queues) =
+            find(vk::QueueFlags::VIDEO_ENCODE_KHR);
+        (hwctx.queue_family_decode_index, hwctx.nb_decode_queues) =
+            find(vk::QueueFlags::VIDEO_DECODE_KHR);
+
+        for (qf, family) in hwctx.qf.iter_mut().zip(&handles.queue_families) {
+            *qf = *family;
+        }
+        hwctx.nb_qf = handles.queue_families.len().min(hwctx.qf.len()) as c_int;
+    }
+}
@@ -1,7 +1,11 @@
 //! Low-level media processing wrapper
 
+pub mod audio;
+pub mod bitstream;
+pub mod demuxer;
 pub mod error;
 pub mod hw;
+pub mod muxer;
 // pub mod libavutil;
 pub mod video;
 
@@ -14,3 +18,24 @@ macro_rules! ffmpeg_result {
     };
 }
 use ffmpeg_result;
+
+/// Copies a codec specific header into an `FFmpeg` allocated buffer, with the required padding.
+///
+/// Returns the buffer and its size, to be owned by a codec context or parameters freeing it.
+#[allow(unsafe_code)]
+fn copy_extradata(data: &[u8]) -> Result<(*mut u8, i32), error::FFmpegError> {
+    /// `AV_INPUT_BUFFER_PADDING_SIZE`
+    const PADDING_SIZE: usize = 64;
+
+    let size = i32::try_from(data.len()).map_err(|_| error::FFmpegError::FormatNotSupported)?;
+
+    unsafe {
+        let buffer = ffmpeg_next::ffi::av_mallocz(data.len() + PADDING_SIZE).cast::<u8>();
+        if buffer.is_null() {
+            return Err(error::FFmpegError::ExtradataAllocationError);
+        }
+        std::ptr::copy_nonoverlapping(data.as_ptr(), buffer, data.len());
+
+        Ok((buffer, size))
+    }
+}
@@ -0,0 +1,145 @@
+//! Encoded packets muxing into a container file
+
+#![allow(unsafe_code)]
+
+use std::path::Path;
+
+use ffmpeg_next::{Packet, Rational, codec, ffi, format};
+
+use crate::transcoding::copy_extradata;
+use crate::transcoding::error::FFmpegError;
+
+/// Encoded stream description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamParameters {
+    Video {
+        codec: codec::Id,
+        width: i32,
+        height: i32,
+        /// Codec specific header
+        extradata: Option<Vec<u8>>,
+    },
+    Audio {
+        codec: codec::Id,
+        sample_rate: i32,
+        channels: i32,
+        /// Codec specific header
+        extradata: Option<Vec<u8>>,
+    },
+}
+
+impl StreamParameters {
+    /// Stream codec ID
+    pub const fn codec(&self) -> codec::Id {
+        match self {
+            Self::Video { codec, .. } | Self::Audio { codec, .. } => *codec,
+        }
+    }
+
+    /// Replaces the codec specific header.
+    pub fn set_extradata(&mut self, data: Vec<u8>) {
+        match self {
+            Self::Video { extradata, .. } | Self::Audio { extradata, .. } => {
+                *extradata = Some(data);
+            }
+        }
+    }
+}
+
+/// Writes already encoded packets into a container, without transcoding.
+pub struct Muxer {
+    output: format::context::Output,
+    /// Time base of each stream, chosen by the container
+    time_bases: Vec<Rational>,
+}
+
+impl Muxer {
+    /// Packets time base: scrcpy timestamps are in microseconds.
+    const TIME_BASE: Rational = Rational(1, 1_000_000);
+
+    /// Creates the file and writes the container header.
+    ///
+    /// # Arguments
+    /// - `path`: Output file path.
+    /// - `format`: Container short name, such as `mp4` or `matroska`.
+    /// - `streams`: Muxed streams, packets are written with their index.
+    pub fn create(
+        path: &Path,
+        format: &str,
+        streams: &[StreamParameters],
+    ) -> Result<Self, FFmpegError> {
+        let mut output = format::output_as(path, format)?;
+
+        for parameters in streams {
+            let mut stream = output.add_stream(None::<codec::Codec>)?;
+            stream.set_time_base(Self::TIME_BASE);
+
+            unsafe {
+                let par = (*stream.as_mut_ptr()).codecpar;
+
+                let extradata = match parameters {
+                    StreamParameters::Video {
+                        codec,
+                        width,
+                        height,
+                        extradata,
+                    } => {
+                        (*par).codec_type = ffi::AVMediaType::AVMEDIA_TYPE_VIDEO;
+                        (*par).codec_id = (*codec).into();
+                        (*par).width = *width;
+                        (*par).height = *height;
+                        extradata
+                    }
+                    StreamParameters::Audio {
+                        codec,
+                        sample_rate,
+                        channels,
+                        extradata,
+                    } => {
+                        (*par).codec_type = ffi::AVMediaType::AVMEDIA_TYPE_AUDIO;
+                        (*par).codec_id = (*codec).into();
+                        (*par).sample_rate = *sample_rate;
+                        ffi::av_channel_layout_default(&raw mut (*par).ch_layout, *channels);
+                        extradata
+                    }
+                };
+
+                // Freed with the stream parameters
+                if let Some(extradata) = extradata {
+                    ((*par).extradata, (*par).extradata_size) = copy_extradata(extradata)?;
+                }
+            }
+        }
+
+        output.write_header()?;
+
+        // The header may change the requested time bases.
+        let time_bases = output.streams().map(|stream| stream.time_base()).collect();
+
+        Ok(Self { output, time_bases })
+    }
+
+    /// Writes a packet with timestamps in microseconds.
+    ///
+    /// # Arguments
+    /// - `stream`: Stream index, in the creation order.
+    /// - `packet`: Encoded packet.
+    pub fn write(&mut self, stream: usize, mut packet: Packet) -> Result<(), FFmpegError> {
+        let time_base = *self
+            .time_bases
+            .get(stream)
+            .ok_or(FFmpegError::FormatNotSupported)?;
+
+        packet.set_stream(stream);
+        packet.rescale_ts(Self::TIME_BASE, time_base);
+        packet.write_interleaved(&mut self.output)?;
+
+        Ok(())
+    }
+
+    /// Flushes the interleaved packets and writes the container trailer.
+    pub fn finish(mut self) -> Result<(), FFmpegError> {
+        self.output.write_trailer()?;
+        Ok(())
+    }
+}
@@ -1,11 +1,50 @@
 #![allow(unsafe_code)]
 
-use ffmpeg_next::{Dictionary, Error, Packet, codec, decoder, ffi, frame, threading};
+use std::ffi::CString;
+use std::ptr;
+
+use ffmpeg_next::codec::discard::Discard;
+use ffmpeg_next::util::error::{EAGAIN, EINVAL};
+use ffmpeg_next::{Error, Packet, codec, decoder, ffi, frame, threading};
 
 use crate::transcoding::error::FFmpegError;
 use crate::transcoding::hw::device::HWDevice;
 use crate::transcoding::video::frame_buffer::FrameBuffer;
 
+/// `AV_OPT_SEARCH_CHILDREN`
+const AV_OPT_SEARCH_CHILDREN: i32 = 1;
+/// `AV_OPT_SEARCH_FAKE_OBJ`
+const AV_OPT_SEARCH_FAKE_OBJ: i32 = 1 << 1;
+
+/// Decoder tuning
+#[derive(Debug, Clone)]
+pub struct DecoderConfig {
+    /// Decoding threads. A count of 0 uses one thread per core.
+    pub threading: threading::Config,
+    /// Frames decoded without their loop filter
+    pub skip_loop_filter: Discard,
+    /// Codec context and decoder private options, by name
+    pub options: Vec<(String, String)>,
+    /// Hardware frames held after decoding, added to the fixed size frame pools (VAAPI, QSV)
+    /// sized for the decoded picture buffer only.
+    pub extra_hw_frames: usize,
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self {
+            // `threading::Type::Frame` increase delay by one frame per thread
+            threading: threading::Config {
+                kind: threading::Type::Slice,
+                count: 5,
+            },
+            skip_loop_filter: Discard::Default,
+            options: Vec::new(),
+            extra_hw_frames: 0,
+        }
+    }
+}
+
 pub struct VideoDecoder<'a> {
     /// Video decoder context
     pub decoder: decoder::Video,
@@ -14,17 +53,17 @@ pub struct VideoDecoder<'a> {
 }
 
 impl<'a> VideoDecoder<'a> {
-    /// Create a new video decoder from the specified codec ID and options.
+    /// Create a new video decoder from the specified codec ID and configuration.
     ///
     /// # Arguments
     /// - `codec`: The codec ID to use for decoding.
-    /// - `options`: A dictionary of options to configure the decoder.
+    /// - `config`: The decoder tuning. Unknown or invalid options are errors.
     /// - `hw_device`: An optional hardware device to use for hardware-accelerated decoding.
     ///   `(HWDevice, (width, height))` for hardware decoding,
     ///   `None` for software decoding.
     pub fn from_codec(
         codec: codec::Id,
-        options: &Dictionary<'_>,
+        config: &DecoderConfig,
         hw_device: Option<(&'a HWDevice, (i32, i32))>,
     ) -> Result<Self, FFmpegError> {
         // Find the decoder for the specified codec ID
@@ -39,37 +78,82 @@ impl<'a> VideoDecoder<'a> {
         // Create decoder context
         let mut decoder_ctx =
             unsafe { codec::context::Context::wrap(ffi::avcodec_alloc_context3(decoder), None) };
-        // `threading::Type::Frame` increase delay by one frame, do not use
-        decoder_ctx.set_threading(threading::Config {
-            kind: threading::Type::Slice,
-            count: 5,
-        });
-        decoder_ctx.set_flags(codec::Flags::LOW_DELAY);
-
-        // Apply decoder options
-        for (k, v) in options.iter() {
-            unsafe {
-                let k_cstr = std::ffi::CString::new(k).unwrap_or_default();
-                let v_cstr = std::ffi::CString::new(v).unwrap_or_default();
+        decoder_ctx.set_threading(config.threading);
+        // Low delay disables frame threading, it is only set without it.
+        if config.threading.kind == threading::Type::Slice {
+            decoder_ctx.set_flags(codec::Flags::LOW_DELAY);
+        }
+
+        // Apply decoder options, to the context or the decoder private data
+        for (key, value) in &config.options {
+            let invalid = |err| FFmpegError::InvalidOption(key.clone(), err);
+            let k_cstr = CString::new(key.as_str()).map_err(|_| invalid(ffi::AVERROR(EINVAL)))?;
+            let v_cstr = CString::new(value.as_str()).map_err(|_| invalid(ffi::AVERROR(EINVAL)))?;
+            let err = unsafe {
                 ffi::av_opt_set(
-                    (*decoder_ctx.as_mut_ptr()).priv_data,
+                    decoder_ctx.as_mut_ptr().cast(),
                     k_cstr.as_ptr(),
                     v_cstr.as_ptr(),
-                    0,
-                );
+                    AV_OPT_SEARCH_CHILDREN,
+                )
+            };
+            if err < 0 {
+                return Err(invalid(err));
             }
         }
 
         if let Some((hw_device, size)) = hw_device {
             hw_device.bind_transcoder(&mut decoder_ctx, size)?;
+            unsafe {
+                (*decoder_ctx.as_mut_ptr()).extra_hw_frames =
+                    i32::try_from(config.extra_hw_frames).unwrap_or(i32::MAX);
+            }
         }
 
+        let mut decoder = decoder_ctx.decoder();
+        decoder.skip_loop_filter(config.skip_loop_filter);
+
         Ok(Self {
-            decoder: decoder_ctx.decoder().open()?.video()?,
+            decoder: decoder.open()?.video()?,
             hw_device,
         })
     }
 
+    /// Whether an option exists for a decoder, in the codec context or its private options.
+    ///
+    /// # Arguments
+    /// - `codec`: The codec ID of the decoder.
+    /// - `key`: The option name.
+    pub fn has_option(codec: codec::Id, key: &str) -> bool {
+        let Ok(key) = CString::new(key) else {
+            return false;
+        };
+
+        unsafe {
+            let decoder = ffi::avcodec_find_decoder(codec.into());
+            let private = if decoder.is_null() {
+                ptr::null()
+            } else {
+                (*decoder).priv_class
+            };
+
+            [ffi::avcodec_get_class(), private]
+                .into_iter()
+                .filter(|class| !class.is_null())
+                .any(|class| {
+                    // Fake objects are pointers to their class.
+                    !ffi::av_opt_find(
+                        (&raw const class).cast_mut().cast(),
+                        key.as_ptr(),
+                        ptr::null(),
+                        0,
+                        AV_OPT_SEARCH_FAKE_OBJ,
+                    )
+                    .is_null()
+                })
+        }
+    }
+
     pub fn send_packet(&mut self, packet: &Packet) -> Result<(), FFmpegError> {
         self.decoder.send_packet(packet).map_err(Into::into)
     }
@@ -99,6 +183,9 @@ impl<'a> VideoDecoder<'a> {
         Ok(frame_buffer)
     }
 
+    /// Receives the decoded frames until the decoder needs another packet, or is flushed.
+    ///
+    /// Other errors are decoding failures.
     pub fn receive_frames<F>(&mut self, mut process_frame: F) -> Result<(), FFmpegError>
     where
         F: FnMut(FrameBuffer) -> Result<(), FFmpegError>,
@@ -106,7 +193,7 @@ impl<'a> VideoDecoder<'a> {
         loop {
             match self.receive_frame() {
                 Ok(frame_buffer) => process_frame(frame_buffer)?,
-                Err(FFmpegError::InternalError(Error::Eof | Error::Exit | Error::Other { .. })) => {
+                Err(FFmpegError::InternalError(Error::Eof | Error::Other { errno: EAGAIN })) => {
                     break;
                 }
                 Err(err) => return Err(err),
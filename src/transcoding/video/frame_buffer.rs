@@ -103,6 +103,16 @@ impl DerefMut for FrameBuffer {
     }
 }
 
+impl From<frame::Video> for FrameBuffer {
+    fn from(frame: frame::Video) -> Self {
+        if unsafe { (*frame.as_ptr()).hw_frames_ctx.is_null() } {
+            Self::Sw(frame)
+        } else {
+            Self::Hw(frame)
+        }
+    }
+}
+
 impl From<FrameBuffer> for frame::Video {
     fn from(frame: FrameBuffer) -> Self {
         match frame {
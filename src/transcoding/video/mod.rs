@@ -4,4 +4,6 @@
 
 pub mod decoder;
 pub mod frame_buffer;
-// pub mod vulkan;
+pub mod rgba;
+pub mod scaler;
+pub mod vulkan;
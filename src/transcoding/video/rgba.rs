@@ -0,0 +1,37 @@
+//! Video frame conversion to packed RGBA
+
+use ffmpeg_next::frame;
+use ffmpeg_next::software::scaling;
+use ffmpeg_next::util::format;
+
+use crate::transcoding::error::FFmpegError;
+
+/// Converts a software frame to tightly packed 8-bit RGBA pixels, at its native size.
+pub fn to_rgba(input: &frame::Video) -> Result<Vec<u8>, FFmpegError> {
+    let (width, height) = (input.width(), input.height());
+    if width == 0 || height == 0 {
+        return Err(FFmpegError::FrameEmpty);
+    }
+
+    let mut context = scaling::Context::get(
+        input.format(),
+        width,
+        height,
+        format::Pixel::RGBA,
+        width,
+        height,
+        scaling::Flags::BILINEAR,
+    )?;
+    let mut output = frame::Video::empty();
+    context.run(input, &mut output)?;
+
+    // Rows may be padded for alignment.
+    let row = width as usize * 4;
+    Ok(output
+        .data(0)
+        .chunks(output.stride(0))
+        .take(height as usize)
+        .flat_map(|line| &line[..row])
+        .copied()
+        .collect())
+}
@@ -0,0 +1,111 @@
+//! Pixel format conversion of software frames
+
+use ffmpeg_next::software::scaling;
+use ffmpeg_next::util::color;
+use ffmpeg_next::{format, frame};
+
+use crate::transcoding::error::FFmpegError;
+
+/// `AV_PIX_FMT_FLAG_RGB`
+const AV_PIX_FMT_FLAG_RGB: u64 = 1 << 5;
+
+/// Converts software frames to another pixel format, keeping their size.
+///
+/// The scaling context is reused while the input format and size do not change.
+/// The output colorimetry follows the swscale conversion: RGB inputs become BT.601 limited range
+/// YUV, full range YUVJ inputs become limited range.
+pub struct FrameScaler {
+    context: Option<scaling::Context>,
+    output: frame::Video,
+}
+
+// The context is only accessed through `&mut self`.
+unsafe impl Send for FrameScaler {}
+unsafe impl Sync for FrameScaler {}
+
+impl Default for FrameScaler {
+    fn default() -> Self {
+        Self {
+            context: None,
+            output: frame::Video::empty(),
+        }
+    }
+}
+
+impl FrameScaler {
+    /// Whether the samples of a format have more than 8 bits
+    pub fn is_high_depth(format: format::Pixel) -> bool {
+        format
+            .descriptor()
+            .is_some_and(|descriptor| unsafe { (*descriptor.as_ptr()).comp[0].depth > 8 })
+    }
+
+    /// Converts a frame. The output is valid until the next conversion.
+    pub fn run(
+        &mut self,
+        input: &frame::Video,
+        format: format::Pixel,
+    ) -> Result<&frame::Video, FFmpegError> {
+        let (width, height) = (input.width(), input.height());
+        if width == 0 || height == 0 {
+            return Err(FFmpegError::FrameEmpty);
+        }
+
+        let definition = scaling::Definition {
+            format: input.format(),
+            width,
+            height,
+        };
+        let context = match &mut self.context {
+            Some(context)
+                if *context.input() == definition && context.output().format == format =>
+            {
+                context
+            }
+            context => {
+                // The output frame is reallocated for the new definition.
+                self.output = frame::Video::empty();
+                context.insert(scaling::Context::get(
+                    input.format(),
+                    width,
+                    height,
+                    format,
+                    width,
+                    height,
+                    scaling::Flags::BILINEAR,
+                )?)
+            }
+        };
+        context.run(input, &mut self.output)?;
+
+        let output = &mut self.output;
+        output.set_pts(input.pts());
+        output.set_color_primaries(input.color_primaries());
+        output.set_color_transfer_characteristic(input.color_transfer_characteristic());
+
+        let (space, range) = if Self::is_rgb(input.format()) {
+            (color::Space::SMPTE170M, color::Range::MPEG)
+        } else if Self::is_full_range(input.format()) {
+            (input.color_space(), color::Range::MPEG)
+        } else {
+            (input.color_space(), input.color_range())
+        };
+        output.set_color_space(space);
+        output.set_color_range(range);
+
+        Ok(output)
+    }
+
+    fn is_rgb(format: format::Pixel) -> bool {
+        format.descriptor().is_some_and(|descriptor| unsafe {
+            (*descriptor.as_ptr()).flags & AV_PIX_FMT_FLAG_RGB != 0
+        })
+    }
+
+    /// YUVJ formats, full range by definition
+    fn is_full_range(format: format::Pixel) -> bool {
+        format
+            .descriptor()
+            .is_some_and(|descriptor| descriptor.name().starts_with("yuvj"))
+    }
+}
@@ -0,0 +1,168 @@
+//! Vulkan hardware frames access
+//!
+//! `hwcontext_vulkan.h` is not part of the generated bindings, the structures are mirrored
+//! from `FFmpeg` 7.1 and only accessed with a matching libavutil.
+
+use std::ffi::{c_int, c_void};
+
+use ash::vk;
+use ffmpeg_next::{ffi, format, frame};
+
+use crate::transcoding::hw::vulkan::{AVVulkanDeviceContext, is_layout_supported};
+
+/// `AV_NUM_DATA_POINTERS`
+const AV_NUM_DATA_POINTERS: usize = 8;
+
+/// `AVVulkanFramesContext`
+#[repr(C)]
+pub struct AVVulkanFramesContext {
+    pub tiling: vk::ImageTiling,
+    pub usage: vk::ImageUsageFlags,
+    pub create_pnext: *mut c_void,
+    pub alloc_pnext: [*mut c_void; AV_NUM_DATA_POINTERS],
+    /// `AVVkFrameFlags`
+    pub flags: c_int,
+    pub format: [vk::Format; AV_NUM_DATA_POINTERS],
+    pub img_flags: vk::ImageCreateFlags,
+    pub lock_frame: Option<unsafe extern "C" fn(*mut ffi::AVHWFramesContext, *mut AVVkFrame)>,
+    pub unlock_frame: Option<unsafe extern "C" fn(*mut ffi::AVHWFramesContext, *mut AVVkFrame)>,
+    pub nb_layers: c_int,
+}
+
+/// `AVVkFrame`
+#[repr(C)]
+pub struct AVVkFrame {
+    /// Image of each plane, a single multi-planar image for most formats
+    pub img: [vk::Image; AV_NUM_DATA_POINTERS],
+    pub tiling: vk::ImageTiling,
+    pub mem: [vk::DeviceMemory; AV_NUM_DATA_POINTERS],
+    pub size: [usize; AV_NUM_DATA_POINTERS],
+    pub flags: vk::MemoryPropertyFlags,
+    /// Last access of each image, to be updated after an external access
+    pub access: [vk::AccessFlags; AV_NUM_DATA_POINTERS],
+    /// Current layout of each image, to be updated after an external access
+    pub layout: [vk::ImageLayout; AV_NUM_DATA_POINTERS],
+    /// Timeline semaphore of each image, signaled when its last operation completes
+    pub sem: [vk::Semaphore; AV_NUM_DATA_POINTERS],
+    /// Value the semaphore reaches when the last operation completes
+    pub sem_value: [u64; AV_NUM_DATA_POINTERS],
+    pub internal: *mut c_void,
+    pub offset: [isize; AV_NUM_DATA_POINTERS],
+    pub queue_family: [u32; AV_NUM_DATA_POINTERS],
+}
+
+/// Vulkan image of a hardware frame
+pub struct VulkanFrame<'a> {
+    _frame: &'a frame::Video,
+    frames_ctx: *mut ffi::AVHWFramesContext,
+    vk_frame: *mut AVVkFrame,
+}
+
+impl<'a> VulkanFrame<'a> {
+    /// Vulkan view of a hardware frame. `None` for other frames, or if the libavutil structures
+    /// layout is not supported.
+    pub fn new(frame: &'a frame::Video) -> Option<Self> {
+        if frame.format() != format::Pixel::VULKAN || !is_layout_supported() {
+            return None;
+        }
+
+        unsafe {
+            let frame_ptr = frame.as_ptr();
+            let frames_ref = (*frame_ptr).hw_frames_ctx;
+            let vk_frame = (*frame_ptr).data[0].cast::<AVVkFrame>();
+            if frames_ref.is_null() || vk_frame.is_null() {
+                return None;
+            }
+
+            Some(Self {
+                _frame: frame,
+                frames_ctx: (*frames_ref).data.cast(),
+                vk_frame,
+            })
+        }
+    }
+
+    /// Device owning the image
+    pub fn device(&self) -> vk::Device {
+        unsafe {
+            let device_ctx = (*self.frames_ctx).device_ctx;
+            (*(*device_ctx).hwctx.cast::<AVVulkanDeviceContext>()).act_dev
+        }
+    }
+
+    /// Image size in pixels (width, height), it may be padded beyond the frame size.
+    #[allow(clippy::cast_sign_loss)]
+    pub fn image_size(&self) -> (u32, u32) {
+        unsafe {
+            (
+                (*self.frames_ctx).width as u32,
+                (*self.frames_ctx).height as u32,
+            )
+        }
+    }
+
+    /// Layout of the image planes
+    pub fn sw_format(&self) -> format::Pixel {
+        unsafe { (*self.frames_ctx).sw_format.into() }
+    }
+
+    /// Locks the frame for an external access, until the lock is dropped.
+    pub fn lock(&self) -> VulkanFrameLock<'_, 'a> {
+        unsafe {
+            let hwctx = (*self.frames_ctx).hwctx.cast::<AVVulkanFramesContext>();
+            if let Some(lock_frame) = (*hwctx).lock_frame {
+                lock_frame(self.frames_ctx, self.vk_frame);
+            }
+        }
+
+        VulkanFrameLock { frame: self }
+    }
+}
+
+/// External access to a Vulkan frame
+pub struct VulkanFrameLock<'l, 'a> {
+    frame: &'l VulkanFrame<'a>,
+}
+
+impl VulkanFrameLock<'_, '_> {
+    /// Multi-planar image of the frame. `None` if each plane has its own image.
+    pub fn image(&self) -> Option<vk::Image> {
+        let images = unsafe { &(*self.frame.vk_frame).img };
+        (images[1] == vk::Image::null()).then_some(images[0])
+    }
+
+    /// Timeline semaphore and the value it reaches once the frame is decoded.
+    pub fn semaphore(&self) -> (vk::Semaphore, u64) {
+        unsafe {
+            let vk_frame = &*self.frame.vk_frame;
+            (vk_frame.sem[0], vk_frame.sem_value[0])
+        }
+    }
+
+    /// Current layout of the image
+    pub fn layout(&self) -> vk::ImageLayout {
+        unsafe { (*self.frame.vk_frame).layout[0] }
+    }
+
+    /// Records the image state left by an external access.
+    pub fn set_layout(&self, layout: vk::ImageLayout, access: vk::AccessFlags) {
+        unsafe {
+            let vk_frame = &mut *self.frame.vk_frame;
+            vk_frame.layout[0] = layout;
+            vk_frame.access[0] = access;
+        }
+    }
+}
+
+impl Drop for VulkanFrameLock<'_, '_> {
+    fn drop(&mut self) {
+        unsafe {
+            let hwctx = (*self.frame.frames_ctx)
+                .hwctx
+                .cast::<AVVulkanFramesContext>();
+            if let Some(unlock_frame) = (*hwctx).unlock_frame {
+                unlock_frame(self.frame.frames_ctx, self.frame.vk_frame);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use eframe::egui::{
     Align, Button, Color32, CursorIcon, Frame, Grid, Layout, Response, RichText, ScrollArea, Sense,
     TextStyle, Ui, Vec2, ViewportId, vec2,
@@ -5,6 +7,8 @@ use eframe::egui::{
 use scrcpy_launcher::adb::server::{DeviceLong, DeviceState};
 
 use crate::core::msg::Msg;
+use crate::services::playback::PlaybackConfig;
+use crate::services::session::SessionConfig;
 use crate::ui::components::CtxWidget;
 use crate::ui::context::ViewContext;
 use crate::ui::pages::CurrentPage;
@@ -44,7 +48,9 @@ impl CtxWidget for DeviceList<'_> {
 
             ui.separator();
 
-            if self.devices.is_empty() {
+            let playback = ctx.data.session_settings.playback_config();
+
+            if self.devices.is_empty() && playback.is_none() {
                 ui.centered_and_justified(|ui| {
                     ui.label(
                         RichText::new("No devices found via ADB.")
@@ -58,6 +64,11 @@ impl CtxWidget for DeviceList<'_> {
                     .show(ui, |ui| {
                         ui.take_available_width();
 
+                        if let Some(config) = playback {
+                            ui.add_space(5.0);
+                            VirtualDevice::new(config).ui(ui, ctx);
+                        }
+
                         for device in self.devices {
                             ui.add_space(5.0);
                             Device::new(device).ui(ui, ctx);
@@ -167,17 +178,11 @@ impl CtxWidget for Device<'_> {
             let interaction = interaction.on_hover_cursor(CursorIcon::PointingHand);
 
             if interaction.clicked() {
-                let context = ui.ctx().clone();
-                let viewport_id = ViewportId::from_hash_of(self.device.identifier.as_str());
-
-                ctx.send(Msg::RequestStartSession(
-                    ctx.data.session_settings.to_config(
-                        self.device.identifier.clone(),
-                        Box::new(move || {
-                            context.request_repaint_of(viewport_id);
-                        }),
-                    ),
-                ));
+                ctx.send(Msg::RequestStartSession(session_config(
+                    ui,
+                    ctx,
+                    &self.device.identifier,
+                )));
             }
 
             if interaction.hovered() {
@@ -218,3 +223,141 @@ impl CtxWidget for Device<'_> {
         frame_response
     }
 }
+
+/// Recorded video played back as a device
+struct VirtualDevice {
+    config: PlaybackConfig,
+}
+
+impl VirtualDevice {
+    pub const fn new(config: PlaybackConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl CtxWidget for VirtualDevice {
+    fn ui(self, ui: &mut Ui, ctx: &mut ViewContext<'_>) -> Response {
+        let name = self.config.path.file_name().map_or_else(
+            || self.config.path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        let device_id = format!("file:{name}");
+
+        let frame_response = Frame::group(ui.style())
+            .inner_margin(10.0)
+            .corner_radius(6.0)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.take_available_width();
+
+                    ui.horizontal_centered(|ui| {
+                        ui.label(RichText::new("🎞").size(24.0));
+                    });
+
+                    ui.add_space(8.0);
+
+                    ui.vertical(|ui| {
+                        ui.label(RichText::new(&name).strong().text_style(TextStyle::Body));
+
+                        ui.horizontal(|ui| {
+                            let (rect, _) = ui.allocate_exact_size(vec2(6.0, 6.0), Sense::hover());
+                            ui.painter()
+                                .circle_filled(rect.center(), 3.0, Color32::LIGHT_BLUE);
+
+                            ui.label(
+                                RichText::new("Virtual Device")
+                                    .color(Color32::LIGHT_BLUE)
+                                    .text_style(TextStyle::Small),
+                            );
+
+                            ui.label(
+                                RichText::new("•")
+                                    .color(ui.visuals().weak_text_color())
+                                    .text_style(TextStyle::Small),
+                            );
+
+                            ui.label(
+                                RichText::new(self.config.path.display().to_string())
+                                    .monospace()
+                                    .color(ui.visuals().weak_text_color())
+                                    .text_style(TextStyle::Small),
+                            );
+                        });
+                    });
+                });
+            })
+            .response;
+
+        let interaction = ui
+            .interact(frame_response.rect, frame_response.id, Sense::click())
+            .on_hover_cursor(CursorIcon::PointingHand);
+
+        if interaction.clicked() {
+            // Played back videos have no control nor audio stream.
+            let mut config = session_config(ui, ctx, &device_id);
+            config.control = None;
+            config.audio = None;
+            config.playback = Some(self.config);
+
+            ctx.send(Msg::RequestStartSession(config));
+        }
+
+        if interaction.hovered() {
+            ui.painter().rect_filled(
+                frame_response.rect,
+                6.0,
+                ui.visuals().widgets.hovered.bg_fill.gamma_multiply(0.2),
+            );
+        }
+
+        frame_response
+    }
+}
+
+/// Creates a session configuration from the settings, reporting the session events.
+///
+/// # Arguments
+///
+/// - `ui`: Device list, repainting the session viewport on new frames.
+/// - `ctx`: View context, receiving the session events.
+/// - `device_id`: Session device serial.
+fn session_config(ui: &Ui, ctx: &ViewContext<'_>, device_id: &str) -> SessionConfig {
+    let context = ui.ctx().clone();
+    let viewport_id = ViewportId::from_hash_of(device_id);
+    let tx = ctx.sender();
+    let device_id = device_id.to_owned();
+
+    ctx.data.session_settings.to_config(
+        device_id.clone(),
+        Box::new(move || {
+            context.request_repaint_of(viewport_id);
+        }),
+        Box::new({
+            let tx = tx.clone();
+            let device_id = device_id.clone();
+            move |direction, size| {
+                let _ = tx.send(Msg::ClipboardSynced {
+                    device_id: device_id.clone(),
+                    direction,
+                    size,
+                });
+            }
+        }),
+        Arc::new({
+            let tx = tx.clone();
+            let device_id = device_id.clone();
+            move |path| {
+                let _ = tx.send(Msg::RecordingSaved {
+                    device_id: device_id.clone(),
+                    path,
+                });
+            }
+        }),
+        Box::new(move |reason| {
+            let _ = tx.send(Msg::DecoderFallback {
+                device_id: device_id.clone(),
+                reason,
+            });
+        }),
+    )
+}
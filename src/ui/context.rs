@@ -26,6 +26,11 @@ impl<'a> ViewContext<'a> {
     pub fn send(&self, msg: Msg) {
         let _ = self.tx.send(msg);
     }
+
+    /// Message sender, for callbacks outliving the view.
+    pub fn sender(&self) -> MsgSender {
+        self.tx.clone()
+    }
 }
 
 impl OwnedViewContext {
@@ -1,9 +1,17 @@
+use std::time::Duration;
+
 use eframe::egui;
 use eframe::egui::{
     Align, ComboBox, CursorIcon, Layout, RichText, Sense, Slider, Stroke, Ui, Vec2,
 };
 
-use crate::ui::pages::settings::state::{SessionSettings, VideoCodec};
+use crate::transcoding::hw::device_index::HWDeviceIndex;
+use crate::transcoding::hw::device_pool::HWDevicePool;
+use crate::transcoding::video::decoder::VideoDecoder;
+use crate::ui::pages::settings::state::{
+    AudioCodec, AudioSource, DecoderThreading, HwBackend, PlaybackPacing, Presentation,
+    RecordingFormat, SessionSettings, SkipLoopFilter, ToneMapping, VideoCodec,
+};
 
 pub struct VideoSection;
 impl VideoSection {
@@ -72,20 +80,176 @@ impl VideoSection {
                 ui.add_space(4.0);
 
                 switch_row(ui, "Hardware Decoding", &mut state.hw_decoder);
+
+                if state.hw_decoder {
+                    hw_device_row(ui, state);
+                }
+
+                combo_row(
+                    ui,
+                    "tone_mapping",
+                    "HDR Tone Mapping",
+                    &mut state.tone_mapping,
+                    vec![
+                        (ToneMapping::Hable, "Filmic"),
+                        (ToneMapping::Reinhard, "Reinhard"),
+                        (ToneMapping::Clip, "Clip"),
+                        (ToneMapping::Disabled, "Disabled"),
+                    ],
+                );
+
+                combo_row(
+                    ui,
+                    "presentation",
+                    "Presentation",
+                    &mut state.presentation,
+                    vec![
+                        (Presentation::LowestLatency, "Lowest Latency"),
+                        (Presentation::Smooth, "Smooth"),
+                        (Presentation::EveryFrame, "Every Frame"),
+                    ],
+                );
+            }
+        });
+    }
+}
+
+pub struct DecoderSection;
+impl DecoderSection {
+    pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
+        if !state.video_enabled {
+            return;
+        }
+
+        card(ui, "Decoder", |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Threads"));
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(Slider::new(&mut state.decoder_threads, 0..=16).show_value(false));
+                    let text = if state.decoder_threads == 0 {
+                        "Auto".to_string()
+                    } else {
+                        state.decoder_threads.to_string()
+                    };
+                    ui.add_sized([60.0, 30.0], egui::Label::new(text));
+                });
+            });
+            ui.add_space(4.0);
+
+            combo_row(
+                ui,
+                "decoder_threading",
+                "Threading",
+                &mut state.decoder_threading,
+                vec![
+                    (DecoderThreading::Slice, "Slice"),
+                    (DecoderThreading::Frame, "Frame (+ latency)"),
+                ],
+            );
+
+            combo_row(
+                ui,
+                "skip_loop_filter",
+                "Skip Loop Filter",
+                &mut state.skip_loop_filter,
+                vec![
+                    (SkipLoopFilter::Never, "Never"),
+                    (SkipLoopFilter::NonReference, "Non-Reference"),
+                    (SkipLoopFilter::Bidirectional, "B-Frames"),
+                    (SkipLoopFilter::NonKey, "Non-Key"),
+                    (SkipLoopFilter::All, "All"),
+                ],
+            );
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Options"))
+                    .on_hover_text("FFmpeg decoder options, a key=value pair per line");
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut state.decoder_options)
+                            .desired_width(240.0)
+                            .desired_rows(2)
+                            .hint_text("key=value"),
+                    );
+                });
+            });
+
+            // Values are checked when the decoder opens, unknown keys right away.
+            let codec = state.codec.codec_id();
+            for (key, _) in state.decoder_options() {
+                if !VideoDecoder::has_option(codec, key) {
+                    ui.label(
+                        RichText::new(format!("Unknown {codec:?} decoder option \"{key}\""))
+                            .color(ui.visuals().error_fg_color),
+                    );
+                }
             }
         });
     }
 }
 
+/// Refresh period of the decoding devices row while they are probed
+const PROBE_REFRESH: Duration = Duration::from_millis(250);
+
+/// Hardware decoding device, among the probed devices of the selectable backends
+fn hw_device_row(ui: &mut Ui, state: &mut SessionSettings) {
+    // The devices are probed in the background at startup, the pool is not waited for.
+    let Some(pool) = HWDevicePool::probed() else {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new("Decoding Device"));
+            ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                ui.label(RichText::new("Probing…").color(ui.visuals().weak_text_color()));
+            });
+        });
+        ui.add_space(4.0);
+        ui.ctx().request_repaint_after(PROBE_REFRESH);
+        return;
+    };
+
+    let mut devices = pool
+        .devices()
+        .filter_map(|device| {
+            match (
+                HwBackend::from_device_type(device.device_type()),
+                device.device_index(),
+            ) {
+                (Some(backend), HWDeviceIndex::Index(index)) => {
+                    Some(((backend, index), format!("{} #{index}", device.name())))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    // The persisted device may be missing, e.g. unplugged.
+    if let Some(selected) = state.hw_device
+        && !devices.iter().any(|(device, _)| *device == selected)
+    {
+        devices.push((
+            selected,
+            format!("{:?} #{} (missing)", selected.0, selected.1),
+        ));
+    }
+
+    let mut options = vec![(None, "Renderer")];
+    options.extend(
+        devices
+            .iter()
+            .map(|(device, label)| (Some(*device), label.as_str())),
+    );
+    combo_row(
+        ui,
+        "hw_device",
+        "Decoding Device",
+        &mut state.hw_device,
+        options,
+    );
+}
+
 pub struct AudioSection;
 impl AudioSection {
     pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
         card(ui, "Audio", |ui| {
-            switch_row(
-                ui,
-                "Enable Audio Forwarding (Not implemented)",
-                &mut state.audio_enabled,
-            );
+            switch_row(ui, "Enable Audio Forwarding", &mut state.audio_enabled);
 
             if state.audio_enabled {
                 ui.add_space(8.0);
@@ -95,6 +259,43 @@ impl AudioSection {
                 ui.label(RichText::new(
                     "Audio will be forwarded from the device to this computer.",
                 ));
+                ui.add_space(8.0);
+
+                combo_row(
+                    ui,
+                    "audio_codec",
+                    "Codec",
+                    &mut state.audio_codec,
+                    vec![
+                        (AudioCodec::Opus, "Opus"),
+                        (AudioCodec::Aac, "AAC"),
+                        (AudioCodec::Flac, "FLAC"),
+                        (AudioCodec::Raw, "Raw (PCM)"),
+                    ],
+                );
+
+                combo_row(
+                    ui,
+                    "audio_source",
+                    "Source",
+                    &mut state.audio_source,
+                    vec![
+                        (AudioSource::Output, "Device Output"),
+                        (AudioSource::Mic, "Microphone"),
+                    ],
+                );
+
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Buffer Latency"))
+                        .on_hover_text("Higher values prevent crackling on unstable connections");
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        ui.add(Slider::new(&mut state.audio_buffer_ms, 10..=500).show_value(false));
+                        ui.add_sized(
+                            [60.0, 30.0],
+                            egui::Label::new(format!("{} ms", state.audio_buffer_ms)),
+                        );
+                    });
+                });
             }
         });
     }
@@ -104,11 +305,7 @@ pub struct ControlSection;
 impl ControlSection {
     pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
         card(ui, "Input Control", |ui| {
-            switch_row(
-                ui,
-                "Enable Mouse & Keyboard (Not implemented)",
-                &mut state.control_enabled,
-            );
+            switch_row(ui, "Enable Mouse & Keyboard", &mut state.control_enabled);
 
             if state.control_enabled {
                 ui.add_space(8.0);
@@ -118,11 +315,143 @@ impl ControlSection {
                 ui.label(RichText::new(
                     "Mouse and keyboard events will be captured and sent to the device.",
                 ));
+                ui.add_space(8.0);
+
+                switch_row(ui, "Clipboard Sync", &mut state.clipboard_sync);
+
+                if state.clipboard_sync {
+                    ui.add_space(4.0);
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("Clipboard Size Limit"));
+                        ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            ui.add(
+                                Slider::new(&mut state.clipboard_limit_kib, 1..=256)
+                                    .show_value(false),
+                            );
+                            ui.add_sized(
+                                [60.0, 30.0],
+                                egui::Label::new(format!("{} KiB", state.clipboard_limit_kib)),
+                            );
+                        });
+                    });
+                }
             }
         });
     }
 }
 
+pub struct RecordingSection;
+impl RecordingSection {
+    pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
+        card(ui, "Recording", |ui| {
+            ui.label(RichText::new(
+                "Sessions are recorded from their window, without re-encoding.",
+            ));
+            ui.add_space(8.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Directory"));
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut state.recordings_dir).desired_width(240.0),
+                    );
+                });
+            });
+            ui.add_space(4.0);
+
+            combo_row(
+                ui,
+                "recording_format",
+                "Format",
+                &mut state.recording_format,
+                vec![
+                    (RecordingFormat::Matroska, "Matroska (MKV)"),
+                    (RecordingFormat::Mp4, "MP4"),
+                ],
+            );
+            ui.add_space(4.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Instant Replay"))
+                    .on_hover_text("Last seconds of the session kept in memory, saved with F9");
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(Slider::new(&mut state.replay_secs, 0..=120).show_value(false));
+                    let text = if state.replay_secs == 0 {
+                        "Disabled".to_string()
+                    } else {
+                        format!("{} s", state.replay_secs)
+                    };
+                    ui.add_sized([60.0, 30.0], egui::Label::new(text));
+                });
+            });
+            ui.add_space(4.0);
+
+            switch_row(ui, "Capture Raw Stream", &mut state.capture_stream);
+        });
+    }
+}
+
+pub struct ScreenshotSection;
+impl ScreenshotSection {
+    pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
+        card(ui, "Screenshots", |ui| {
+            ui.label(RichText::new(
+                "Screenshots are taken from the session window, at the device resolution.",
+            ));
+            ui.add_space(8.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Directory"));
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut state.screenshots_dir).desired_width(240.0),
+                    );
+                });
+            });
+            ui.add_space(4.0);
+
+            switch_row(ui, "Copy to Clipboard", &mut state.screenshot_clipboard);
+        });
+    }
+}
+
+pub struct PlaybackSection;
+impl PlaybackSection {
+    pub fn show(ui: &mut Ui, state: &mut SessionSettings) {
+        card(ui, "Virtual Device", |ui| {
+            ui.label(RichText::new(
+                "Plays a recorded video back as a device, to use without a phone attached.",
+            ));
+            ui.add_space(8.0);
+
+            ui.horizontal(|ui| {
+                ui.label(RichText::new("Recording"))
+                    .on_hover_text("Annex-B stream, MP4 or MKV file. Empty to hide the device.");
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut state.playback_path).desired_width(240.0),
+                    );
+                });
+            });
+            ui.add_space(4.0);
+
+            combo_row(
+                ui,
+                "playback_pacing",
+                "Pacing",
+                &mut state.playback_pacing,
+                vec![
+                    (PlaybackPacing::RealTime, "Real Time"),
+                    (PlaybackPacing::Unpaced, "As Fast As Possible"),
+                ],
+            );
+            ui.add_space(4.0);
+
+            switch_row(ui, "Loop", &mut state.playback_loop);
+        });
+    }
+}
+
 /// A rounded card container for a group of settings.
 pub fn card(ui: &mut Ui, title: &str, content: impl FnOnce(&mut Ui)) {
     let frame = egui::Frame::new()
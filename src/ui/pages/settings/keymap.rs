@@ -0,0 +1,126 @@
+//! Keymap profiles, binding keys and mouse buttons to synthesized touches
+//!
+//! Points are normalized to `[0, 1]` in video coordinates, so profiles survive resolution changes.
+
+use std::fmt;
+
+use eframe::egui::{Key, PointerButton, Pos2, Rect};
+
+/// Keymap profiles of every device and package.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct Keymaps {
+    pub profiles: Vec<KeymapProfile>,
+}
+
+/// Device or application a keymap profile applies to.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub enum KeymapTarget {
+    /// Device serial
+    Device(String),
+    /// Android package name
+    Package(String),
+}
+
+/// Key bindings of a device or package.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KeymapProfile {
+    pub target: KeymapTarget,
+    pub bindings: Vec<KeyBinding>,
+    pub joysticks: Vec<Joystick>,
+}
+
+/// Key or mouse button triggering a binding.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    Key(Key),
+    Mouse(PointerButton),
+}
+
+/// Touch synthesized when a trigger is pressed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+    pub trigger: Trigger,
+    pub action: BindingAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum BindingAction {
+    /// Touches and releases the point on press.
+    Tap { point: Pos2 },
+    /// Touches the point while the trigger is held.
+    Hold { point: Pos2 },
+    /// Drags from `from` to `to` over `duration_ms` on press.
+    Swipe {
+        from: Pos2,
+        to: Pos2,
+        duration_ms: u32,
+    },
+}
+
+/// Directional keys dragging a touch from the center of a region to its edges,
+/// like a virtual thumbstick.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Joystick {
+    /// Drag region, normalized to `[0, 1]`
+    pub region: Rect,
+    pub up: Trigger,
+    pub down: Trigger,
+    pub left: Trigger,
+    pub right: Trigger,
+}
+
+impl Keymaps {
+    /// Profile of a device or package.
+    pub fn profile(&self, target: &KeymapTarget) -> Option<&KeymapProfile> {
+        self.profiles.iter().find(|p| &p.target == target)
+    }
+
+    /// Profile of a device or package, created empty if missing.
+    pub fn profile_mut(&mut self, target: &KeymapTarget) -> &mut KeymapProfile {
+        let index = match self.profiles.iter().position(|p| &p.target == target) {
+            Some(index) => index,
+            None => {
+                self.profiles.push(KeymapProfile::new(target.clone()));
+                self.profiles.len() - 1
+            }
+        };
+
+        &mut self.profiles[index]
+    }
+}
+
+impl KeymapProfile {
+    pub const fn new(target: KeymapTarget) -> Self {
+        Self {
+            target,
+            bindings: Vec::new(),
+            joysticks: Vec::new(),
+        }
+    }
+
+    /// Whether a trigger is bound to a binding or a joystick direction.
+    pub fn is_bound(&self, trigger: Trigger) -> bool {
+        self.bindings.iter().any(|b| b.trigger == trigger)
+            || self
+                .joysticks
+                .iter()
+                .any(|j| j.triggers().contains(&trigger))
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{}", key.name()),
+            Self::Mouse(button) => write!(f, "Mouse {button:?}"),
+        }
+    }
+}
+
+impl Joystick {
+    /// Direction triggers, in order: up, down, left, right.
+    pub const fn triggers(&self) -> [Trigger; 4] {
+        [self.up, self.down, self.left, self.right]
+    }
+}
@@ -2,11 +2,18 @@ use eframe::egui::{self, Align, Button, Layout, RichText, ScrollArea, Ui, Vec2};
 
 use crate::core::msg::Msg;
 use crate::ui::context::ViewContext;
-use crate::ui::pages::settings::components::{AudioSection, ControlSection, VideoSection};
+use crate::ui::pages::settings::components::{
+    AudioSection, ControlSection, DecoderSection, PlaybackSection, RecordingSection,
+    ScreenshotSection, VideoSection,
+};
+pub use crate::ui::pages::settings::keymap::{
+    BindingAction, Joystick, KeyBinding, KeymapProfile, KeymapTarget, Keymaps, Trigger,
+};
 pub use crate::ui::pages::settings::state::SessionSettings;
 use crate::ui::pages::{CurrentPage, Page};
 
 mod components;
+mod keymap;
 mod state;
 
 pub struct SettingsPage;
@@ -73,8 +80,12 @@ impl Page for SettingsPage {
                                 self.render_header(ui, ctx);
 
                                 VideoSection::show(ui, &mut ctx.data.session_settings);
+                                DecoderSection::show(ui, &mut ctx.data.session_settings);
                                 AudioSection::show(ui, &mut ctx.data.session_settings);
                                 ControlSection::show(ui, &mut ctx.data.session_settings);
+                                RecordingSection::show(ui, &mut ctx.data.session_settings);
+                                ScreenshotSection::show(ui, &mut ctx.data.session_settings);
+                                PlaybackSection::show(ui, &mut ctx.data.session_settings);
 
                                 self.render_footer(ui, ctx);
 
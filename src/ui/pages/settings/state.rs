@@ -1,17 +1,58 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ffmpeg_next::codec::discard::Discard;
+use ffmpeg_next::{codec, ffi, threading};
 use scrcpy_launcher::options;
 
-use crate::services::session::{FrameCallback, SessionConfig, SessionVideoConfig};
+use crate::services::audio_sink::AudioOutput;
+use crate::services::control::{ClipboardCallback, ClipboardConfig};
+use crate::services::playback::{Pacing, PlaybackConfig};
+use crate::services::recorder::{ContainerFormat, RecorderConfig, RecordingCallback};
+use crate::services::screenshot::ScreenshotConfig;
+use crate::services::session::{
+    self, FrameCallback, SessionAudioConfig, SessionConfig, SessionControlConfig,
+    SessionVideoConfig,
+};
+use crate::services::stream_decoder::FallbackCallback;
+use crate::transcoding::video::decoder::DecoderConfig;
 
+/// Missing fields, added since the settings were persisted, take their default value.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
 pub struct SessionSettings {
     pub control_enabled: bool,
+    pub clipboard_sync: bool,
+    pub clipboard_limit_kib: u32,
     pub audio_enabled: bool,
+    pub audio_codec: AudioCodec,
+    pub audio_source: AudioSource,
+    pub audio_buffer_ms: u32,
     pub video_enabled: bool,
     pub codec: VideoCodec,
     pub bitrate_mbps: u32,
     pub max_fps: u32,
     pub limit_resolution: u32,
     pub hw_decoder: bool,
+    /// Hardware decoding device and its index. `None` for the renderer device.
+    pub hw_device: Option<(HwBackend, u8)>,
+    pub tone_mapping: ToneMapping,
+    pub presentation: Presentation,
+    /// Decoding threads, 0 for one per core
+    pub decoder_threads: u32,
+    pub decoder_threading: DecoderThreading,
+    pub skip_loop_filter: SkipLoopFilter,
+    /// `FFmpeg` decoder options, a `key=value` pair per line
+    pub decoder_options: String,
+    pub recordings_dir: String,
+    pub recording_format: RecordingFormat,
+    pub replay_secs: u32,
+    pub capture_stream: bool,
+    pub screenshots_dir: String,
+    pub screenshot_clipboard: bool,
+    pub playback_path: String,
+    pub playback_pacing: PlaybackPacing,
+    pub playback_loop: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
@@ -21,35 +62,176 @@ pub enum VideoCodec {
     AV1,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum HwBackend {
+    Vulkan,
+    Vaapi,
+    Cuda,
+    Drm,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapping {
+    Disabled,
+    Clip,
+    Reinhard,
+    Hable,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Presentation {
+    LowestLatency,
+    Smooth,
+    EveryFrame,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderThreading {
+    /// Slices of a frame decoded in parallel
+    Slice,
+    /// Frames decoded in parallel, a frame of delay per thread
+    Frame,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SkipLoopFilter {
+    Never,
+    NonReference,
+    Bidirectional,
+    NonKey,
+    All,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AudioCodec {
+    Opus,
+    Aac,
+    Flac,
+    Raw,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AudioSource {
+    /// Device audio output
+    Output,
+    /// Device microphone
+    Mic,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Mp4,
+    Matroska,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackPacing {
+    /// At the recorded frame rate
+    RealTime,
+    /// As fast as decoded
+    Unpaced,
+}
+
 impl Default for SessionSettings {
     fn default() -> Self {
         Self {
             control_enabled: false,
+            clipboard_sync: true,
+            clipboard_limit_kib: 64,
             audio_enabled: false,
+            audio_codec: AudioCodec::Opus,
+            audio_source: AudioSource::Output,
+            audio_buffer_ms: 50,
             video_enabled: true,
             codec: VideoCodec::H264,
             bitrate_mbps: 8,
             max_fps: 0,
             limit_resolution: 0,
             hw_decoder: false,
+            hw_device: None,
+            tone_mapping: ToneMapping::Hable,
+            presentation: Presentation::LowestLatency,
+            decoder_threads: 5,
+            decoder_threading: DecoderThreading::Slice,
+            skip_loop_filter: SkipLoopFilter::Never,
+            decoder_options: String::new(),
+            recordings_dir: "recordings".to_owned(),
+            recording_format: RecordingFormat::Matroska,
+            replay_secs: 30,
+            capture_stream: false,
+            screenshots_dir: "screenshots".to_owned(),
+            screenshot_clipboard: false,
+            playback_path: String::new(),
+            playback_pacing: PlaybackPacing::RealTime,
+            playback_loop: true,
+        }
+    }
+}
+
+impl VideoCodec {
+    pub const fn codec_id(self) -> codec::Id {
+        match self {
+            Self::H264 => codec::Id::H264,
+            Self::H265 => codec::Id::H265,
+            Self::AV1 => codec::Id::AV1,
+        }
+    }
+}
+
+impl HwBackend {
+    /// Backend of a hardware device type. `None` if it cannot be selected.
+    pub const fn from_device_type(device_type: ffi::AVHWDeviceType) -> Option<Self> {
+        match device_type {
+            ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN => Some(Self::Vulkan),
+            ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI => Some(Self::Vaapi),
+            ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA => Some(Self::Cuda),
+            ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_DRM => Some(Self::Drm),
+            _ => None,
+        }
+    }
+
+    pub const fn device_type(self) -> ffi::AVHWDeviceType {
+        match self {
+            Self::Vulkan => ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VULKAN,
+            Self::Vaapi => ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_VAAPI,
+            Self::Cuda => ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+            Self::Drm => ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_DRM,
         }
     }
 }
 
 impl SessionSettings {
     #[allow(clippy::cast_precision_loss)]
-    pub fn to_config(&self, device_id: String, on_frame_cb: FrameCallback) -> SessionConfig {
-        if self.control_enabled {
-            Some(())
-        } else {
-            None
-        };
+    pub fn to_config(
+        &self,
+        device_id: String,
+        on_frame_cb: FrameCallback,
+        on_clipboard_cb: ClipboardCallback,
+        on_recording_cb: RecordingCallback,
+        on_fallback_cb: FallbackCallback,
+    ) -> SessionConfig {
+        let control = self.control_enabled.then(|| SessionControlConfig {
+            clipboard: self.clipboard_sync.then(|| ClipboardConfig {
+                max_size: self.clipboard_limit_kib as usize * 1024,
+                on_sync_cb: on_clipboard_cb,
+            }),
+        });
 
-        if self.audio_enabled {
-            Some(())
-        } else {
-            None
-        };
+        let audio = self.audio_enabled.then(|| SessionAudioConfig {
+            codec: match self.audio_codec {
+                AudioCodec::Opus => options::AudioCodec::Opus,
+                AudioCodec::Aac => options::AudioCodec::Aac,
+                AudioCodec::Flac => options::AudioCodec::Flac,
+                AudioCodec::Raw => options::AudioCodec::Raw,
+            },
+            source: match self.audio_source {
+                AudioSource::Output => options::AudioSource::Output,
+                AudioSource::Mic => options::AudioSource::Mic,
+            },
+            output: AudioOutput::Device {
+                target_latency: Duration::from_millis(self.audio_buffer_ms.into()),
+            },
+        });
 
         let video = if self.video_enabled {
             Some(SessionVideoConfig {
@@ -62,17 +244,101 @@ impl SessionSettings {
                 bitrate: (self.bitrate_mbps * 1_000_000).cast_signed(),
                 max_fps: self.max_fps as f32,
                 hw_decoder: self.hw_decoder,
+                hw_device: self
+                    .hw_device
+                    .map(|(backend, index)| (index.into(), backend.device_type())),
+                tone_mapping: match self.tone_mapping {
+                    ToneMapping::Disabled => session::ToneMapping::Disabled,
+                    ToneMapping::Clip => session::ToneMapping::Clip,
+                    ToneMapping::Reinhard => session::ToneMapping::Reinhard,
+                    ToneMapping::Hable => session::ToneMapping::Hable,
+                },
+                presentation: match self.presentation {
+                    Presentation::LowestLatency => session::PresentationMode::LowestLatency,
+                    Presentation::Smooth => session::PresentationMode::Smooth,
+                    Presentation::EveryFrame => session::PresentationMode::EveryFrame,
+                },
+                decoder: DecoderConfig {
+                    threading: threading::Config {
+                        kind: match self.decoder_threading {
+                            DecoderThreading::Slice => threading::Type::Slice,
+                            DecoderThreading::Frame => threading::Type::Frame,
+                        },
+                        count: self.decoder_threads as usize,
+                    },
+                    skip_loop_filter: match self.skip_loop_filter {
+                        SkipLoopFilter::Never => Discard::Default,
+                        SkipLoopFilter::NonReference => Discard::NonReference,
+                        SkipLoopFilter::Bidirectional => Discard::Bidirectional,
+                        SkipLoopFilter::NonKey => Discard::NonKey,
+                        SkipLoopFilter::All => Discard::All,
+                    },
+                    options: self
+                        .decoder_options()
+                        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                        .collect(),
+                    ..DecoderConfig::default()
+                },
                 on_frame_cb,
+                on_fallback_cb,
             })
         } else {
             None
         };
 
+        let record = RecorderConfig {
+            directory: PathBuf::from(&self.recordings_dir),
+            format: match self.recording_format {
+                RecordingFormat::Mp4 => ContainerFormat::Mp4,
+                RecordingFormat::Matroska => ContainerFormat::Matroska,
+            },
+            replay_duration: Duration::from_secs(self.replay_secs.into()),
+            capture_stream: self.capture_stream,
+            on_saved_cb: on_recording_cb,
+        };
+
+        let screenshot = ScreenshotConfig {
+            directory: PathBuf::from(&self.screenshots_dir),
+            copy_to_clipboard: self.screenshot_clipboard,
+        };
+
         SessionConfig {
             device_id,
-            control: None,
-            audio: None,
+            control,
+            audio,
             video,
+            record,
+            screenshot,
+            playback: None,
         }
     }
+
+    /// Decoder options, by line. A line without `=` is an option without value.
+    pub fn decoder_options(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.decoder_options
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.split_once('=')
+                    .map_or((line, ""), |(key, value)| (key.trim(), value.trim()))
+            })
+    }
+
+    /// Virtual device playback configuration. `None` if no recording is selected.
+    pub fn playback_config(&self) -> Option<PlaybackConfig> {
+        let path = self.playback_path.trim();
+        if path.is_empty() {
+            return None;
+        }
+
+        Some(PlaybackConfig {
+            path: PathBuf::from(path),
+            pacing: match self.playback_pacing {
+                PlaybackPacing::RealTime => Pacing::RealTime,
+                PlaybackPacing::Unpaced => Pacing::Unpaced,
+            },
+            looping: self.playback_loop,
+        })
+    }
 }
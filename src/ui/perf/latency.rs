@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use eframe::egui_wgpu::{CallbackResources, CallbackTrait};
+
+use crate::services::stream_decoder::FrameTiming;
+
+/// Frame latency shared with the paint callbacks
+pub type SharedFrameLatency = Arc<parking_lot::Mutex<FrameLatency>>;
+
+/// Latency percentiles over a sliding window
+pub struct LatencyCounter {
+    samples: VecDeque<(Instant, Duration)>,
+    sorted_samples: Vec<Duration>,
+    capacity: usize,
+    window_duration: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LatencyStats {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencyCounter {
+    /// # Panics
+    ///
+    /// Panics when capacity = 0.
+    pub fn new(capacity: usize, window_duration: Duration) -> Self {
+        assert!(capacity > 0);
+
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            sorted_samples: Vec::with_capacity(capacity),
+            capacity,
+            window_duration,
+        }
+    }
+
+    /// Adds a latency measured at `now`
+    pub fn push(&mut self, now: Instant, latency: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back((now, latency));
+
+        let limit = now.checked_sub(self.window_duration).unwrap_or(now);
+
+        while let Some(&(front, _)) = self.samples.front() {
+            if front < limit {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub fn stats(&mut self) -> LatencyStats {
+        if self.samples.is_empty() {
+            return LatencyStats::default();
+        }
+
+        self.sorted_samples.clear();
+        self.sorted_samples
+            .extend(self.samples.iter().map(|&(_, latency)| latency));
+        self.sorted_samples.sort_unstable();
+
+        let len = self.sorted_samples.len();
+        let percentile = |p: usize| self.sorted_samples[(len * p / 100).min(len - 1)];
+
+        LatencyStats {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: self.sorted_samples[len - 1],
+        }
+    }
+}
+
+/// Latency of the displayed frames, from the device to the screen
+pub struct FrameLatency {
+    /// Device to host transit, relative to the fastest packet
+    network: LatencyCounter,
+    /// Reception to decoding completion
+    decode: LatencyCounter,
+    /// Decoding completion to texture upload, including the presentation queue
+    upload: LatencyCounter,
+    /// Texture upload to paint
+    present: LatencyCounter,
+    /// Device to paint
+    total: LatencyCounter,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameLatencyStats {
+    pub network: LatencyStats,
+    pub decode: LatencyStats,
+    pub upload: LatencyStats,
+    pub present: LatencyStats,
+    pub total: LatencyStats,
+}
+
+impl FrameLatency {
+    /// # Panics
+    ///
+    /// Panics when capacity = 0.
+    pub fn new(capacity: usize, window_duration: Duration) -> Self {
+        let counter = || LatencyCounter::new(capacity, window_duration);
+
+        Self {
+            network: counter(),
+            decode: counter(),
+            upload: counter(),
+            present: counter(),
+            total: counter(),
+        }
+    }
+
+    /// Records a displayed frame.
+    ///
+    /// # Arguments
+    ///
+    /// - `timing`: Frame reception and decoding instants.
+    /// - `uploaded`: Texture upload submission.
+    /// - `painted`: Submission of the ui frame drawing it.
+    pub fn record(&mut self, timing: &FrameTiming, uploaded: Instant, painted: Instant) {
+        let stages = [
+            (&mut self.network, timing.sent, timing.arrival),
+            (&mut self.decode, timing.arrival, timing.decoded),
+            (&mut self.upload, timing.decoded, uploaded),
+            (&mut self.present, uploaded, painted),
+            (&mut self.total, timing.sent, painted),
+        ];
+        for (counter, start, end) in stages {
+            counter.push(painted, end.saturating_duration_since(start));
+        }
+    }
+
+    pub fn stats(&mut self) -> FrameLatencyStats {
+        FrameLatencyStats {
+            network: self.network.stats(),
+            decode: self.decode.stats(),
+            upload: self.upload.stats(),
+            present: self.present.stats(),
+            total: self.total.stats(),
+        }
+    }
+}
+
+/// Paint callback recording a displayed frame, once the ui frame drawing it is prepared for
+/// submission: after its tessellation and its buffers upload.
+pub struct PaintedFrame {
+    latency: SharedFrameLatency,
+    timing: FrameTiming,
+    uploaded: Instant,
+}
+
+impl PaintedFrame {
+    /// # Arguments
+    ///
+    /// - `latency`: Latency the frame is recorded in.
+    /// - `timing`: Frame reception and decoding instants.
+    /// - `uploaded`: Texture upload submission.
+    pub const fn new(latency: SharedFrameLatency, timing: FrameTiming, uploaded: Instant) -> Self {
+        Self {
+            latency,
+            timing,
+            uploaded,
+        }
+    }
+}
+
+impl CallbackTrait for PaintedFrame {
+    fn finish_prepare(
+        &self,
+        _device: &wgpu::Device,
+        _queue: &wgpu::Queue,
+        _egui_encoder: &mut wgpu::CommandEncoder,
+        _callback_resources: &mut CallbackResources,
+    ) -> Vec<wgpu::CommandBuffer> {
+        self.latency
+            .lock()
+            .record(&self.timing, self.uploaded, Instant::now());
+        Vec::new()
+    }
+
+    fn paint(
+        &self,
+        _info: egui::PaintCallbackInfo,
+        _render_pass: &mut wgpu::RenderPass<'static>,
+        _callback_resources: &CallbackResources,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::ui::perf::latency::LatencyCounter;
+
+    #[test]
+    fn test_percentiles() {
+        let mut counter = LatencyCounter::new(100, Duration::from_secs(1));
+        let now = Instant::now();
+
+        for ms in (1..=100).rev() {
+            counter.push(now, Duration::from_millis(ms));
+        }
+
+        let stats = counter.stats();
+        assert_eq!(stats.p50, Duration::from_millis(51));
+        assert_eq!(stats.p90, Duration::from_millis(91));
+        assert_eq!(stats.p99, Duration::from_millis(100));
+        assert_eq!(stats.max, Duration::from_millis(100));
+
+        // Samples out of the window are discarded.
+        counter.push(now + Duration::from_secs(2), Duration::from_millis(5));
+        assert_eq!(counter.stats().max, Duration::from_millis(5));
+    }
+}
@@ -0,0 +1,239 @@
+//! Vulkan device shared by the renderer and the hardware decoder
+
+#![allow(unsafe_code)]
+
+use std::ffi::CStr;
+use std::sync::Arc;
+
+use ash::vk;
+use eframe::egui_wgpu::WgpuSetupExisting;
+use wgpu::hal::api::Vulkan;
+
+use crate::transcoding::hw::vulkan::{
+    AVVulkanDeviceQueueFamily, VulkanDeviceHandles, avutil_version, is_layout_supported,
+};
+
+/// Device configuration, given an adapter
+pub type DeviceDescriptor =
+    Arc<dyn Fn(&wgpu::Adapter) -> wgpu::DeviceDescriptor<'static> + Send + Sync>;
+
+/// Renderer queue index, the first queue of the graphics family is left to the decoder
+const RENDERER_QUEUE: u32 = 1;
+
+/// Video decoding extensions, enabled when supported
+const DECODE_EXTENSIONS: &[&CStr] = &[
+    ash::khr::video_queue::NAME,
+    ash::khr::video_decode_queue::NAME,
+    ash::khr::video_decode_h264::NAME,
+    ash::khr::video_decode_h265::NAME,
+    ash::khr::video_decode_av1::NAME,
+];
+
+/// Creates the renderer device with the queues and extensions required by the `FFmpeg` Vulkan
+/// decoder.
+///
+/// Returns the renderer setup and the handles to create the decoder device with.
+///
+/// # Errors
+///
+/// Fails if the adapter cannot decode video or share its graphics queue family, or if the
+/// `FFmpeg` Vulkan structures layout is not supported. The renderer should then create its own
+/// device.
+pub async fn create_shared_device(
+    instance_descriptor: &wgpu::InstanceDescriptor,
+    power_preference: wgpu::PowerPreference,
+    device_descriptor: &DeviceDescriptor,
+) -> Result<(WgpuSetupExisting, VulkanDeviceHandles), anyhow::Error> {
+    if !is_layout_supported() {
+        let (major, minor) = avutil_version();
+        return Err(anyhow::anyhow!(
+            "The Vulkan structures of libavutil {major}.{minor} are not supported"
+        ));
+    }
+
+    let instance = wgpu::Instance::new(instance_descriptor);
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        })
+        .await?;
+
+    let descriptor = device_descriptor(&adapter);
+
+    let (open_device, handles) = {
+        let hal_adapter = unsafe { adapter.as_hal::<Vulkan>() }
+            .ok_or_else(|| anyhow::anyhow!("The adapter is not a Vulkan adapter"))?;
+        unsafe { open_device(&hal_adapter, &descriptor)? }
+    };
+
+    let (device, queue) =
+        unsafe { adapter.create_device_from_hal::<Vulkan>(open_device, &descriptor)? };
+
+    Ok((
+        WgpuSetupExisting {
+            instance,
+            adapter,
+            device,
+            queue,
+        },
+        handles,
+    ))
+}
+
+/// Opens a device with the wgpu requirements, a video decode queue, and a second graphics queue.
+///
+/// # Safety
+///
+/// The decoder queues must not be used by the renderer.
+unsafe fn open_device(
+    adapter: &wgpu::hal::vulkan::Adapter,
+    descriptor: &wgpu::DeviceDescriptor<'_>,
+) -> Result<(wgpu::hal::OpenDevice<Vulkan>, VulkanDeviceHandles), anyhow::Error> {
+    let shared = adapter.shared_instance();
+    let instance = shared.raw_instance();
+    let physical_device = adapter.raw_physical_device();
+
+    let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+    if shared.instance_api_version() < vk::API_VERSION_1_3
+        || properties.api_version < vk::API_VERSION_1_3
+    {
+        return Err(anyhow::anyhow!("Vulkan 1.3 is not supported"));
+    }
+
+    // Extensions
+    let supported = unsafe { instance.enumerate_device_extension_properties(physical_device)? };
+    let is_supported = |name: &CStr| {
+        supported
+            .iter()
+            .any(|extension| extension.extension_name_as_c_str() == Ok(name))
+    };
+    if !DECODE_EXTENSIONS[..2]
+        .iter()
+        .all(|&name| is_supported(name))
+    {
+        return Err(anyhow::anyhow!("Vulkan video decoding is not supported"));
+    }
+    let mut extensions = adapter.required_device_extensions(descriptor.required_features);
+    for &name in DECODE_EXTENSIONS {
+        if is_supported(name) && !extensions.contains(&name) {
+            extensions.push(name);
+        }
+    }
+
+    // Queue families
+    let count =
+        unsafe { instance.get_physical_device_queue_family_properties2_len(physical_device) };
+    let mut video = vec![vk::QueueFamilyVideoPropertiesKHR::default(); count];
+    let families = {
+        let mut families = video
+            .iter_mut()
+            .map(|video| vk::QueueFamilyProperties2::default().push_next(video))
+            .collect::<Vec<_>>();
+        unsafe {
+            instance.get_physical_device_queue_family_properties2(physical_device, &mut families);
+        }
+        families
+            .iter()
+            .map(|family| family.queue_family_properties)
+            .collect::<Vec<_>>()
+    };
+
+    let graphics = families
+        .iter()
+        .position(|family| family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+        .ok_or_else(|| anyhow::anyhow!("No graphics queue family"))?;
+    if families[graphics].queue_count <= RENDERER_QUEUE {
+        return Err(anyhow::anyhow!(
+            "The graphics queue family has a single queue"
+        ));
+    }
+    let decode = (0..families.len())
+        .find(|&index| {
+            index != graphics
+                && families[index]
+                    .queue_flags
+                    .contains(vk::QueueFlags::VIDEO_DECODE_KHR)
+        })
+        .ok_or_else(|| anyhow::anyhow!("No video decode queue family"))?;
+
+    let priorities = [1.0; 2];
+    let queue_infos = [
+        vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(u32::try_from(graphics)?)
+            .queue_priorities(&priorities),
+        vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(u32::try_from(decode)?)
+            .queue_priorities(&priorities[..1]),
+    ];
+
+    // Device
+    let mut features = adapter.physical_device_features(&extensions, descriptor.required_features);
+    let core_features = features.get_core();
+    let mut synchronization2 =
+        vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
+    let names = extensions
+        .iter()
+        .map(|name| name.as_ptr())
+        .collect::<Vec<_>>();
+    let info = vk::DeviceCreateInfo::default()
+        .queue_create_infos(&queue_infos)
+        .enabled_extension_names(&names)
+        .push_next(&mut synchronization2);
+    let info = features.add_to_device_create(info);
+
+    let raw_device = unsafe { instance.create_device(physical_device, &info, None)? };
+    let device = raw_device.handle();
+
+    let open_device = unsafe {
+        adapter.device_from_raw(
+            raw_device,
+            None,
+            &extensions,
+            descriptor.required_features,
+            &descriptor.memory_hints,
+            u32::try_from(graphics)?,
+            RENDERER_QUEUE,
+        )?
+    };
+
+    // The decoder gets the queues of the renderer family preceding the renderer queue.
+    let (family, queue) = (
+        open_device.device.queue_family_index(),
+        open_device.device.queue_index(),
+    );
+    if family as usize != graphics || queue == 0 {
+        return Err(anyhow::anyhow!(
+            "The renderer opened the queue {queue} of the family {family}, none left to the decoder"
+        ));
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let handles = VulkanDeviceHandles {
+        get_proc_addr: shared.entry().static_fn().get_instance_proc_addr,
+        instance: instance.handle(),
+        physical_device,
+        device,
+        features: core_features,
+        instance_extensions: shared.extensions().to_vec(),
+        device_extensions: extensions,
+        queue_families: vec![
+            // Graphics and compute queues implicitly support transfers.
+            AVVulkanDeviceQueueFamily {
+                idx: family as i32,
+                num: queue as i32,
+                flags: families[graphics].queue_flags | vk::QueueFlags::TRANSFER,
+                video_caps: vk::VideoCodecOperationFlagsKHR::empty(),
+            },
+            AVVulkanDeviceQueueFamily {
+                idx: decode as i32,
+                num: 1,
+                flags: families[decode].queue_flags,
+                video_caps: video[decode].video_codec_operations,
+            },
+        ],
+    };
+
+    Ok((open_device, handles))
+}
@@ -0,0 +1,379 @@
+//! YUV to RGB color conversion, from the frame color metadata
+
+use bytemuck::{Pod, Zeroable};
+use ffmpeg_next::util::color;
+use ffmpeg_next::{format, frame};
+use wgpu::util::DeviceExt;
+
+use crate::services::session::ToneMapping;
+
+/// YUV matrix coefficients
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvMatrix {
+    /// BT.601, SD content
+    Bt601,
+    /// BT.709, HD content
+    #[default]
+    Bt709,
+    /// BT.2020 non-constant luminance, UHD and HDR content
+    Bt2020,
+    Smpte240m,
+    Fcc,
+}
+
+impl YuvMatrix {
+    /// Red and blue luma weights (Kr, Kb)
+    const fn weights(self) -> (f32, f32) {
+        match self {
+            Self::Bt601 => (0.299, 0.114),
+            Self::Bt709 => (0.2126, 0.0722),
+            Self::Bt2020 => (0.2627, 0.0593),
+            Self::Smpte240m => (0.212, 0.087),
+            Self::Fcc => (0.30, 0.11),
+        }
+    }
+}
+
+/// Transfer function of the RGB values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transfer {
+    /// SDR gamma, displayed as is
+    #[default]
+    Sdr,
+    /// HDR perceptual quantizer (SMPTE ST 2084)
+    Pq,
+    /// HDR hybrid log-gamma (ARIB STD-B67)
+    Hlg,
+}
+
+/// Color description of a YUV frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Colorimetry {
+    pub matrix: YuvMatrix,
+    /// Full range (0-255) instead of limited range (16-235)
+    pub full_range: bool,
+    pub transfer: Transfer,
+}
+
+impl Colorimetry {
+    /// Reads the frame color metadata.
+    ///
+    /// Unspecified matrices are guessed from the primaries, then from the resolution.
+    /// YUVJ formats are always full range.
+    pub fn from_frame(frame: &frame::Video) -> Self {
+        let matrix = match frame.color_space() {
+            color::Space::BT709 => YuvMatrix::Bt709,
+            color::Space::BT470BG | color::Space::SMPTE170M => YuvMatrix::Bt601,
+            color::Space::BT2020NCL | color::Space::BT2020CL => YuvMatrix::Bt2020,
+            color::Space::SMPTE240M => YuvMatrix::Smpte240m,
+            color::Space::FCC => YuvMatrix::Fcc,
+            _ => match frame.color_primaries() {
+                color::Primaries::BT709 => YuvMatrix::Bt709,
+                color::Primaries::BT470BG | color::Primaries::SMPTE170M => YuvMatrix::Bt601,
+                color::Primaries::BT2020 => YuvMatrix::Bt2020,
+                _ if frame.height() >= 720 => YuvMatrix::Bt709,
+                _ => YuvMatrix::Bt601,
+            },
+        };
+
+        let full_range = frame.color_range() == color::Range::JPEG
+            || matches!(
+                frame.format(),
+                format::Pixel::YUVJ420P
+                    | format::Pixel::YUVJ422P
+                    | format::Pixel::YUVJ444P
+                    | format::Pixel::YUVJ440P
+                    | format::Pixel::YUVJ411P
+            );
+
+        let transfer = match frame.color_transfer_characteristic() {
+            color::TransferCharacteristic::SMPTE2084 => Transfer::Pq,
+            color::TransferCharacteristic::ARIB_STD_B67 => Transfer::Hlg,
+            _ => Transfer::Sdr,
+        };
+
+        Self {
+            matrix,
+            full_range,
+            transfer,
+        }
+    }
+}
+
+/// Shader color conversion uniform, `rgb = matrix * (yuv + offset)` then the HDR tone mapping.
+///
+/// Mirrors the `ColorConversion` structure of `color.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Pod, Zeroable)]
+pub struct ColorConversion {
+    /// Columns of the `mat3x3<f32>`, padded to 16 bytes
+    columns: [[f32; 4]; 3],
+    offset: [f32; 3],
+    /// 0: SDR, 1: PQ, 2: HLG
+    transfer: u32,
+    /// 0: clip, 1: Reinhard, 2: Hable
+    tone_mapping: u32,
+    _padding: [u32; 3],
+}
+
+impl ColorConversion {
+    /// Conversion of the frames of the given colorimetry.
+    ///
+    /// # Arguments
+    ///
+    /// - `sample_scale`: Factor normalizing the texture samples, for 10-bit samples stored in
+    ///   16-bit textures.
+    pub fn new(colorimetry: Colorimetry, tone_mapping: ToneMapping, sample_scale: f32) -> Self {
+        let (kr, kb) = colorimetry.matrix.weights();
+        let kg = 1.0 - kr - kb;
+
+        // Normalized 8-bit quantization levels, close enough for 10-bit samples
+        let (y_offset, y_scale, c_scale) = if colorimetry.full_range {
+            (0.0, 1.0, 1.0)
+        } else {
+            (-16.0 / 255.0, 255.0 / 219.0, 255.0 / 224.0)
+        };
+        let c_offset = -128.0 / 255.0;
+
+        // The sample scale is applied before the offset: matrix * (scale * yuv + offset)
+        let (y_scale, c_scale) = (y_scale * sample_scale, c_scale * sample_scale);
+
+        let transfer = match (tone_mapping, colorimetry.transfer) {
+            (ToneMapping::Disabled, _) | (_, Transfer::Sdr) => 0,
+            (_, Transfer::Pq) => 1,
+            (_, Transfer::Hlg) => 2,
+        };
+        let tone_mapping = match tone_mapping {
+            ToneMapping::Disabled | ToneMapping::Clip => 0,
+            ToneMapping::Reinhard => 1,
+            ToneMapping::Hable => 2,
+        };
+
+        Self {
+            columns: [
+                [y_scale, y_scale, y_scale, 0.0],
+                [
+                    0.0,
+                    -2.0 * kb * (1.0 - kb) / kg * c_scale,
+                    2.0 * (1.0 - kb) * c_scale,
+                    0.0,
+                ],
+                [
+                    2.0 * (1.0 - kr) * c_scale,
+                    -2.0 * kr * (1.0 - kr) / kg * c_scale,
+                    0.0,
+                    0.0,
+                ],
+            ],
+            offset: [
+                y_offset / sample_scale,
+                c_offset / sample_scale,
+                c_offset / sample_scale,
+            ],
+            transfer,
+            tone_mapping,
+            _padding: [0; 3],
+        }
+    }
+}
+
+/// Color conversion uniform buffer, bound to the group 1 of the converter pipelines.
+///
+/// The conversion is rewritten when the frame colorimetry changes, the pipeline is kept.
+pub struct ColorUniform {
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    sample_scale: f32,
+    /// Current conversion parameters
+    current: (Colorimetry, ToneMapping),
+}
+
+impl ColorUniform {
+    /// Shader declarations of the uniform, to prepend to the converter shaders
+    pub const SHADER: &'static str = include_str!("color.wgsl");
+
+    pub fn new(device: &wgpu::Device, label: Option<&'static str>, sample_scale: f32) -> Self {
+        let current = (Colorimetry::default(), ToneMapping::default());
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::bytes_of(&ColorConversion::new(current.0, current.1, sample_scale)),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label,
+            layout: &layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            layout,
+            buffer,
+            bind_group,
+            sample_scale,
+            current,
+        }
+    }
+
+    pub const fn layout(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+
+    pub const fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Writes the conversion of the given colorimetry, if it changed.
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        colorimetry: Colorimetry,
+        tone_mapping: ToneMapping,
+    ) {
+        if self.current != (colorimetry, tone_mapping) {
+            self.current = (colorimetry, tone_mapping);
+            queue.write_buffer(
+                &self.buffer,
+                0,
+                bytemuck::bytes_of(&ColorConversion::new(
+                    colorimetry,
+                    tone_mapping,
+                    self.sample_scale,
+                )),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Converts normalized YUV samples, as the shader does for SDR frames.
+    fn convert(conversion: ColorConversion, yuv: [f32; 3]) -> [f32; 3] {
+        let input: [f32; 3] = std::array::from_fn(|i| yuv[i] + conversion.offset[i]);
+
+        std::array::from_fn(|row| {
+            (0..3)
+                .map(|column| conversion.columns[column][row] * input[column])
+                .sum()
+        })
+    }
+
+    fn convert_8bit(colorimetry: Colorimetry, yuv: [f32; 3]) -> [f32; 3] {
+        let conversion = ColorConversion::new(colorimetry, ToneMapping::default(), 1.0);
+        convert(conversion, yuv.map(|sample| sample / 255.0))
+    }
+
+    fn assert_rgb(actual: [f32; 3], expected: [f32; 3]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 0.01, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_limited_range() {
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020] {
+            let colorimetry = Colorimetry {
+                matrix,
+                ..Default::default()
+            };
+            assert_rgb(convert_8bit(colorimetry, [16.0, 128.0, 128.0]), [0.0; 3]);
+            assert_rgb(convert_8bit(colorimetry, [235.0, 128.0, 128.0]), [1.0; 3]);
+        }
+    }
+
+    #[test]
+    fn test_full_range() {
+        let colorimetry = Colorimetry {
+            matrix: YuvMatrix::Bt709,
+            full_range: true,
+            ..Default::default()
+        };
+        assert_rgb(convert_8bit(colorimetry, [0.0, 128.0, 128.0]), [0.0; 3]);
+        assert_rgb(convert_8bit(colorimetry, [255.0, 128.0, 128.0]), [1.0; 3]);
+        // BT.709 red: Y = 0.2126, Cb = -0.1146, Cr = 0.5
+        assert_rgb(
+            convert_8bit(colorimetry, [54.2, 98.8, 255.5]),
+            [1.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn test_10bit_samples() {
+        let colorimetry = Colorimetry {
+            matrix: YuvMatrix::Bt2020,
+            ..Default::default()
+        };
+        // Low bits of 16-bit words, as YUV420P10
+        let conversion =
+            ColorConversion::new(colorimetry, ToneMapping::default(), 65535.0 / 1023.0);
+        let sample = |value: f32| value / 65535.0;
+        assert_rgb(
+            convert(conversion, [sample(64.0), sample(512.0), sample(512.0)]),
+            [0.0; 3],
+        );
+        assert_rgb(
+            convert(conversion, [sample(940.0), sample(512.0), sample(512.0)]),
+            [1.0; 3],
+        );
+    }
+
+    #[test]
+    fn test_bt601_coefficients() {
+        let conversion = ColorConversion::new(
+            Colorimetry {
+                matrix: YuvMatrix::Bt601,
+                ..Default::default()
+            },
+            ToneMapping::default(),
+            1.0,
+        );
+        // https://github.com/spurious/SDL-mirror/blob/4ddd4c445aa059bb127e101b74a8c5b59257fbe2/src/render/opengl/SDL_shaders_gl.c#L93
+        let expected = [
+            [1.1644, 1.1644, 1.1644],
+            [0.0, -0.3918, 2.0172],
+            [1.5960, -0.8130, 0.0],
+        ];
+        for (column, expected) in conversion.columns.iter().zip(expected) {
+            for (a, e) in column.iter().zip(expected) {
+                assert!((a - e).abs() < 0.001, "{column:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_tone_mapping_disabled() {
+        let colorimetry = Colorimetry {
+            transfer: Transfer::Pq,
+            ..Default::default()
+        };
+        assert_eq!(
+            ColorConversion::new(colorimetry, ToneMapping::Disabled, 1.0).transfer,
+            0
+        );
+        assert_eq!(
+            ColorConversion::new(colorimetry, ToneMapping::Hable, 1.0).transfer,
+            1
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// YUV to RGB conversion of the frame colorimetry, shared by the converters
+
+struct ColorConversion {
+    // YUV to RGB matrix, including the range expansion
+    matrix: mat3x3<f32>,
+    // YUV offset, applied before the matrix
+    offset: vec3<f32>,
+    // 0: SDR, 1: PQ, 2: HLG
+    transfer: u32,
+    // 0: clip, 1: Reinhard, 2: Hable
+    tone_mapping: u32,
+};
+
+@group(1) @binding(0) var<uniform> conversion: ColorConversion;
+
+const TRANSFER_SDR: u32 = 0u;
+const TRANSFER_PQ: u32 = 1u;
+const TONE_MAPPING_REINHARD: u32 = 1u;
+const TONE_MAPPING_HABLE: u32 = 2u;
+
+// Luminances in nits: HDR reference white, and peak assumed for the mastering display
+const SDR_WHITE: f32 = 203.0;
+const HDR_PEAK: f32 = 1000.0;
+
+// BT.2020 to BT.709 primaries, in linear light
+const BT2020_TO_BT709 = mat3x3<f32>(
+     1.6605, -0.1246, -0.0182,
+    -0.5876,  1.1329, -0.1006,
+    -0.0728, -0.0083,  1.1187,
+);
+
+// PQ signal to linear light, relative to the SDR white
+fn pq_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let p = pow(clamp(color, vec3(0.0), vec3(1.0)), vec3(1.0 / 78.84375));
+    let l = pow(max(p - 0.8359375, vec3(0.0)) / (18.8515625 - 18.6875 * p), vec3(1.0 / 0.1593017578125));
+    return l * 10000.0 / SDR_WHITE;
+}
+
+// HLG signal to linear light, relative to the SDR white, as shown on a display of HDR_PEAK nits
+fn hlg_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let e = clamp(color, vec3(0.0), vec3(1.0));
+    let low = e * e / 3.0;
+    let high = (exp((e - 0.55991073) / 0.17883277) + 0.28466892) / 12.0;
+    let scene = select(high, low, e <= vec3(0.5));
+
+    // Display OOTF, system gamma 1.2
+    let luminance = dot(scene, vec3(0.2627, 0.6780, 0.0593));
+    return scene * pow(max(luminance, 1e-6), 0.2) * HDR_PEAK / SDR_WHITE;
+}
+
+fn hable_curve(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f;
+}
+
+// Linear HDR light to SDR, the peak being mapped to the SDR white
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    let peak = HDR_PEAK / SDR_WHITE;
+
+    if conversion.tone_mapping == TONE_MAPPING_REINHARD {
+        return color * (1.0 + color / (peak * peak)) / (1.0 + color);
+    }
+    if conversion.tone_mapping == TONE_MAPPING_HABLE {
+        return hable_curve(color) / hable_curve(vec3(peak));
+    }
+    return color;
+}
+
+fn yuv_to_rgb(yuv: vec3<f32>) -> vec3<f32> {
+    let rgb = conversion.matrix * (yuv + conversion.offset);
+    if conversion.transfer == TRANSFER_SDR {
+        return rgb;
+    }
+
+    var light: vec3<f32>;
+    if conversion.transfer == TRANSFER_PQ {
+        light = pq_to_linear(rgb);
+    } else {
+        light = hlg_to_linear(rgb);
+    }
+
+    // HDR content uses the BT.2020 primaries, displayed as BT.709.
+    light = max(BT2020_TO_BT709 * light, vec3(0.0));
+
+    let sdr = clamp(tone_map(light), vec3(0.0), vec3(1.0));
+    return pow(sdr, vec3(1.0 / 2.2));
+}
@@ -1,16 +1,52 @@
 //! Shaders and color space conversion pipelines
 
+pub mod color;
 pub mod nv12;
 mod utils;
 pub mod yuv420p;
 
 use ffmpeg_next::frame;
 
+use crate::services::session::ToneMapping;
+use crate::ui::renderer::frame::convert::color::Colorimetry;
+
 /// Converter output texture format
 pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
 /// Converter output texture format bytes per pixel
 pub const TEXTURE_FORMAT_BYTES: u32 = 4;
 
+/// Bit depth of the source samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleDepth {
+    /// 8-bit samples, as NV12 and YUV420P
+    Eight,
+    /// 10-bit samples in the high bits of 16-bit words, as P010
+    TenHigh,
+    /// 10-bit samples in the low bits of 16-bit words, as YUV420P10
+    TenLow,
+}
+
+impl SampleDepth {
+    /// Source texture format of a plane with the given number of components
+    pub const fn plane_format(self, components: u32) -> wgpu::TextureFormat {
+        match (self, components) {
+            (Self::Eight, 1) => wgpu::TextureFormat::R8Unorm,
+            (Self::Eight, _) => wgpu::TextureFormat::Rg8Unorm,
+            (_, 1) => wgpu::TextureFormat::R16Unorm,
+            (_, _) => wgpu::TextureFormat::Rg16Unorm,
+        }
+    }
+
+    /// Factor normalizing the texture samples to `[0, 1]`
+    pub const fn sample_scale(self) -> f32 {
+        match self {
+            Self::Eight => 1.0,
+            Self::TenHigh => 65535.0 / 65472.0,
+            Self::TenLow => 65535.0 / 1023.0,
+        }
+    }
+}
+
 /// Abstraction for a GPU-based pixel format converter.
 /// It manages its own source textures (e.g., Y and UV planes) and pipeline.
 pub trait PixelConverter: Send + Sync {
@@ -23,6 +59,20 @@ pub trait PixelConverter: Send + Sync {
     /// Uploads the raw frame data to the GPU source textures.
     fn update(&mut self, queue: &wgpu::Queue, frame: &frame::Video);
 
+    /// Luma and chroma source textures of the semi-planar converters, hardware frames being
+    /// copied into them on the GPU instead of uploaded. `None` for the other converters.
+    fn plane_textures(&self) -> Option<[&wgpu::Texture; 2]> {
+        None
+    }
+
+    /// Sets the YUV to RGB conversion of the next renders.
+    fn set_colorimetry(
+        &mut self,
+        queue: &wgpu::Queue,
+        colorimetry: Colorimetry,
+        tone_mapping: ToneMapping,
+    );
+
     /// Records the draw command to convert source -> target.
     /// The `RenderPass` provided must target the `VideoPlayer`'s output texture.
     fn render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>);
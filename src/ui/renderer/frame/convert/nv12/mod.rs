@@ -1,21 +1,26 @@
-use eframe::wgpu::include_wgsl;
 use ffmpeg_next::frame;
 use textures::Nv12Textures;
 use wgpu::util::DeviceExt;
 
+use crate::services::session::ToneMapping;
+use crate::ui::renderer::frame::convert::color::{ColorUniform, Colorimetry};
 use crate::ui::renderer::frame::convert::nv12::vertex::{VERTICES, Vertex};
 use crate::ui::renderer::frame::convert::utils::{
     create_bind_group_layout, create_sampler, write_texture,
 };
-use crate::ui::renderer::frame::convert::{PixelConverter, TEXTURE_FORMAT};
+use crate::ui::renderer::frame::convert::{PixelConverter, SampleDepth, TEXTURE_FORMAT};
 pub mod textures;
 mod vertex;
 
+/// Semi-planar 4:2:0 converter: NV12, or P010 with 16-bit textures
 pub struct Nv12Converter {
     pipeline: wgpu::RenderPipeline,
     bind_group: wgpu::BindGroup,
+    /// Color conversion of the frames
+    color: ColorUniform,
     vertex_buffer: wgpu::Buffer,
     textures: Nv12Textures,
+    depth: SampleDepth,
     width: u32,
     height: u32,
 }
@@ -23,8 +28,8 @@ pub struct Nv12Converter {
 impl Nv12Converter {
     pub const NAME: &'static str = "NV12 Converter";
 
-    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
-        let textures = Nv12Textures::new(device, width, height);
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, depth: SampleDepth) -> Self {
+        let textures = Nv12Textures::new(device, width, height, depth);
 
         // Sampler
         let sampler = create_sampler(device, Some(Self::NAME));
@@ -33,38 +38,33 @@ impl Nv12Converter {
         let bind_group_layout = create_bind_group_layout::<3>(device, Some(Self::NAME));
 
         // Bind Group
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("NV12 Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(
-                        &textures
-                            .y()
-                            .create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(
-                        &textures
-                            .uv()
-                            .create_view(&wgpu::TextureViewDescriptor::default()),
-                    ),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-            ],
-        });
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &sampler,
+            &textures
+                .y()
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            &textures
+                .uv()
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+        );
+
+        // Color Matrix
+        let color = ColorUniform::new(device, Some(Self::NAME), depth.sample_scale());
 
         // Pipeline
-        let shader = device.create_shader_module(include_wgsl!("rgba.wgsl"));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(Self::NAME),
+            source: wgpu::ShaderSource::Wgsl(
+                [ColorUniform::SHADER, include_str!("rgba.wgsl")]
+                    .concat()
+                    .into(),
+            ),
+        });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some(Self::NAME),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, color.layout()],
             push_constant_ranges: &[],
         });
 
@@ -103,12 +103,41 @@ impl Nv12Converter {
         Self {
             pipeline,
             bind_group,
+            color,
             vertex_buffer,
             textures,
+            depth,
             width,
             height,
         }
     }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        y: &wgpu::TextureView,
+        uv: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("NV12 Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(y),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(uv),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        })
+    }
 }
 
 impl PixelConverter for Nv12Converter {
@@ -139,14 +168,28 @@ impl PixelConverter for Nv12Converter {
         );
     }
 
+    fn plane_textures(&self) -> Option<[&wgpu::Texture; 2]> {
+        Some([self.textures.y(), self.textures.uv()])
+    }
+
+    fn set_colorimetry(
+        &mut self,
+        queue: &wgpu::Queue,
+        colorimetry: Colorimetry,
+        tone_mapping: ToneMapping,
+    ) {
+        self.color.update(queue, colorimetry, tone_mapping);
+    }
+
     fn render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, self.color.bind_group(), &[]);
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         pass.draw(0..6, 0..1);
     }
 
     fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
-        *self = Self::new(device, width, height);
+        *self = Self::new(device, width, height, self.depth);
     }
 }
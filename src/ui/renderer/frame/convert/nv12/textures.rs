@@ -1,6 +1,7 @@
-use eframe::wgpu::{Device, Texture, TextureFormat, TextureUsages};
+use eframe::wgpu::{Device, Texture, TextureUsages};
 
 use crate::ui::renderer::create_2d_texture;
+use crate::ui::renderer::frame::convert::SampleDepth;
 
 pub struct Nv12Textures {
     y: Texture,
@@ -8,18 +9,12 @@ pub struct Nv12Textures {
 }
 
 impl Nv12Textures {
-    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+    pub fn new(device: &Device, width: u32, height: u32, depth: SampleDepth) -> Self {
         let usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
 
         Self {
-            y: create_2d_texture(device, width, height, TextureFormat::R8Unorm, usage),
-            uv: create_2d_texture(
-                device,
-                width / 2,
-                height / 2,
-                TextureFormat::Rg8Unorm,
-                usage,
-            ),
+            y: create_2d_texture(device, width, height, depth.plane_format(1), usage),
+            uv: create_2d_texture(device, width / 2, height / 2, depth.plane_format(2), usage),
         }
     }
 
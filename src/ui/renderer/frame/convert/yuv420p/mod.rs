@@ -1,21 +1,26 @@
 use ffmpeg_next::frame;
-use wgpu::include_wgsl;
 use wgpu::util::DeviceExt;
 
+use crate::services::session::ToneMapping;
+use crate::ui::renderer::frame::convert::color::{ColorUniform, Colorimetry};
 use crate::ui::renderer::frame::convert::utils::{
     create_bind_group_layout, create_sampler, write_texture,
 };
 use crate::ui::renderer::frame::convert::yuv420p::textures::Yuv420pTextures;
 use crate::ui::renderer::frame::convert::yuv420p::vertex::{VERTICES, Vertex};
-use crate::ui::renderer::frame::convert::{PixelConverter, TEXTURE_FORMAT};
+use crate::ui::renderer::frame::convert::{PixelConverter, SampleDepth, TEXTURE_FORMAT};
 mod textures;
 mod vertex;
 
+/// Planar 4:2:0 converter: YUV420P, or YUV420P10 with 16-bit textures
 pub struct Yuv420pConverter {
     pipeline: wgpu::RenderPipeline,
     bind_group: wgpu::BindGroup,
+    /// Color conversion of the frames
+    color: ColorUniform,
     vertex_buffer: wgpu::Buffer,
     textures: Yuv420pTextures,
+    depth: SampleDepth,
     width: u32,
     height: u32,
 }
@@ -23,8 +28,8 @@ pub struct Yuv420pConverter {
 impl Yuv420pConverter {
     pub const NAME: &'static str = "YUV420P Converter";
 
-    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
-        let textures = Yuv420pTextures::new(device, width, height);
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, depth: SampleDepth) -> Self {
+        let textures = Yuv420pTextures::new(device, width, height, depth);
 
         // Sampler
         let sampler = create_sampler(device, Some(Self::NAME));
@@ -68,11 +73,21 @@ impl Yuv420pConverter {
             ],
         });
 
+        // Color Matrix
+        let color = ColorUniform::new(device, Some(Self::NAME), depth.sample_scale());
+
         // Pipeline
-        let shader = device.create_shader_module(include_wgsl!("rgba.wgsl"));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(Self::NAME),
+            source: wgpu::ShaderSource::Wgsl(
+                [ColorUniform::SHADER, include_str!("rgba.wgsl")]
+                    .concat()
+                    .into(),
+            ),
+        });
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some(Self::NAME),
-            bind_group_layouts: &[&bind_group_layout],
+            bind_group_layouts: &[&bind_group_layout, color.layout()],
             push_constant_ranges: &[],
         });
 
@@ -111,8 +126,10 @@ impl Yuv420pConverter {
         Self {
             pipeline,
             bind_group,
+            color,
             vertex_buffer,
             textures,
+            depth,
             width,
             height,
         }
@@ -156,14 +173,24 @@ impl PixelConverter for Yuv420pConverter {
         );
     }
 
+    fn set_colorimetry(
+        &mut self,
+        queue: &wgpu::Queue,
+        colorimetry: Colorimetry,
+        tone_mapping: ToneMapping,
+    ) {
+        self.color.update(queue, colorimetry, tone_mapping);
+    }
+
     fn render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, self.color.bind_group(), &[]);
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         pass.draw(0..6, 0..1);
     }
 
     fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
-        *self = Self::new(device, width, height);
+        *self = Self::new(device, width, height, self.depth);
     }
 }
@@ -1,6 +1,7 @@
-use eframe::wgpu::{Device, Texture, TextureFormat, TextureUsages};
+use eframe::wgpu::{Device, Texture, TextureUsages};
 
 use crate::ui::renderer::create_2d_texture;
+use crate::ui::renderer::frame::convert::SampleDepth;
 
 pub struct Yuv420pTextures {
     y: Texture,
@@ -9,13 +10,14 @@ pub struct Yuv420pTextures {
 }
 
 impl Yuv420pTextures {
-    pub fn new(device: &Device, width: u32, height: u32) -> Self {
+    pub fn new(device: &Device, width: u32, height: u32, depth: SampleDepth) -> Self {
         let usage = TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST;
+        let format = depth.plane_format(1);
 
         Self {
-            y: create_2d_texture(device, width, height, TextureFormat::R8Unorm, usage),
-            u: create_2d_texture(device, width / 2, height / 2, TextureFormat::R8Unorm, usage),
-            v: create_2d_texture(device, width / 2, height / 2, TextureFormat::R8Unorm, usage),
+            y: create_2d_texture(device, width, height, format, usage),
+            u: create_2d_texture(device, width / 2, height / 2, format, usage),
+            v: create_2d_texture(device, width / 2, height / 2, format, usage),
         }
     }
 
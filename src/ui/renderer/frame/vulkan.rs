@@ -0,0 +1,370 @@
+//! GPU copy of the Vulkan decoded frames
+
+#![allow(unsafe_code)]
+
+use ash::vk;
+use ffmpeg_next::{format, frame};
+use wgpu::hal::api::Vulkan;
+
+use crate::transcoding::video::vulkan::VulkanFrame;
+
+/// Copies the planes of the frames decoded on the renderer device into the converter textures,
+/// without leaving the GPU.
+///
+/// The copies are submitted directly to the renderer queue, the textures being left in the
+/// sampling state wgpu expects. They wait for the decoding on the GPU, and the frames are kept
+/// alive until their copy completes, their image being returned to the decoder afterwards.
+///
+/// The renderer queue is only used from the ui thread, which submits the copies.
+pub struct FrameImporter {
+    /// Also keeps the raw device alive
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    raw_device: ash::Device,
+    raw_queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    slots: [CopySlot; Self::SLOTS],
+    /// Slot of the next copy
+    next: usize,
+}
+
+/// Command buffer of a copy, with the frame it reads
+struct CopySlot {
+    command_buffer: vk::CommandBuffer,
+    /// Signaled once the copy completed
+    fence: vk::Fence,
+    /// `None` once released
+    frame: Option<frame::Video>,
+}
+
+impl FrameImporter {
+    /// Copies in flight
+    const SLOTS: usize = 2;
+    /// Maximum wait for a previous copy to complete, in nanoseconds
+    const COPY_TIMEOUT: u64 = 100_000_000;
+
+    /// Creates the copy resources on the renderer queue. `None` if the renderer does not use
+    /// Vulkan.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        let hal_device = unsafe { device.as_hal::<Vulkan>() }?;
+        let raw_device = hal_device.raw_device().clone();
+        let raw_queue = hal_device.raw_queue();
+        let family = hal_device.queue_family_index();
+        drop(hal_device);
+
+        let pool_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(family);
+        let command_pool = match unsafe { raw_device.create_command_pool(&pool_info, None) } {
+            Ok(pool) => pool,
+            Err(err) => {
+                error!("Frame importer: command pool creation failed: {err}");
+                return None;
+            }
+        };
+
+        // The command buffers are released with the pool.
+        #[allow(clippy::cast_possible_truncation)]
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(Self::SLOTS as u32);
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        let slots = unsafe {
+            raw_device
+                .allocate_command_buffers(&allocate_info)
+                .and_then(|command_buffers| {
+                    let mut slots = Vec::with_capacity(Self::SLOTS);
+                    for command_buffer in command_buffers {
+                        match raw_device.create_fence(&fence_info, None) {
+                            Ok(fence) => slots.push(CopySlot {
+                                command_buffer,
+                                fence,
+                                frame: None,
+                            }),
+                            Err(err) => {
+                                for slot in slots {
+                                    raw_device.destroy_fence(slot.fence, None);
+                                }
+                                return Err(err);
+                            }
+                        }
+                    }
+                    Ok(slots)
+                })
+        };
+        let slots = match slots.map(<[CopySlot; Self::SLOTS]>::try_from) {
+            Ok(Ok(slots)) => slots,
+            Ok(Err(_)) => unreachable!("One command buffer is allocated per slot"),
+            Err(err) => {
+                error!("Frame importer: command buffers creation failed: {err}");
+                unsafe { raw_device.destroy_command_pool(command_pool, None) };
+                return None;
+            }
+        };
+
+        Some(Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            raw_device,
+            raw_queue,
+            command_pool,
+            slots,
+            next: 0,
+        })
+    }
+
+    /// Layout of the planes of a frame that can be copied, NV12 or P010.
+    ///
+    /// Returns `None` for the software frames, the frames of another device or the unsupported
+    /// formats.
+    pub fn format(&self, frame: &frame::Video) -> Option<format::Pixel> {
+        let vk_frame = VulkanFrame::new(frame)?;
+        let format = vk_frame.sw_format();
+        let (width, height) = vk_frame.image_size();
+        let supported = matches!(format, format::Pixel::NV12 | format::Pixel::P010LE)
+            && width >= frame.width()
+            && height >= frame.height()
+            && vk_frame.device() == self.raw_device.handle();
+
+        supported.then_some(format)
+    }
+
+    /// Copies the planes of a frame into the luma and chroma textures, which must have the frame
+    /// size and the plane formats.
+    ///
+    /// Returns `false` if the copy could not be submitted.
+    pub fn copy(&mut self, frame: &frame::Video, planes: [&wgpu::Texture; 2]) -> bool {
+        let Some(vk_frame) = VulkanFrame::new(frame) else {
+            return false;
+        };
+
+        // The frames of the completed copies are released.
+        for slot in &mut self.slots {
+            if slot.frame.is_some()
+                && unsafe { self.raw_device.get_fence_status(slot.fence) } == Ok(true)
+            {
+                slot.frame = None;
+            }
+        }
+
+        let CopySlot {
+            command_buffer,
+            fence,
+            ..
+        } = self.slots[self.next];
+        let waited = unsafe {
+            self.raw_device
+                .wait_for_fences(&[fence], true, Self::COPY_TIMEOUT)
+        };
+        if let Err(err) = waited {
+            error!("Frame importer: previous copy wait failed: {err}");
+            return false;
+        }
+        self.slots[self.next].frame = None;
+
+        // wgpu tracks the textures in the sampling state, the copy leaves them in.
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.transition_resources(
+            std::iter::empty(),
+            planes.iter().map(|texture| wgpu::TextureTransition {
+                texture: *texture,
+                selector: None,
+                state: wgpu::TextureUses::RESOURCE,
+            }),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let Some(targets) = planes
+            .iter()
+            .map(|texture| unsafe { texture.as_hal::<Vulkan>().map(|raw| raw.raw_handle()) })
+            .collect::<Option<Vec<_>>>()
+        else {
+            return false;
+        };
+
+        let lock = vk_frame.lock();
+        let Some(image) = lock.image() else {
+            return false;
+        };
+        let (semaphore, value) = lock.semaphore();
+        let layout = lock.layout();
+
+        let recorded = self.record(
+            command_buffer,
+            (image, layout),
+            &targets,
+            (frame.width(), frame.height()),
+        );
+        if let Err(err) = recorded {
+            error!("Frame importer: copy recording failed: {err}");
+            return false;
+        }
+
+        // The copy waits for the decoding on the GPU.
+        let mut timeline = vk::TimelineSemaphoreSubmitInfo::default()
+            .wait_semaphore_values(std::slice::from_ref(&value));
+        let wait_stage = vk::PipelineStageFlags::TRANSFER;
+        let submit = vk::SubmitInfo::default()
+            .wait_semaphores(std::slice::from_ref(&semaphore))
+            .wait_dst_stage_mask(std::slice::from_ref(&wait_stage))
+            .command_buffers(std::slice::from_ref(&command_buffer))
+            .push_next(&mut timeline);
+        let submitted = unsafe {
+            self.raw_device.reset_fences(&[fence]).and_then(|()| {
+                self.raw_device
+                    .queue_submit(self.raw_queue, &[submit], fence)
+            })
+        };
+        if let Err(err) = submitted {
+            error!("Frame importer: copy submission failed: {err}");
+            return false;
+        }
+
+        lock.set_layout(
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::AccessFlags::TRANSFER_READ,
+        );
+        drop(lock);
+
+        self.slots[self.next].frame = Some(frame.clone());
+        self.next = (self.next + 1) % Self::SLOTS;
+
+        true
+    }
+
+    /// Records the copy of the image planes into the targets, transitioning the image from its
+    /// decoder state and the targets back to the sampling state.
+    fn record(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        (image, layout): (vk::Image, vk::ImageLayout),
+        targets: &[vk::Image],
+        (width, height): (u32, u32),
+    ) -> Result<(), vk::Result> {
+        let range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(1)
+            .layer_count(1);
+        // The decoder image is shared with the queue families of the decoder.
+        let barrier = |image, (old_layout, src_access), (new_layout, dst_access)| {
+            vk::ImageMemoryBarrier::default()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+        };
+        let sampled = (
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::AccessFlags::SHADER_READ,
+        );
+        let written = (
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        // The decoding writes are visible once the semaphore waited on is signaled. The targets
+        // are entirely overwritten, their previous content is discarded.
+        let mut before = vec![barrier(
+            image,
+            (layout, vk::AccessFlags::empty()),
+            (
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::AccessFlags::TRANSFER_READ,
+            ),
+        )];
+        before.extend(targets.iter().map(|target| {
+            barrier(
+                *target,
+                (vk::ImageLayout::UNDEFINED, vk::AccessFlags::SHADER_READ),
+                written,
+            )
+        }));
+        let after = targets
+            .iter()
+            .map(|target| barrier(*target, written, sampled))
+            .collect::<Vec<_>>();
+
+        // The chroma plane has half the size of the luma plane.
+        let regions = [
+            (vk::ImageAspectFlags::PLANE_0, 1),
+            (vk::ImageAspectFlags::PLANE_1, 2),
+        ]
+        .map(|(aspect, divisor)| {
+            let layers = |aspect| {
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(aspect)
+                    .layer_count(1)
+            };
+            vk::ImageCopy::default()
+                .src_subresource(layers(aspect))
+                .dst_subresource(layers(vk::ImageAspectFlags::COLOR))
+                .extent(vk::Extent3D {
+                    width: width / divisor,
+                    height: height / divisor,
+                    depth: 1,
+                })
+        });
+
+        let device = &self.raw_device;
+        unsafe {
+            device.reset_command_buffer(command_buffer, vk::CommandBufferResetFlags::empty())?;
+            device.begin_command_buffer(
+                command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            )?;
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &before,
+            );
+            for (target, region) in targets.iter().zip(regions) {
+                device.cmd_copy_image(
+                    command_buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    *target,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                );
+            }
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &after,
+            );
+            device.end_command_buffer(command_buffer)
+        }
+    }
+}
+
+impl Drop for FrameImporter {
+    fn drop(&mut self) {
+        let fences = self.slots.iter().map(|slot| slot.fence).collect::<Vec<_>>();
+        unsafe {
+            if let Err(err) = self.raw_device.wait_for_fences(&fences, true, u64::MAX) {
+                error!("Frame importer: copies wait failed: {err}");
+            }
+            for fence in fences {
+                self.raw_device.destroy_fence(fence, None);
+            }
+            self.raw_device
+                .destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
@@ -4,8 +4,10 @@ use wgpu::{
     Device, Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
 };
 
+pub mod device;
 pub mod frame;
 pub mod offscreen_texture;
+pub mod screen_mapping;
 pub mod video_player;
 
 /// Create a new 2d texture
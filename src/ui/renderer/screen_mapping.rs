@@ -0,0 +1,329 @@
+//! Mapping between viewport points and device pixels
+
+use eframe::egui::{Pos2, Rect, pos2, vec2};
+
+use crate::services::control::Position;
+
+/// Clockwise rotation of the displayed video.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Orientation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Orientation {
+    /// Orientation rotated by a quarter turn clockwise.
+    pub const fn clockwise(self) -> Self {
+        match self {
+            Self::Deg0 => Self::Deg90,
+            Self::Deg90 => Self::Deg180,
+            Self::Deg180 => Self::Deg270,
+            Self::Deg270 => Self::Deg0,
+        }
+    }
+
+    /// Orientation rotated by a quarter turn counterclockwise.
+    pub const fn counterclockwise(self) -> Self {
+        match self {
+            Self::Deg0 => Self::Deg270,
+            Self::Deg90 => Self::Deg0,
+            Self::Deg180 => Self::Deg90,
+            Self::Deg270 => Self::Deg180,
+        }
+    }
+
+    /// Whether the video width is displayed vertically.
+    pub const fn is_transposed(self) -> bool {
+        matches!(self, Self::Deg90 | Self::Deg270)
+    }
+
+    /// Maps normalized display coordinates to normalized video coordinates.
+    fn display_to_video(self, p: Pos2) -> Pos2 {
+        match self {
+            Self::Deg0 => p,
+            Self::Deg90 => pos2(p.y, 1.0 - p.x),
+            Self::Deg180 => pos2(1.0 - p.x, 1.0 - p.y),
+            Self::Deg270 => pos2(1.0 - p.y, p.x),
+        }
+    }
+
+    /// Maps normalized video coordinates to normalized display coordinates.
+    fn video_to_display(self, p: Pos2) -> Pos2 {
+        match self {
+            Self::Deg0 => p,
+            Self::Deg90 => pos2(1.0 - p.y, p.x),
+            Self::Deg180 => pos2(1.0 - p.x, 1.0 - p.y),
+            Self::Deg270 => pos2(p.y, 1.0 - p.x),
+        }
+    }
+}
+
+/// Bidirectional mapping between viewport points and device pixels.
+///
+/// Accounts for the letterboxing, the displayed video region and the display rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenMapping {
+    /// Video size in pixels (width, height)
+    video_size: (u32, u32),
+    /// Displayed video region, normalized to `[0, 1]`
+    crop: Rect,
+    /// Display rotation
+    orientation: Orientation,
+    /// Displayed image rectangle, in points
+    image_rect: Rect,
+}
+
+impl ScreenMapping {
+    /// Full video region, without crop nor zoom.
+    pub const FULL: Rect = Rect::from_min_max(Pos2::ZERO, pos2(1.0, 1.0));
+
+    /// Creates a mapping for a video displayed in `image_rect`.
+    ///
+    /// Returns `None` if the video or the displayed rectangle is empty.
+    ///
+    /// # Arguments
+    ///
+    /// - `video_size`: Video size in pixels (width, height).
+    /// - `crop`: Displayed video region, normalized to `[0, 1]`.
+    /// - `orientation`: Display rotation.
+    /// - `image_rect`: Displayed image rectangle, in points.
+    pub fn new(
+        video_size: (u32, u32),
+        crop: Rect,
+        orientation: Orientation,
+        image_rect: Rect,
+    ) -> Option<Self> {
+        let crop = crop.intersect(Self::FULL);
+
+        if video_size.0 == 0
+            || video_size.1 == 0
+            || crop.width() <= 0.0
+            || crop.height() <= 0.0
+            || image_rect.width() <= 0.0
+            || image_rect.height() <= 0.0
+        {
+            return None;
+        }
+
+        Some(Self {
+            video_size,
+            crop,
+            orientation,
+            image_rect,
+        })
+    }
+
+    /// Creates a mapping for a video letterboxed and centered in `available`.
+    ///
+    /// # Arguments
+    ///
+    /// - `video_size`: Video size in pixels (width, height).
+    /// - `crop`: Displayed video region, normalized to `[0, 1]`.
+    /// - `orientation`: Display rotation.
+    /// - `available`: Available rectangle, in points.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn fit(
+        video_size: (u32, u32),
+        crop: Rect,
+        orientation: Orientation,
+        available: Rect,
+    ) -> Option<Self> {
+        let crop = crop.intersect(Self::FULL);
+
+        let mut displayed = vec2(
+            crop.width() * video_size.0 as f32,
+            crop.height() * video_size.1 as f32,
+        );
+        if orientation.is_transposed() {
+            displayed = vec2(displayed.y, displayed.x);
+        }
+
+        if displayed.x <= 0.0 || displayed.y <= 0.0 {
+            return None;
+        }
+
+        let scale = (available.width() / displayed.x).min(available.height() / displayed.y);
+        let image_rect = Rect::from_center_size(available.center(), displayed * scale);
+
+        Self::new(video_size, crop, orientation, image_rect)
+    }
+
+    /// Displayed image rectangle, in points
+    pub const fn image_rect(&self) -> Rect {
+        self.image_rect
+    }
+
+    /// Normalized texture coordinates of the displayed image corners,
+    /// in order: top left, top right, bottom right, bottom left.
+    pub fn corners_uv(&self) -> [Pos2; 4] {
+        [
+            pos2(0.0, 0.0),
+            pos2(1.0, 0.0),
+            pos2(1.0, 1.0),
+            pos2(0.0, 1.0),
+        ]
+        .map(|corner| self.uncrop(self.orientation.display_to_video(corner)))
+    }
+
+    /// Maps a viewport point to the device pixel under it.
+    ///
+    /// Returns `None` if the point falls outside the displayed image, in the black bars.
+    pub fn to_device(&self, pos: Pos2) -> Option<(i32, i32)> {
+        if !self.image_rect.contains(pos) {
+            return None;
+        }
+
+        Some(self.to_device_unchecked(pos))
+    }
+
+    /// Maps a viewport point to the nearest device pixel, clamping it to the displayed image.
+    pub fn to_device_clamped(&self, pos: Pos2) -> (i32, i32) {
+        self.to_device_unchecked(self.image_rect.clamp(pos))
+    }
+
+    /// Maps a device pixel to the viewport point at its center.
+    ///
+    /// The point might fall outside the displayed image if the pixel is cropped out.
+    pub fn to_screen(&self, point: (i32, i32)) -> Pos2 {
+        let video = self.normalize(point);
+        let display = self.orientation.video_to_display(self.crop_local(video));
+
+        self.image_rect.min + display.to_vec2() * self.image_rect.size()
+    }
+
+    /// Maps normalized video coordinates to the device pixel under them, clamped to the video.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_wrap
+    )]
+    pub fn denormalize(&self, p: Pos2) -> (i32, i32) {
+        let (width, height) = self.video_size;
+
+        let x = (p.x * width as f32).floor() as i32;
+        let y = (p.y * height as f32).floor() as i32;
+
+        (x.clamp(0, width as i32 - 1), y.clamp(0, height as i32 - 1))
+    }
+
+    /// Maps a device pixel to the normalized video coordinates of its center.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn normalize(&self, point: (i32, i32)) -> Pos2 {
+        let (width, height) = self.video_size;
+
+        pos2(
+            (point.0 as f32 + 0.5) / width as f32,
+            (point.1 as f32 + 0.5) / height as f32,
+        )
+    }
+
+    /// Device position for a device pixel, with the screen size expected by the device.
+    pub fn position(&self, point: (i32, i32)) -> Position {
+        let (width, height) = self.video_size;
+
+        Position {
+            point,
+            screen_size: (
+                u16::try_from(width).unwrap_or(u16::MAX),
+                u16::try_from(height).unwrap_or(u16::MAX),
+            ),
+        }
+    }
+
+    fn to_device_unchecked(&self, pos: Pos2) -> (i32, i32) {
+        let display = ((pos - self.image_rect.min) / self.image_rect.size()).to_pos2();
+
+        self.denormalize(self.uncrop(self.orientation.display_to_video(display)))
+    }
+
+    /// Maps normalized crop coordinates to normalized video coordinates.
+    fn uncrop(&self, p: Pos2) -> Pos2 {
+        self.crop.min + p.to_vec2() * self.crop.size()
+    }
+
+    /// Maps normalized video coordinates to normalized crop coordinates.
+    fn crop_local(&self, p: Pos2) -> Pos2 {
+        ((p - self.crop.min) / self.crop.size()).to_pos2()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eframe::egui::{Rect, pos2, vec2};
+
+    use crate::ui::renderer::screen_mapping::{Orientation, ScreenMapping};
+
+    const ORIENTATIONS: [Orientation; 4] = [
+        Orientation::Deg0,
+        Orientation::Deg90,
+        Orientation::Deg180,
+        Orientation::Deg270,
+    ];
+
+    #[test]
+    fn test_round_trip() {
+        let available = Rect::from_min_size(pos2(13.0, 7.0), vec2(500.0, 377.0));
+        let crops = [
+            ScreenMapping::FULL,
+            Rect::from_min_max(pos2(0.25, 0.1), pos2(0.75, 0.6)),
+        ];
+
+        for orientation in ORIENTATIONS {
+            for crop in crops {
+                let mapping =
+                    ScreenMapping::fit((1080, 2400), crop, orientation, available).unwrap();
+
+                for point in [(540, 500), (300, 400), (809, 1439), (270, 240)] {
+                    let pos = mapping.to_screen(point);
+                    assert_eq!(
+                        mapping.to_device(pos),
+                        Some(point),
+                        "{orientation:?} {crop:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_letterbox() {
+        let available = Rect::from_min_size(pos2(0.0, 0.0), vec2(400.0, 400.0));
+        let mapping = ScreenMapping::fit(
+            (1000, 500),
+            ScreenMapping::FULL,
+            Orientation::Deg0,
+            available,
+        )
+        .unwrap();
+
+        assert_eq!(
+            mapping.image_rect(),
+            Rect::from_min_max(pos2(0.0, 100.0), pos2(400.0, 300.0))
+        );
+        assert_eq!(mapping.to_device(pos2(200.0, 50.0)), None);
+        assert_eq!(mapping.to_device(pos2(200.0, 350.0)), None);
+        assert_eq!(mapping.to_device_clamped(pos2(-10.0, 50.0)), (0, 0));
+        assert_eq!(mapping.to_device(pos2(0.0, 100.0)), Some((0, 0)));
+        assert_eq!(mapping.to_device(pos2(400.0, 300.0)), Some((999, 499)));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let available = Rect::from_min_size(pos2(0.0, 0.0), vec2(500.0, 1000.0));
+        let mapping = ScreenMapping::fit(
+            (1000, 500),
+            ScreenMapping::FULL,
+            Orientation::Deg90,
+            available,
+        )
+        .unwrap();
+
+        assert_eq!(mapping.image_rect(), available);
+        // Rotated clockwise: the video top left corner is displayed top right.
+        assert_eq!(mapping.to_device(pos2(499.0, 0.0)), Some((0, 1)));
+        assert_eq!(mapping.to_device(pos2(0.0, 999.0)), Some((999, 499)));
+    }
+}
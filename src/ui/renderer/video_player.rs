@@ -1,14 +1,59 @@
 //! High level video rendering interface
 
+use std::fmt;
+use std::time::Instant;
+
 use eframe::egui;
 use eframe::egui_wgpu::RenderState;
-use ffmpeg_next::frame;
 use ffmpeg_next::util::format;
 
-use crate::ui::renderer::frame::convert::PixelConverter;
+use crate::services::session::ToneMapping;
+use crate::transcoding::video::frame_buffer::FrameBuffer;
+use crate::transcoding::video::scaler::FrameScaler;
+use crate::ui::renderer::frame::convert::color::Colorimetry;
 use crate::ui::renderer::frame::convert::nv12::Nv12Converter;
 use crate::ui::renderer::frame::convert::yuv420p::Yuv420pConverter;
+use crate::ui::renderer::frame::convert::{PixelConverter, SampleDepth};
+use crate::ui::renderer::frame::vulkan::FrameImporter;
 use crate::ui::renderer::offscreen_texture::OffscreenTexture;
+use crate::ui::renderer::screen_mapping::{Orientation, ScreenMapping};
+
+/// Route of the decoded frames to the converter, shown in the session overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramePath {
+    /// Hardware frame copied on the GPU
+    Imported(format::Pixel),
+    /// Hardware frame downloaded to the CPU and uploaded
+    Downloaded(format::Pixel),
+    /// Software frame uploaded
+    Uploaded(format::Pixel),
+    /// Software frame converted by swscale and uploaded
+    Scaled {
+        from: format::Pixel,
+        to: format::Pixel,
+    },
+}
+
+impl FramePath {
+    /// Pixel format of the converter
+    pub const fn format(self) -> format::Pixel {
+        match self {
+            Self::Imported(format) | Self::Downloaded(format) | Self::Uploaded(format) => format,
+            Self::Scaled { to, .. } => to,
+        }
+    }
+}
+
+impl fmt::Display for FramePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Imported(format) => write!(f, "GPU COPY {format:?}"),
+            Self::Downloaded(format) => write!(f, "DOWNLOAD {format:?}"),
+            Self::Uploaded(format) => write!(f, "UPLOAD {format:?}"),
+            Self::Scaled { from, to } => write!(f, "SWSCALE {from:?} > {to:?}"),
+        }
+    }
+}
 
 /// Video player, automatically handle size and format changes
 pub struct VideoPlayer {
@@ -19,26 +64,51 @@ pub struct VideoPlayer {
     converter: Option<Box<dyn PixelConverter>>,
     /// Current pixel format
     current_format: Option<format::Pixel>,
+    /// GPU copy of the hardware frames, `None` if the renderer does not use Vulkan
+    importer: Option<FrameImporter>,
+    /// Fallback conversion of the formats without a GPU converter
+    scaler: FrameScaler,
+    /// Video path of the last frame
+    path: Option<FramePath>,
+    /// HDR to SDR conversion
+    tone_mapping: ToneMapping,
     /// Last runtime error
     last_error: Option<String>,
 }
 
 impl VideoPlayer {
-    pub fn new(state: RenderState) -> Self {
+    pub fn new(state: RenderState, tone_mapping: ToneMapping) -> Self {
+        let importer = FrameImporter::new(&state.device, &state.queue);
+
         Self {
             state,
             texture: None,
             converter: None,
             current_format: None,
+            importer,
+            scaler: FrameScaler::default(),
+            path: None,
+            tone_mapping,
             last_error: None,
         }
     }
 
-    /// Called every frame with new data
-    pub fn update(&mut self, frame: &frame::Video) {
+    /// Video path of the last frame
+    pub const fn path(&self) -> Option<FramePath> {
+        self.path
+    }
+
+    /// Called every frame with new data.
+    ///
+    /// Hardware frames decoded on the renderer device are copied on the GPU, others are
+    /// downloaded to the CPU and uploaded again. Formats without a GPU converter are converted
+    /// by swscale first.
+    ///
+    /// Returns the instant the texture upload was submitted at, `None` if the frame is not
+    /// displayed.
+    pub fn update(&mut self, frame: &mut FrameBuffer) -> Option<Instant> {
         let width = frame.width();
         let height = frame.height();
-        let format = frame.format();
 
         // let planes = frame.planes();
         // let (size, stride) = if frame.planes() > 0 {
@@ -58,16 +128,73 @@ impl VideoPlayer {
         // );
 
         if width == 0 || height == 0 {
-            return;
+            return None;
         }
 
         self.last_error.take();
 
+        let high_depth = self
+            .state
+            .device
+            .features()
+            .contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM);
+
+        // P010 planes are copied into 16-bit textures.
+        let imported = match (&*frame, &self.importer) {
+            (FrameBuffer::Hw(hw_frame), Some(importer)) => importer
+                .format(hw_frame)
+                .filter(|format| *format == format::Pixel::NV12 || high_depth),
+            _ => None,
+        };
+        let downloaded = imported.is_none() && matches!(frame, FrameBuffer::Hw(_));
+        if imported.is_none()
+            && let Err(err) = frame.download_to_cpu()
+        {
+            error!("Ffmpeg: {err:?}");
+            self.last_error.replace(err.to_string());
+            return None;
+        }
+
+        let path = imported.map_or_else(
+            || {
+                let source = frame.format();
+                match Self::converter_format(source, high_depth) {
+                    Some(format) if downloaded => FramePath::Downloaded(format),
+                    Some(format) => FramePath::Uploaded(format),
+                    // Other formats are converted by swscale, keeping 10-bit samples if possible.
+                    None if high_depth && FrameScaler::is_high_depth(source) => FramePath::Scaled {
+                        from: source,
+                        to: format::Pixel::YUV420P10LE,
+                    },
+                    None => FramePath::Scaled {
+                        from: source,
+                        to: format::Pixel::YUV420P,
+                    },
+                }
+            },
+            FramePath::Imported,
+        );
+        let format = path.format();
+        self.path.replace(path);
+
         if let Err(err) = self.ensure_resources(width, height, format) {
             self.last_error.replace(err);
-            return;
+            return None;
         }
 
+        let source = match path {
+            FramePath::Scaled { to, .. } => match self.scaler.run(frame, to) {
+                Ok(scaled) => scaled,
+                Err(err) => {
+                    error!("Ffmpeg: {err:?}");
+                    self.last_error.replace(err.to_string());
+                    return None;
+                }
+            },
+            _ => frame.frame(),
+        };
+        let colorimetry = Colorimetry::from_frame(source);
+
         let device = &self.state.device;
         let queue = &self.state.queue;
 
@@ -80,7 +207,23 @@ impl VideoPlayer {
             .as_mut()
             .expect("We ensured resources are created");
 
-        converter.update(queue, frame);
+        if let Some(importer) = self
+            .importer
+            .as_mut()
+            .filter(|_| matches!(path, FramePath::Imported(_)))
+        {
+            let copied = converter
+                .plane_textures()
+                .is_some_and(|planes| importer.copy(source, planes));
+            if !copied {
+                self.last_error
+                    .replace(format!("Hardware frame copy failed: {format:?}"));
+                return None;
+            }
+        } else {
+            converter.update(queue, source);
+        }
+        converter.set_colorimetry(queue, colorimetry, self.tone_mapping);
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
         {
@@ -104,6 +247,20 @@ impl VideoPlayer {
         }
 
         queue.submit(Some(encoder.finish()));
+
+        Some(Instant::now())
+    }
+
+    /// Converter format of a software frame, `None` if it has no GPU converter.
+    ///
+    /// YUVJ only differs by its range, handled by the colorimetry.
+    const fn converter_format(format: format::Pixel, high_depth: bool) -> Option<format::Pixel> {
+        match format {
+            format::Pixel::NV12 | format::Pixel::YUV420P => Some(format),
+            format::Pixel::YUVJ420P => Some(format::Pixel::YUV420P),
+            format::Pixel::P010LE | format::Pixel::YUV420P10LE if high_depth => Some(format),
+            _ => None,
+        }
     }
 
     /// Handles creation and resizing of GPU resources.
@@ -133,15 +290,30 @@ impl VideoPlayer {
                 converter.resize(device, width, height);
             }
         } else {
-            let new_converter = match format {
-                format::Pixel::VULKAN | format::Pixel::NV12 => {
-                    Box::new(Nv12Converter::new(device, width, height)) as _
-                }
-                format::Pixel::YUV420P => {
-                    Box::new(Yuv420pConverter::new(device, width, height)) as _
+            let depth = match format {
+                format::Pixel::VULKAN | format::Pixel::NV12 | format::Pixel::YUV420P => {
+                    SampleDepth::Eight
                 }
+                format::Pixel::P010LE => SampleDepth::TenHigh,
+                format::Pixel::YUV420P10LE => SampleDepth::TenLow,
                 f => return Err(format!("Unsupported pixel format: {f:?}")),
             };
+            if depth != SampleDepth::Eight
+                && !device
+                    .features()
+                    .contains(wgpu::Features::TEXTURE_FORMAT_16BIT_NORM)
+            {
+                return Err(format!(
+                    "Unsupported pixel format: {format:?}, 16-bit textures are not supported"
+                ));
+            }
+
+            let new_converter = match format {
+                format::Pixel::YUV420P | format::Pixel::YUV420P10LE => {
+                    Box::new(Yuv420pConverter::new(device, width, height, depth)) as _
+                }
+                _ => Box::new(Nv12Converter::new(device, width, height, depth)) as _,
+            };
             self.current_format.replace(format);
             self.converter.replace(new_converter);
         }
@@ -151,44 +323,57 @@ impl VideoPlayer {
 }
 
 impl VideoPlayer {
-    pub fn ui(&self, ui: &mut egui::Ui) {
-        let Some(texture) = &self.texture else {
-            return;
-        };
+    /// Draws the letterboxed video.
+    ///
+    /// Returns the interaction over the displayed image, excluding the black bars,
+    /// and the mapping between the viewport and the device pixels.
+    ///
+    /// # Arguments
+    ///
+    /// - `orientation`: Display rotation.
+    /// - `crop`: Displayed video region, normalized to `[0, 1]`.
+    pub fn ui(
+        &self,
+        ui: &mut egui::Ui,
+        orientation: Orientation,
+        crop: egui::Rect,
+    ) -> Option<(egui::Response, ScreenMapping)> {
+        let texture = self.texture.as_ref()?;
 
-        let available_size = ui.available_size();
+        let (rect, _) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+        let mapping =
+            ScreenMapping::fit((texture.width(), texture.height()), crop, orientation, rect)?;
 
-        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-        let video_width = texture.width() as f32;
-        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
-        let video_height = texture.height() as f32;
+        let image_rect = mapping.image_rect();
+        let uv = mapping.corners_uv();
 
-        if video_width == 0.0 || video_height == 0.0 {
-            return;
+        let mut mesh = egui::Mesh::with_texture(texture.id());
+        for (pos, uv) in [
+            image_rect.left_top(),
+            image_rect.right_top(),
+            image_rect.right_bottom(),
+            image_rect.left_bottom(),
+        ]
+        .into_iter()
+        .zip(uv)
+        {
+            mesh.vertices.push(egui::epaint::Vertex {
+                pos,
+                uv,
+                color: egui::Color32::WHITE,
+            });
         }
+        mesh.add_triangle(0, 1, 2);
+        mesh.add_triangle(0, 2, 3);
 
-        let video_ratio = video_width / video_height;
-        let window_ratio = available_size.x / available_size.y;
+        ui.painter().add(egui::Shape::mesh(mesh));
 
-        let target_size = if window_ratio > video_ratio {
-            egui::vec2(available_size.y * video_ratio, available_size.y)
-        } else {
-            egui::vec2(available_size.x, available_size.x / video_ratio)
-        };
-
-        let x_offset = (available_size.x - target_size.x) / 2.0;
-        let y_offset = (available_size.y - target_size.y) / 2.0;
-
-        let (rect, _) = ui.allocate_exact_size(available_size, egui::Sense::hover());
-
-        let image_rect =
-            egui::Rect::from_min_size(rect.min + egui::vec2(x_offset, y_offset), target_size);
-
-        ui.painter().image(
-            texture.id(),
+        let response = ui.interact(
             image_rect,
-            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-            egui::Color32::WHITE,
+            ui.id().with("video_image"),
+            egui::Sense::click_and_drag(),
         );
+
+        Some((response, mapping))
     }
 }
@@ -0,0 +1,172 @@
+//! Viewport input translation into device control messages
+
+use eframe::egui::{Event, MouseWheelUnit, PointerButton, Pos2, Vec2};
+
+use crate::services::control::{ControlMessage, MotionButtons, PointerId, Position, TouchAction};
+use crate::ui::renderer::screen_mapping::ScreenMapping;
+
+/// Emulates a finger touch with the primary mouse button, and forwards the mouse wheel.
+///
+/// Holding Ctrl while pressing adds a second finger, mirrored around the viewport center,
+/// to pinch and rotate.
+#[derive(Debug, Default)]
+pub struct PointerInput {
+    /// Last device position of the pressed pointer. `None` while released.
+    pressed: Option<Position>,
+    /// Last device position of the mirrored finger. `None` without pinch.
+    mirrored: Option<Position>,
+    /// Last hovered viewport point, where scroll events are injected
+    hover: Option<Pos2>,
+}
+
+impl PointerInput {
+    /// Pointer identifier of the emulated finger.
+    const POINTER_ID: PointerId = PointerId::GENERIC_FINGER;
+    /// Pointer identifier of the mirrored finger.
+    const MIRRORED_POINTER_ID: PointerId = PointerId::VIRTUAL_FINGER;
+    /// Scroll distance in points of a mouse wheel notch.
+    const POINTS_PER_NOTCH: f32 = 50.0;
+    /// Scroll distance in notches of a page.
+    const NOTCHES_PER_PAGE: f32 = 10.0;
+
+    /// Translates the frame pointer events into touch and scroll events.
+    ///
+    /// A touch starts on the displayed video only, but follows the pointer outside of it
+    /// until released, clamped to the video edges.
+    ///
+    /// # Arguments
+    ///
+    /// - `events`: Frame input events.
+    /// - `mapping`: Viewport to device mapping.
+    /// - `out`: Produced control messages.
+    pub fn handle(
+        &mut self,
+        events: &[Event],
+        mapping: &ScreenMapping,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        for event in events {
+            match *event {
+                Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: true,
+                    modifiers,
+                } => {
+                    if self.pressed.is_none()
+                        && let Some(point) = mapping.to_device(pos)
+                    {
+                        let position = mapping.position(point);
+                        self.pressed = Some(position);
+                        out.push(touch(TouchAction::Down, Self::POINTER_ID, position));
+
+                        if modifiers.command {
+                            let mirrored = mirror(pos, mapping);
+                            self.mirrored = Some(mirrored);
+                            out.push(touch(
+                                TouchAction::Down,
+                                Self::MIRRORED_POINTER_ID,
+                                mirrored,
+                            ));
+                        }
+                    }
+                }
+                Event::PointerButton {
+                    pos,
+                    button: PointerButton::Primary,
+                    pressed: false,
+                    ..
+                } => {
+                    if self.pressed.take().is_some() {
+                        let position = mapping.position(mapping.to_device_clamped(pos));
+                        out.push(touch(TouchAction::Up, Self::POINTER_ID, position));
+                    }
+                    if self.mirrored.take().is_some() {
+                        out.push(touch(
+                            TouchAction::Up,
+                            Self::MIRRORED_POINTER_ID,
+                            mirror(pos, mapping),
+                        ));
+                    }
+                }
+                Event::PointerMoved(pos) => {
+                    self.hover = Some(pos);
+
+                    let position = mapping.position(mapping.to_device_clamped(pos));
+                    if let Some(last) = self.pressed
+                        && position != last
+                    {
+                        self.pressed = Some(position);
+                        out.push(touch(TouchAction::Move, Self::POINTER_ID, position));
+
+                        if self.mirrored.is_some() {
+                            let mirrored = mirror(pos, mapping);
+                            self.mirrored = Some(mirrored);
+                            out.push(touch(
+                                TouchAction::Move,
+                                Self::MIRRORED_POINTER_ID,
+                                mirrored,
+                            ));
+                        }
+                    }
+                }
+                Event::MouseWheel { unit, delta, .. } => {
+                    if let Some(point) = self.hover.and_then(|pos| mapping.to_device(pos)) {
+                        out.push(scroll(mapping.position(point), unit, delta));
+                    }
+                }
+                Event::PointerGone => {
+                    self.hover = None;
+                    self.release(out);
+                }
+                Event::WindowFocused(false) => self.release(out),
+                _ => {}
+            }
+        }
+    }
+
+    /// Releases the touch in progress, if any, at its last position.
+    pub fn release(&mut self, out: &mut Vec<ControlMessage>) {
+        if let Some(position) = self.pressed.take() {
+            out.push(touch(TouchAction::Up, Self::POINTER_ID, position));
+        }
+        if let Some(position) = self.mirrored.take() {
+            out.push(touch(TouchAction::Up, Self::MIRRORED_POINTER_ID, position));
+        }
+    }
+}
+
+/// Device position of the mirrored finger of a viewport point, around the viewport center.
+fn mirror(pos: Pos2, mapping: &ScreenMapping) -> Position {
+    let center = mapping.image_rect().center();
+    let mirrored = center + (center - pos);
+
+    mapping.position(mapping.to_device_clamped(mirrored))
+}
+
+/// Finger touch event at a device position.
+const fn touch(action: TouchAction, pointer_id: PointerId, position: Position) -> ControlMessage {
+    let pressure = match action {
+        TouchAction::Up => 0.0,
+        TouchAction::Down | TouchAction::Move => 1.0,
+    };
+
+    ControlMessage::touch(action, pointer_id, position, pressure)
+}
+
+/// Scroll event at a device position, from a mouse wheel delta.
+fn scroll(position: Position, unit: MouseWheelUnit, delta: Vec2) -> ControlMessage {
+    let notches = match unit {
+        MouseWheelUnit::Point => delta / PointerInput::POINTS_PER_NOTCH,
+        MouseWheelUnit::Line => delta,
+        MouseWheelUnit::Page => delta * PointerInput::NOTCHES_PER_PAGE,
+    };
+
+    // egui scrolls the content, Android scrolls the view: the horizontal axis is reversed.
+    ControlMessage::InjectScroll {
+        position,
+        hscroll: -notches.x,
+        vscroll: notches.y,
+        buttons: MotionButtons::NONE,
+    }
+}
@@ -0,0 +1,316 @@
+//! Keyboard translation into device key and text events
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use eframe::egui::{Event, Key, Modifiers, RawInput};
+
+use crate::services::control::{ControlMessage, CopyKey, KeyAction, Keycode, MetaState};
+
+/// Forwards the keyboard of a session viewport to the device.
+///
+/// Printable keys are injected as text, heeding the host keyboard layout,
+/// unless a shortcut modifier is held. Other keys are injected as key codes.
+#[derive(Debug, Default)]
+pub struct KeyboardInput {
+    /// Keys held down on the device, with their key code and repeat count
+    pressed: HashMap<Key, (Keycode, u32)>,
+}
+
+impl KeyboardInput {
+    /// Consumes the keyboard events of the session viewport raw input.
+    ///
+    /// Consumed events are removed so egui does not interpret them as its own shortcuts.
+    ///
+    /// # Arguments
+    ///
+    /// - `raw_input`: Session viewport raw input.
+    /// - `clipboard_sync`: Whether the device clipboard is sent back on copy and cut.
+    /// - `out`: Produced control messages.
+    pub fn handle(
+        &mut self,
+        raw_input: &mut RawInput,
+        clipboard_sync: bool,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        raw_input.events.retain(|event| match event {
+            Event::Key {
+                key,
+                pressed,
+                modifiers,
+                ..
+            } => {
+                self.key(*key, *pressed, *modifiers, out);
+                false
+            }
+            Event::Text(text) | Event::Paste(text) => {
+                out.extend(ControlMessage::texts(text));
+                false
+            }
+            // The device presses the key, then sends its clipboard back. Without clipboard
+            // synchronization, only the key is pressed.
+            Event::Copy if clipboard_sync => {
+                out.push(ControlMessage::GetClipboard {
+                    copy_key: CopyKey::Copy,
+                });
+                false
+            }
+            Event::Cut if clipboard_sync => {
+                out.push(ControlMessage::GetClipboard {
+                    copy_key: CopyKey::Cut,
+                });
+                false
+            }
+            Event::Copy => {
+                press(Keycode::Copy, out);
+                false
+            }
+            Event::Cut => {
+                press(Keycode::Cut, out);
+                false
+            }
+            Event::WindowFocused(false) => {
+                self.release_all(out);
+                true
+            }
+            _ => true,
+        });
+
+        if !raw_input.focused {
+            self.release_all(out);
+        }
+    }
+
+    /// Releases every key held down on the device.
+    pub fn release_all(&mut self, out: &mut Vec<ControlMessage>) {
+        for (_, (keycode, _)) in self.pressed.drain() {
+            out.push(ControlMessage::key(
+                KeyAction::Up,
+                keycode,
+                0,
+                MetaState::NONE,
+            ));
+        }
+    }
+
+    fn key(
+        &mut self,
+        key: Key,
+        pressed: bool,
+        modifiers: Modifiers,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        let meta_state = meta_state(modifiers);
+
+        if !pressed {
+            if let Some((keycode, _)) = self.pressed.remove(&key) {
+                out.push(ControlMessage::key(KeyAction::Up, keycode, 0, meta_state));
+            }
+            return;
+        }
+
+        // The text event carries printable keys, unless already held as a key code.
+        let is_shortcut = modifiers.ctrl || modifiers.alt || modifiers.mac_cmd;
+        if is_printable(key) && !is_shortcut && !self.pressed.contains_key(&key) {
+            return;
+        }
+
+        let Some(keycode) = keycode(key) else {
+            return;
+        };
+
+        // egui reports repeats after the raw input hook, count them here.
+        let (keycode, repeat) = match self.pressed.entry(key) {
+            Entry::Occupied(mut entry) => {
+                let (keycode, repeat) = entry.get_mut();
+                *repeat += 1;
+                (*keycode, *repeat)
+            }
+            Entry::Vacant(entry) => *entry.insert((keycode, 0)),
+        };
+
+        out.push(ControlMessage::key(
+            KeyAction::Down,
+            keycode,
+            repeat,
+            meta_state,
+        ));
+    }
+}
+
+/// Presses and releases a key on the device.
+fn press(keycode: Keycode, out: &mut Vec<ControlMessage>) {
+    for action in [KeyAction::Down, KeyAction::Up] {
+        out.push(ControlMessage::key(action, keycode, 0, MetaState::NONE));
+    }
+}
+
+/// Android meta state of the held modifiers.
+fn meta_state(modifiers: Modifiers) -> MetaState {
+    let mut meta_state = MetaState::NONE;
+
+    if modifiers.shift {
+        meta_state = meta_state | MetaState::SHIFT;
+    }
+    if modifiers.ctrl {
+        meta_state = meta_state | MetaState::CTRL;
+    }
+    if modifiers.alt {
+        meta_state = meta_state | MetaState::ALT;
+    }
+    if modifiers.mac_cmd {
+        meta_state = meta_state | MetaState::META;
+    }
+
+    meta_state
+}
+
+/// Whether the key produces a text event when typed.
+const fn is_printable(key: Key) -> bool {
+    !matches!(
+        key,
+        Key::ArrowDown
+            | Key::ArrowLeft
+            | Key::ArrowRight
+            | Key::ArrowUp
+            | Key::Escape
+            | Key::Tab
+            | Key::Backspace
+            | Key::Enter
+            | Key::Insert
+            | Key::Delete
+            | Key::Home
+            | Key::End
+            | Key::PageUp
+            | Key::PageDown
+            | Key::Copy
+            | Key::Cut
+            | Key::Paste
+            | Key::F1
+            | Key::F2
+            | Key::F3
+            | Key::F4
+            | Key::F5
+            | Key::F6
+            | Key::F7
+            | Key::F8
+            | Key::F9
+            | Key::F10
+            | Key::F11
+            | Key::F12
+            | Key::F13
+            | Key::F14
+            | Key::F15
+            | Key::F16
+            | Key::F17
+            | Key::F18
+            | Key::F19
+            | Key::F20
+            | Key::F21
+            | Key::F22
+            | Key::F23
+            | Key::F24
+            | Key::F25
+            | Key::F26
+            | Key::F27
+            | Key::F28
+            | Key::F29
+            | Key::F30
+            | Key::F31
+            | Key::F32
+            | Key::F33
+            | Key::F34
+            | Key::F35
+            | Key::BrowserBack
+    )
+}
+
+/// Android key code of an egui key.
+///
+/// Returns `None` for keys without an Android equivalent.
+const fn keycode(key: Key) -> Option<Keycode> {
+    let keycode = match key {
+        Key::ArrowDown => Keycode::DpadDown,
+        Key::ArrowLeft => Keycode::DpadLeft,
+        Key::ArrowRight => Keycode::DpadRight,
+        Key::ArrowUp => Keycode::DpadUp,
+        Key::Escape => Keycode::Escape,
+        Key::Tab => Keycode::Tab,
+        Key::Backspace => Keycode::Del,
+        Key::Enter => Keycode::Enter,
+        Key::Space => Keycode::Space,
+        Key::Insert => Keycode::Insert,
+        Key::Delete => Keycode::ForwardDel,
+        Key::Home => Keycode::MoveHome,
+        Key::End => Keycode::MoveEnd,
+        Key::PageUp => Keycode::PageUp,
+        Key::PageDown => Keycode::PageDown,
+        Key::Copy => Keycode::Copy,
+        Key::Cut => Keycode::Cut,
+        Key::Paste => Keycode::Paste,
+        Key::Comma => Keycode::Comma,
+        Key::Backslash => Keycode::Backslash,
+        Key::Slash => Keycode::Slash,
+        Key::OpenBracket => Keycode::LeftBracket,
+        Key::CloseBracket => Keycode::RightBracket,
+        Key::Backtick => Keycode::Grave,
+        Key::Minus => Keycode::Minus,
+        Key::Period => Keycode::Period,
+        Key::Plus => Keycode::Plus,
+        Key::Equals => Keycode::Equals,
+        Key::Semicolon => Keycode::Semicolon,
+        Key::Quote => Keycode::Apostrophe,
+        Key::Num0 => Keycode::Num0,
+        Key::Num1 => Keycode::Num1,
+        Key::Num2 => Keycode::Num2,
+        Key::Num3 => Keycode::Num3,
+        Key::Num4 => Keycode::Num4,
+        Key::Num5 => Keycode::Num5,
+        Key::Num6 => Keycode::Num6,
+        Key::Num7 => Keycode::Num7,
+        Key::Num8 => Keycode::Num8,
+        Key::Num9 => Keycode::Num9,
+        Key::A => Keycode::A,
+        Key::B => Keycode::B,
+        Key::C => Keycode::C,
+        Key::D => Keycode::D,
+        Key::E => Keycode::E,
+        Key::F => Keycode::F,
+        Key::G => Keycode::G,
+        Key::H => Keycode::H,
+        Key::I => Keycode::I,
+        Key::J => Keycode::J,
+        Key::K => Keycode::K,
+        Key::L => Keycode::L,
+        Key::M => Keycode::M,
+        Key::N => Keycode::N,
+        Key::O => Keycode::O,
+        Key::P => Keycode::P,
+        Key::Q => Keycode::Q,
+        Key::R => Keycode::R,
+        Key::S => Keycode::S,
+        Key::T => Keycode::T,
+        Key::U => Keycode::U,
+        Key::V => Keycode::V,
+        Key::W => Keycode::W,
+        Key::X => Keycode::X,
+        Key::Y => Keycode::Y,
+        Key::Z => Keycode::Z,
+        Key::F1 => Keycode::F1,
+        Key::F2 => Keycode::F2,
+        Key::F3 => Keycode::F3,
+        Key::F4 => Keycode::F4,
+        Key::F5 => Keycode::F5,
+        Key::F6 => Keycode::F6,
+        Key::F7 => Keycode::F7,
+        Key::F8 => Keycode::F8,
+        Key::F9 => Keycode::F9,
+        Key::F10 => Keycode::F10,
+        Key::F11 => Keycode::F11,
+        Key::F12 => Keycode::F12,
+        Key::BrowserBack => Keycode::Back,
+        _ => return None,
+    };
+
+    Some(keycode)
+}
@@ -0,0 +1,406 @@
+//! Keymap translation of keys and mouse buttons into device touches
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use eframe::egui::{Event, Pos2, RawInput, Vec2, vec2};
+
+use crate::services::control::{ControlMessage, PointerId, Position, TouchAction};
+use crate::ui::pages::settings::{BindingAction, Joystick, KeymapProfile, Trigger};
+use crate::ui::renderer::screen_mapping::ScreenMapping;
+
+/// Applies a keymap profile to the session viewport input.
+///
+/// Each held binding is injected with its own pointer, so several keys can be held at once.
+#[derive(Debug, Default)]
+pub struct KeymapInput {
+    /// Triggers held down
+    pressed: HashSet<Trigger>,
+    /// Touches in progress
+    touches: HashMap<TouchSource, Touch>,
+}
+
+/// Binding owning a touch, by index in the profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TouchSource {
+    Binding(usize),
+    Joystick(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Touch {
+    pointer_id: PointerId,
+    /// Last injected position
+    position: Position,
+    /// Swipe animation, for swipe bindings
+    swipe: Option<Swipe>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Swipe {
+    from: Pos2,
+    to: Pos2,
+    start: Instant,
+    duration: Duration,
+}
+
+impl KeymapInput {
+    /// Maximum number of simultaneous keymap touches.
+    ///
+    /// The device tracks 10 pointers, one is left to the mouse.
+    const MAX_POINTERS: u64 = 9;
+
+    /// Consumes the bound key and mouse button events of the session viewport raw input.
+    ///
+    /// # Arguments
+    ///
+    /// - `raw_input`: Session viewport raw input.
+    /// - `profile`: Keymap profile of the session.
+    /// - `mapping`: Viewport to device mapping.
+    /// - `out`: Produced control messages.
+    pub fn handle(
+        &mut self,
+        raw_input: &mut RawInput,
+        profile: &KeymapProfile,
+        mapping: &ScreenMapping,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        raw_input.events.retain(|event| {
+            let (trigger, pressed) = match *event {
+                Event::Key { key, pressed, .. } => (Trigger::Key(key), pressed),
+                Event::PointerButton {
+                    button, pressed, ..
+                } => (Trigger::Mouse(button), pressed),
+                Event::WindowFocused(false) => {
+                    self.release_all(out);
+                    return true;
+                }
+                _ => return true,
+            };
+
+            if !profile.is_bound(trigger) {
+                return true;
+            }
+
+            if pressed {
+                // Keys held down are reported again on repeat.
+                if self.pressed.insert(trigger) {
+                    self.press(trigger, profile, mapping, out);
+                }
+            } else if self.pressed.remove(&trigger) {
+                self.release(trigger, profile, out);
+            }
+            self.update_joysticks(trigger, profile, mapping, out);
+
+            false
+        });
+
+        if !raw_input.focused {
+            self.release_all(out);
+        }
+    }
+
+    /// Advances the swipes in progress.
+    ///
+    /// Returns whether a swipe is still in progress, and needs another frame.
+    pub fn tick(
+        &mut self,
+        now: Instant,
+        mapping: &ScreenMapping,
+        out: &mut Vec<ControlMessage>,
+    ) -> bool {
+        let mut done = Vec::new();
+
+        for (source, touch) in &mut self.touches {
+            let Some(swipe) = touch.swipe else {
+                continue;
+            };
+
+            let t = if swipe.duration.is_zero() {
+                1.0
+            } else {
+                (now.duration_since(swipe.start).as_secs_f32() / swipe.duration.as_secs_f32())
+                    .min(1.0)
+            };
+
+            let position = mapping.position(mapping.denormalize(swipe.from.lerp(swipe.to, t)));
+            if position != touch.position {
+                touch.position = position;
+                out.push(touch_msg(TouchAction::Move, touch.pointer_id, position));
+            }
+
+            if t >= 1.0 {
+                out.push(touch_msg(TouchAction::Up, touch.pointer_id, position));
+                done.push(*source);
+            }
+        }
+
+        for source in done {
+            self.touches.remove(&source);
+        }
+
+        self.touches.values().any(|touch| touch.swipe.is_some())
+    }
+
+    /// Releases every trigger and touch in progress.
+    pub fn release_all(&mut self, out: &mut Vec<ControlMessage>) {
+        self.pressed.clear();
+
+        for (_, touch) in self.touches.drain() {
+            out.push(touch_msg(TouchAction::Up, touch.pointer_id, touch.position));
+        }
+    }
+
+    fn press(
+        &mut self,
+        trigger: Trigger,
+        profile: &KeymapProfile,
+        mapping: &ScreenMapping,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        for (index, binding) in profile.bindings.iter().enumerate() {
+            let source = TouchSource::Binding(index);
+            if binding.trigger != trigger || self.touches.contains_key(&source) {
+                continue;
+            }
+
+            let Some(pointer_id) = self.free_pointer() else {
+                warn!("Keymap: too many simultaneous touches");
+                return;
+            };
+
+            match binding.action {
+                BindingAction::Tap { point } => {
+                    let position = mapping.position(mapping.denormalize(point));
+                    out.push(touch_msg(TouchAction::Down, pointer_id, position));
+                    out.push(touch_msg(TouchAction::Up, pointer_id, position));
+                }
+                BindingAction::Hold { point } => {
+                    let position = mapping.position(mapping.denormalize(point));
+                    out.push(touch_msg(TouchAction::Down, pointer_id, position));
+                    self.touches.insert(
+                        source,
+                        Touch {
+                            pointer_id,
+                            position,
+                            swipe: None,
+                        },
+                    );
+                }
+                BindingAction::Swipe {
+                    from,
+                    to,
+                    duration_ms,
+                } => {
+                    let position = mapping.position(mapping.denormalize(from));
+                    out.push(touch_msg(TouchAction::Down, pointer_id, position));
+                    self.touches.insert(
+                        source,
+                        Touch {
+                            pointer_id,
+                            position,
+                            swipe: Some(Swipe {
+                                from,
+                                to,
+                                start: Instant::now(),
+                                duration: Duration::from_millis(duration_ms.into()),
+                            }),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    fn release(
+        &mut self,
+        trigger: Trigger,
+        profile: &KeymapProfile,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        for (index, binding) in profile.bindings.iter().enumerate() {
+            if binding.trigger != trigger || !matches!(binding.action, BindingAction::Hold { .. }) {
+                continue;
+            }
+
+            if let Some(touch) = self.touches.remove(&TouchSource::Binding(index)) {
+                out.push(touch_msg(TouchAction::Up, touch.pointer_id, touch.position));
+            }
+        }
+    }
+
+    /// Moves, starts or ends the touches of the joysticks driven by `trigger`.
+    fn update_joysticks(
+        &mut self,
+        trigger: Trigger,
+        profile: &KeymapProfile,
+        mapping: &ScreenMapping,
+        out: &mut Vec<ControlMessage>,
+    ) {
+        for (index, joystick) in profile.joysticks.iter().enumerate() {
+            if !joystick.triggers().contains(&trigger) {
+                continue;
+            }
+
+            let source = TouchSource::Joystick(index);
+            let direction = self.direction(joystick);
+
+            if direction == Vec2::ZERO {
+                if let Some(touch) = self.touches.remove(&source) {
+                    out.push(touch_msg(TouchAction::Up, touch.pointer_id, touch.position));
+                }
+                continue;
+            }
+
+            let center = joystick.region.center();
+            let target = center + direction * joystick.region.size() * 0.5;
+            let position = mapping.position(mapping.denormalize(target));
+
+            if let Some(touch) = self.touches.get_mut(&source) {
+                touch.position = position;
+                out.push(touch_msg(TouchAction::Move, touch.pointer_id, position));
+                continue;
+            }
+
+            let Some(pointer_id) = self.free_pointer() else {
+                warn!("Keymap: too many simultaneous touches");
+                continue;
+            };
+
+            // The drag starts at the center for the device to register the direction.
+            let start = mapping.position(mapping.denormalize(center));
+            out.push(touch_msg(TouchAction::Down, pointer_id, start));
+            out.push(touch_msg(TouchAction::Move, pointer_id, position));
+            self.touches.insert(
+                source,
+                Touch {
+                    pointer_id,
+                    position,
+                    swipe: None,
+                },
+            );
+        }
+    }
+
+    /// Held direction of a joystick, with diagonals normalized.
+    fn direction(&self, joystick: &Joystick) -> Vec2 {
+        let axis = |negative: Trigger, positive: Trigger| {
+            let negative = f32::from(u8::from(self.pressed.contains(&negative)));
+            let positive = f32::from(u8::from(self.pressed.contains(&positive)));
+            positive - negative
+        };
+
+        vec2(
+            axis(joystick.left, joystick.right),
+            axis(joystick.up, joystick.down),
+        )
+        .normalized()
+    }
+
+    /// Lowest pointer identifier not used by a touch in progress.
+    fn free_pointer(&self) -> Option<PointerId> {
+        (0..Self::MAX_POINTERS)
+            .map(PointerId)
+            .find(|id| !self.touches.values().any(|touch| touch.pointer_id == *id))
+    }
+}
+
+/// Finger touch event of a keymap pointer.
+const fn touch_msg(
+    action: TouchAction,
+    pointer_id: PointerId,
+    position: Position,
+) -> ControlMessage {
+    let pressure = match action {
+        TouchAction::Up => 0.0,
+        TouchAction::Down | TouchAction::Move => 1.0,
+    };
+
+    ControlMessage::touch(action, pointer_id, position, pressure)
+}
+
+#[cfg(test)]
+mod test {
+    use eframe::egui::{Event, Key, Modifiers, RawInput, Rect, pos2};
+
+    use crate::services::control::{ControlMessage, PointerId, TouchAction};
+    use crate::ui::pages::settings::{
+        BindingAction, KeyBinding, KeymapProfile, KeymapTarget, Trigger,
+    };
+    use crate::ui::renderer::screen_mapping::{Orientation, ScreenMapping};
+    use crate::ui::views::session::keymap::KeymapInput;
+
+    fn key(key: Key, pressed: bool) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed,
+            repeat: false,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    fn touches(out: &[ControlMessage]) -> Vec<(TouchAction, PointerId)> {
+        out.iter()
+            .filter_map(|msg| match msg {
+                ControlMessage::InjectTouch {
+                    action, pointer_id, ..
+                } => Some((*action, *pointer_id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_multi_touch() {
+        let mapping = ScreenMapping::new(
+            (1000, 500),
+            ScreenMapping::FULL,
+            Orientation::Deg0,
+            Rect::from_min_max(pos2(0.0, 0.0), pos2(1000.0, 500.0)),
+        )
+        .unwrap();
+
+        let mut profile = KeymapProfile::new(KeymapTarget::Device("device".to_owned()));
+        for (trigger, x) in [(Key::A, 0.25), (Key::B, 0.75)] {
+            profile.bindings.push(KeyBinding {
+                trigger: Trigger::Key(trigger),
+                action: BindingAction::Hold {
+                    point: pos2(x, 0.5),
+                },
+            });
+        }
+
+        let mut input = KeymapInput::default();
+        let mut raw_input = RawInput {
+            events: vec![
+                key(Key::A, true),
+                key(Key::B, true),
+                key(Key::A, true),
+                key(Key::C, true),
+                key(Key::A, false),
+                key(Key::B, false),
+            ],
+            focused: true,
+            ..RawInput::default()
+        };
+
+        let mut out = Vec::new();
+        input.handle(&mut raw_input, &profile, &mapping, &mut out);
+
+        assert_eq!(raw_input.events, [key(Key::C, true)]);
+        assert_eq!(
+            touches(&out),
+            [
+                (TouchAction::Down, PointerId(0)),
+                (TouchAction::Down, PointerId(1)),
+                (TouchAction::Up, PointerId(0)),
+                (TouchAction::Up, PointerId(1)),
+            ]
+        );
+        let ControlMessage::InjectTouch { position, .. } = &out[1] else {
+            unreachable!();
+        };
+        assert_eq!(position.point, (750, 250));
+    }
+}
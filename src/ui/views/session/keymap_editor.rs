@@ -0,0 +1,445 @@
+//! Visual keymap editor, overlaid on the session video
+
+use eframe::egui;
+use eframe::egui::{
+    Align2, Color32, Event, FontId, Id, Pos2, Rect, Response, Sense, Stroke, Ui, Vec2, pos2, vec2,
+};
+
+use crate::ui::pages::settings::{
+    BindingAction, Joystick, KeyBinding, KeymapProfile, KeymapTarget, Keymaps, Trigger,
+};
+use crate::ui::renderer::screen_mapping::ScreenMapping;
+
+/// Edit mode of the session keymap profile.
+///
+/// The draft is loaded from and saved to the persisted keymaps by [`KeymapEditor::sync`],
+/// as the session viewport has no access to the application data.
+#[derive(Debug, Default)]
+pub struct KeymapEditor {
+    open: bool,
+    /// Edited profile. `None` until loaded.
+    draft: Option<KeymapProfile>,
+    /// Edited profile waiting to be persisted
+    saved: Option<KeymapProfile>,
+    selected: Option<Marker>,
+    /// Clicked point waiting for a key to become a binding, normalized to `[0, 1]`
+    pending: Option<Pos2>,
+    /// Package name of package profiles
+    package: String,
+}
+
+/// Draggable marker of the edited profile, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Marker {
+    Binding(usize),
+    /// End point of a swipe binding
+    SwipeEnd(usize),
+    Joystick(usize),
+}
+
+/// Profile change requested from a marker context menu.
+enum Edit {
+    Action(usize, BindingAction),
+    Directions(usize, [Trigger; 4]),
+    Remove(Marker),
+}
+
+impl KeymapEditor {
+    /// Marker diameter in points.
+    const MARKER_SIZE: f32 = 28.0;
+    /// Default swipe duration in milliseconds.
+    const SWIPE_DURATION_MS: u32 = 200;
+
+    pub const fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Enters the edit mode, reloading the persisted profile.
+    pub fn open(&mut self) {
+        self.open = true;
+        self.draft = None;
+        self.selected = None;
+        self.pending = None;
+    }
+
+    /// Leaves the edit mode, discarding the changes.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.draft = None;
+        self.selected = None;
+        self.pending = None;
+    }
+
+    /// Persists the saved profile and loads the draft of the edit mode.
+    ///
+    /// # Arguments
+    ///
+    /// - `target`: Keymap profile applied to the session, updated on save.
+    /// - `keymaps`: Persisted keymaps.
+    pub fn sync(&mut self, target: &mut KeymapTarget, keymaps: &mut Keymaps) {
+        if let Some(profile) = self.saved.take() {
+            target.clone_from(&profile.target);
+            *keymaps.profile_mut(target) = profile;
+        }
+
+        if self.open && self.draft.is_none() {
+            if let KeymapTarget::Package(package) = target {
+                self.package.clone_from(package);
+            }
+
+            let profile = keymaps
+                .profile(target)
+                .cloned()
+                .unwrap_or_else(|| KeymapProfile::new(target.clone()));
+            self.draft = Some(profile);
+        }
+    }
+
+    /// Shows the profile selection and the save and cancel buttons.
+    ///
+    /// # Arguments
+    ///
+    /// - `ui`: Toolbar area.
+    /// - `device_id`: Session device serial, for device profiles.
+    pub fn toolbar(&mut self, ui: &mut Ui, device_id: &str) {
+        let Some(draft) = &mut self.draft else {
+            ui.spinner();
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            let is_device = matches!(draft.target, KeymapTarget::Device(_));
+            if ui.radio(is_device, "Device").clicked() {
+                draft.target = KeymapTarget::Device(device_id.to_owned());
+            }
+            if ui.radio(!is_device, "Package").clicked()
+                || (!is_device && ui.text_edit_singleline(&mut self.package).changed())
+            {
+                draft.target = KeymapTarget::Package(self.package.trim().to_owned());
+            }
+        });
+
+        let is_valid = draft.target != KeymapTarget::Package(String::new());
+        let (save, cancel) = ui
+            .horizontal(|ui| {
+                (
+                    ui.add_enabled(is_valid, egui::Button::new("Save"))
+                        .clicked(),
+                    ui.button("Cancel").clicked(),
+                )
+            })
+            .inner;
+
+        ui.small("Click to add, drag to move, press a key to assign, right click for options.");
+
+        if save {
+            self.saved = self.draft.take();
+            self.close();
+        } else if cancel {
+            self.close();
+        }
+    }
+
+    /// Draws the markers of the edited profile over the video and handles their edition.
+    ///
+    /// # Arguments
+    ///
+    /// - `ui`: Video area.
+    /// - `response`: Video image response.
+    /// - `mapping`: Viewport to device mapping.
+    pub fn ui(&mut self, ui: &mut Ui, response: &Response, mapping: &ScreenMapping) {
+        let Self {
+            draft: Some(draft),
+            selected,
+            pending,
+            ..
+        } = self
+        else {
+            return;
+        };
+
+        let to_screen = |p: Pos2| mapping.to_screen(mapping.denormalize(p));
+        let to_video = |pos: Pos2| mapping.normalize(mapping.to_device_clamped(pos));
+        let id = ui.id().with("keymap_editor");
+        let mut edit = None;
+
+        if response.clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+            && let Some(point) = mapping.to_device(pos)
+        {
+            *pending = Some(mapping.normalize(point));
+            *selected = None;
+        }
+        response.context_menu(|ui| {
+            if ui.button("Add joystick").clicked() {
+                draft.joysticks.push(Joystick {
+                    region: Rect::from_center_size(pos2(0.5, 0.5), Vec2::splat(0.2)),
+                    up: Trigger::Key(egui::Key::W),
+                    down: Trigger::Key(egui::Key::S),
+                    left: Trigger::Key(egui::Key::A),
+                    right: Trigger::Key(egui::Key::D),
+                });
+                *selected = Some(Marker::Joystick(draft.joysticks.len() - 1));
+                *pending = None;
+            }
+        });
+
+        for (index, joystick) in draft.joysticks.iter_mut().enumerate() {
+            let marker = Marker::Joystick(index);
+            let region = Rect::from_two_pos(
+                to_screen(joystick.region.min),
+                to_screen(joystick.region.max),
+            );
+            ui.painter().rect_stroke(
+                region,
+                4.0,
+                Stroke::new(1.5, Color32::WHITE),
+                egui::StrokeKind::Inside,
+            );
+
+            let [up, down, left, right] = joystick.triggers();
+            let label = format!("{up} {down} {left} {right}");
+            let response = marker_ui(
+                ui,
+                id.with(marker),
+                region.center(),
+                &label,
+                *selected == Some(marker),
+            );
+            select(&response, marker, selected, pending);
+            if response.dragged()
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                joystick.region = Rect::from_center_size(to_video(pos), joystick.region.size());
+            }
+            response.context_menu(|ui| {
+                for (name, triggers) in DIRECTIONS {
+                    if ui.button(name).clicked() {
+                        edit = Some(Edit::Directions(index, triggers));
+                    }
+                }
+                ui.separator();
+                if ui.button("Delete").clicked() {
+                    edit = Some(Edit::Remove(marker));
+                }
+            });
+        }
+
+        for (index, binding) in draft.bindings.iter_mut().enumerate() {
+            let marker = Marker::Binding(index);
+            let label = binding.trigger.to_string();
+
+            let (point, end) = match &mut binding.action {
+                BindingAction::Tap { point } | BindingAction::Hold { point } => (point, None),
+                BindingAction::Swipe { from, to, .. } => (from, Some(to)),
+            };
+
+            if let Some(end) = end {
+                let (from, to) = (to_screen(*point), to_screen(*end));
+                ui.painter()
+                    .arrow(from, to - from, Stroke::new(2.0, Color32::WHITE));
+
+                let marker = Marker::SwipeEnd(index);
+                let response = marker_ui(ui, id.with(marker), to, "", *selected == Some(marker));
+                select(&response, marker, selected, pending);
+                if response.dragged()
+                    && let Some(pos) = response.interact_pointer_pos()
+                {
+                    *end = to_video(pos);
+                }
+            }
+
+            let response = marker_ui(
+                ui,
+                id.with(marker),
+                to_screen(*point),
+                &label,
+                *selected == Some(marker),
+            );
+            select(&response, marker, selected, pending);
+            if response.dragged()
+                && let Some(pos) = response.interact_pointer_pos()
+            {
+                *point = to_video(pos);
+            }
+            response.context_menu(|ui| {
+                let point = *point;
+                for (name, action) in [
+                    ("Tap", BindingAction::Tap { point }),
+                    ("Hold", BindingAction::Hold { point }),
+                    (
+                        "Swipe",
+                        BindingAction::Swipe {
+                            from: point,
+                            to: pos2(point.x, (point.y - 0.2).max(0.0)),
+                            duration_ms: Self::SWIPE_DURATION_MS,
+                        },
+                    ),
+                ] {
+                    if ui.button(name).clicked() {
+                        edit = Some(Edit::Action(index, action));
+                    }
+                }
+                ui.separator();
+                if ui.button("Delete").clicked() {
+                    edit = Some(Edit::Remove(marker));
+                }
+            });
+        }
+
+        if let Some(point) = *pending {
+            let painter = ui.painter();
+            let center = to_screen(point);
+            painter.circle_stroke(
+                center,
+                Self::MARKER_SIZE / 2.0,
+                Stroke::new(2.0, Color32::YELLOW),
+            );
+            painter.text(
+                center + vec2(0.0, Self::MARKER_SIZE),
+                Align2::CENTER_CENTER,
+                "Press a key",
+                FontId::proportional(12.0),
+                Color32::YELLOW,
+            );
+        }
+
+        match edit {
+            Some(Edit::Action(index, action)) => draft.bindings[index].action = action,
+            Some(Edit::Directions(index, [up, down, left, right])) => {
+                let joystick = &mut draft.joysticks[index];
+                (joystick.up, joystick.down, joystick.left, joystick.right) =
+                    (up, down, left, right);
+            }
+            Some(Edit::Remove(Marker::Binding(index) | Marker::SwipeEnd(index))) => {
+                draft.bindings.remove(index);
+                *selected = None;
+            }
+            Some(Edit::Remove(Marker::Joystick(index))) => {
+                draft.joysticks.remove(index);
+                *selected = None;
+            }
+            None => {}
+        }
+
+        assign(ui, draft, selected, pending);
+    }
+}
+
+/// Assigns the pressed key to the selected or pending marker.
+fn assign(
+    ui: &Ui,
+    draft: &mut KeymapProfile,
+    selected: &mut Option<Marker>,
+    pending: &mut Option<Pos2>,
+) {
+    // Keys typed in the package name are not bindings.
+    if ui.ctx().wants_keyboard_input() {
+        return;
+    }
+
+    let key = ui.input(|i| {
+        i.events.iter().find_map(|event| match event {
+            Event::Key {
+                key,
+                pressed: true,
+                repeat: false,
+                ..
+            } => Some(*key),
+            _ => None,
+        })
+    });
+    let Some(key) = key else {
+        return;
+    };
+
+    if key == egui::Key::Escape {
+        *selected = None;
+        *pending = None;
+        return;
+    }
+
+    let trigger = Trigger::Key(key);
+    if let Some(point) = pending.take() {
+        draft.bindings.push(KeyBinding {
+            trigger,
+            action: BindingAction::Tap { point },
+        });
+        *selected = Some(Marker::Binding(draft.bindings.len() - 1));
+    } else if let Some(Marker::Binding(index) | Marker::SwipeEnd(index)) = *selected {
+        draft.bindings[index].trigger = trigger;
+    }
+}
+
+/// Selects a marker on click or drag.
+fn select(
+    response: &Response,
+    marker: Marker,
+    selected: &mut Option<Marker>,
+    pending: &mut Option<Pos2>,
+) {
+    if response.clicked() || response.drag_started() || response.secondary_clicked() {
+        *selected = Some(marker);
+        *pending = None;
+    }
+}
+
+/// Draws a marker and senses its clicks and drags.
+fn marker_ui(ui: &Ui, id: Id, center: Pos2, label: &str, is_selected: bool) -> Response {
+    let rect = Rect::from_center_size(center, Vec2::splat(KeymapEditor::MARKER_SIZE));
+    let response = ui.interact(rect, id, Sense::click_and_drag());
+
+    let stroke = if is_selected {
+        Stroke::new(2.0, Color32::YELLOW)
+    } else {
+        Stroke::new(1.5, Color32::WHITE)
+    };
+
+    let painter = ui.painter();
+    painter.circle(
+        center,
+        KeymapEditor::MARKER_SIZE / 2.0,
+        Color32::from_black_alpha(160),
+        stroke,
+    );
+    painter.text(
+        center,
+        Align2::CENTER_CENTER,
+        label,
+        FontId::proportional(11.0),
+        Color32::WHITE,
+    );
+
+    response
+}
+
+/// Joystick direction presets, in order: up, down, left, right.
+const DIRECTIONS: [(&str, [Trigger; 4]); 3] = [
+    (
+        "WASD",
+        [
+            Trigger::Key(egui::Key::W),
+            Trigger::Key(egui::Key::S),
+            Trigger::Key(egui::Key::A),
+            Trigger::Key(egui::Key::D),
+        ],
+    ),
+    (
+        "ZQSD",
+        [
+            Trigger::Key(egui::Key::Z),
+            Trigger::Key(egui::Key::S),
+            Trigger::Key(egui::Key::Q),
+            Trigger::Key(egui::Key::D),
+        ],
+    ),
+    (
+        "Arrows",
+        [
+            Trigger::Key(egui::Key::ArrowUp),
+            Trigger::Key(egui::Key::ArrowDown),
+            Trigger::Key(egui::Key::ArrowLeft),
+            Trigger::Key(egui::Key::ArrowRight),
+        ],
+    ),
+];
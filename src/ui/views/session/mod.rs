@@ -1,28 +1,63 @@
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use eframe::egui;
 use eframe::egui::{CentralPanel, Context, Id, RichText, TextWrapMode, ViewportId};
+use eframe::{egui, egui_wgpu};
 
 use crate::core::msg::Msg;
 use crate::ui::context::OwnedViewContext;
+use crate::ui::pages::settings::KeymapTarget;
 use crate::ui::perf::fps_counter::FrameStats;
+use crate::ui::perf::latency::PaintedFrame;
+use crate::ui::renderer::screen_mapping::ScreenMapping;
+use crate::ui::renderer::video_player::VideoPlayer;
 use crate::ui::views::session::state::SessionState;
+use crate::ui::views::session::toolbar::NavigationToolbar;
 use crate::utils::math::round_magnitude;
 
+mod input;
+mod keyboard;
+mod keymap;
+mod keymap_editor;
 pub mod state;
+mod toolbar;
 
 pub struct SessionViewport {
     state: Arc<parking_lot::RwLock<SessionState>>,
 }
 
 impl SessionViewport {
+    /// Saves the instant replay of the session
+    pub const REPLAY_KEY: egui::Key = egui::Key::F9;
+    /// Saves a screenshot of the displayed frame
+    pub const SCREENSHOT_KEY: egui::Key = egui::Key::F12;
+
     pub const fn new(state: Arc<parking_lot::RwLock<SessionState>>) -> Self {
         Self { state }
     }
 
+    /// Removes the presses of a session hotkey from the input, before they reach the device.
+    ///
+    /// Returns whether it was pressed.
+    pub fn take_hotkey(raw_input: &mut egui::RawInput, hotkey: egui::Key) -> bool {
+        let mut pressed = false;
+        raw_input.events.retain(|event| match event {
+            egui::Event::Key {
+                key,
+                pressed: down,
+                repeat,
+                ..
+            } if *key == hotkey => {
+                pressed |= *down && !*repeat;
+                false
+            }
+            _ => true,
+        });
+        pressed
+    }
+
     fn viewport_id(&self) -> ViewportId {
-        ViewportId::from_hash_of(self.state.read().session.device_id.as_str())
+        self.state.read().viewport_id()
     }
 
     /// Tick and return the current time
@@ -35,6 +70,7 @@ impl SessionViewport {
         #[allow(clippy::unchecked_duration_subtraction)]
         if state.counter_stats.0 <= now - INTERVAL {
             state.counter_stats = (now, state.counter.stats());
+            state.latency_stats = state.latency.lock().stats();
         }
         now
     }
@@ -62,7 +98,7 @@ impl SessionViewport {
                     return;
                 }
 
-                let _now = self.tick();
+                let now = self.tick();
                 let counter_stats = self.stats();
 
                 let state = &mut *self.state.write();
@@ -86,29 +122,244 @@ impl SessionViewport {
                             )
                             .wrap_mode(TextWrapMode::Extend),
                         );
-                    });
 
-                let frame = state.session.shared_frame.write().take();
-                if let (Some(player), Some(mut frame)) =
-                    (state.player_mut(ctx.state.clone()), frame)
-                {
-                    match frame.download_to_cpu() {
-                        Ok(()) => player.update(&frame),
-                        Err(err) => {
-                            error!("Ffmpeg: {err:?}");
+                        if let Some(path) = state.player().and_then(VideoPlayer::path) {
+                            let stats = &state.session.decoder_stats;
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(format!(
+                                        "VIDEO: {path} FRAMES: {} ERRORS: {}",
+                                        stats.frames(),
+                                        stats.errors(),
+                                    ))
+                                    .monospace()
+                                    .small(),
+                                )
+                                .wrap_mode(TextWrapMode::Extend),
+                            );
+                        }
+
+                        if state.session.video.is_some() {
+                            let frames = &state.session.frames;
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(format!(
+                                        "QUEUE: {:?} DEPTH: {} DROPS: {}",
+                                        frames.mode(),
+                                        frames.depth(),
+                                        frames.dropped(),
+                                    ))
+                                    .monospace()
+                                    .small(),
+                                )
+                                .wrap_mode(TextWrapMode::Extend),
+                            );
+                        }
+
+                        if state.session.video.is_some() {
+                            let latency = &state.latency_stats;
+                            for (stage, stats) in [
+                                ("NETWORK", latency.network),
+                                ("DECODE", latency.decode),
+                                ("UPLOAD", latency.upload),
+                                ("PRESENT", latency.present),
+                                ("TOTAL", latency.total),
+                            ] {
+                                ui.add(
+                                    egui::Label::new(
+                                        RichText::new(format!(
+                                            "{stage:<7} P50: {:.2} P90: {:.2} P99: {:.2} ms",
+                                            round_magnitude(stats.p50.as_secs_f64(), -3, 2),
+                                            round_magnitude(stats.p90.as_secs_f64(), -3, 2),
+                                            round_magnitude(stats.p99.as_secs_f64(), -3, 2),
+                                        ))
+                                        .monospace()
+                                        .small(),
+                                    )
+                                    .wrap_mode(TextWrapMode::Extend),
+                                );
+                            }
+                        }
+
+                        if state.session.audio.is_some() {
+                            let stats = &state.session.audio_stats;
+                            // Positive when the audio is ahead of the video.
+                            let offset = stats.pts().zip(state.video_pts).map_or_else(
+                                || "-".to_owned(),
+                                |(audio, video)| format!("{:+}", (audio - video) / 1000),
+                            );
+
+                            ui.add(
+                                egui::Label::new(
+                                    RichText::new(format!(
+                                        "AUDIO: {} ms UNDERRUNS: {} A/V: {offset} ms",
+                                        stats.fill().as_millis(),
+                                        stats.underruns(),
+                                    ))
+                                    .monospace()
+                                    .small(),
+                                )
+                                .wrap_mode(TextWrapMode::Extend),
+                            );
                         }
-                    }
+                    });
+
+                let control = state.session.control.is_some();
+                let toolbar_visible = control
+                    && state
+                        .preferences
+                        .as_ref()
+                        .is_some_and(|preferences| preferences.toolbar_visible);
+
+                if control {
+                    egui::SidePanel::right("navigation_toolbar")
+                        .resizable(false)
+                        .exact_width(NavigationToolbar::WIDTH)
+                        .show_animated(context, toolbar_visible, |ui| {
+                            let mut messages = Vec::new();
+                            NavigationToolbar::show(ui, &mut state.orientation, &mut messages);
+                            for msg in messages {
+                                state.session.send_control(msg);
+                            }
+                        });
+                }
+
+                let offset = if toolbar_visible {
+                    NavigationToolbar::WIDTH + 5.0
+                } else {
+                    5.0
+                };
+
+                egui::Area::new(Id::new("session_toolbar"))
+                    .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-offset, 5.0))
+                    .order(egui::Order::Foreground)
+                    .show(context, |ui| {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            if state.keymap_editor.is_open() {
+                                state.keymap_editor.toolbar(ui, &state.session.device_id);
+                                return;
+                            }
+
+                            ui.horizontal(|ui| {
+                                let mut recording = state.session.is_recording();
+                                if ui
+                                    .toggle_value(&mut recording, "Record")
+                                    .on_hover_text("Record the session in the recordings directory")
+                                    .changed()
+                                {
+                                    if recording {
+                                        state.session.start_recording();
+                                    } else {
+                                        state.session.stop_recording();
+                                    }
+                                }
+
+                                if ui
+                                    .button("Save replay")
+                                    .on_hover_text("Save the last seconds of the session (F9)")
+                                    .clicked()
+                                {
+                                    state.session.save_replay();
+                                }
+
+                                if ui
+                                    .button("Screenshot")
+                                    .on_hover_text("Save the displayed frame as a PNG image (F12)")
+                                    .clicked()
+                                    && let Some(msg) = state.screenshot()
+                                {
+                                    ctx.send(msg);
+                                }
+
+                                if !control {
+                                    return;
+                                }
+
+                                if let Some(preferences) = &mut state.preferences {
+                                    ui.toggle_value(&mut preferences.toolbar_visible, "Toolbar");
+                                }
+
+                                if let Some(target) = keymap_target_ui(ui, state) {
+                                    // Touches held by the previous profile are released.
+                                    let mut messages = Vec::new();
+                                    state.keymap.release_all(&mut messages);
+                                    for msg in messages {
+                                        state.session.send_control(msg);
+                                    }
+
+                                    state.keymap_target = target;
+                                }
+
+                                if ui.button("Edit keymap").clicked() {
+                                    // Nothing stays held on the device while editing.
+                                    let mut messages = Vec::new();
+                                    state.pointer.release(&mut messages);
+                                    state.keyboard.release_all(&mut messages);
+                                    state.keymap.release_all(&mut messages);
+                                    for msg in messages {
+                                        state.session.send_control(msg);
+                                    }
+
+                                    state.keymap_editor.open();
+                                    ui.ctx().request_repaint();
+                                }
+                            });
+                        });
+                    });
+
+                let frame = state.session.frames.pop(now);
+                // Frames paced or queued are displayed on the next repaints.
+                if let Some(delay) = state.session.frames.next_due(now) {
+                    context.request_repaint_after(delay);
+                }
+                // Frame uploaded this ui frame, and its upload instant
+                let mut uploaded = None;
+                if let Some((mut frame, timing)) = frame {
+                    state.video_pts = frame.pts();
+                    uploaded = state
+                        .player_mut(ctx.state.clone())
+                        .and_then(|player| player.update(&mut frame))
+                        .map(|uploaded| (timing, uploaded));
+                    // Kept at the device resolution for screenshots
+                    state.last_frame = Some(frame.into());
                 }
 
                 CentralPanel::default()
                     .frame(egui::Frame::new().fill(egui::Color32::BLACK))
                     .show(context, |ui| {
-                        if let Some(player) = &state.player_mut(ctx.state.clone()) {
-                            player.ui(ui);
+                        let orientation = state.orientation;
+                        let video = if let Some(player) = &state.player_mut(ctx.state.clone()) {
+                            player.ui(ui, orientation, ScreenMapping::FULL)
                         } else {
                             ui.centered_and_justified(|ui| {
                                 ui.spinner();
                             });
+                            None
+                        };
+
+                        state.mapping = video.as_ref().map(|(_, mapping)| *mapping);
+
+                        // The uploaded frame is recorded once this ui frame is painted.
+                        if let (Some((timing, uploaded)), Some((response, _))) = (uploaded, &video)
+                        {
+                            ui.painter().add(egui_wgpu::Callback::new_paint_callback(
+                                response.rect,
+                                PaintedFrame::new(state.latency.clone(), timing, uploaded),
+                            ));
+                        }
+
+                        if state.keymap_editor.is_open() {
+                            if let Some((response, mapping)) = &video {
+                                state.keymap_editor.ui(ui, response, mapping);
+                            }
+                        } else if state.session.control.is_some()
+                            && let Some((_response, mapping)) = video
+                        {
+                            let mut messages = Vec::new();
+                            ui.input(|i| state.pointer.handle(&i.events, &mapping, &mut messages));
+                            for msg in messages {
+                                state.session.send_control(msg);
+                            }
                         }
 
                         if ui.input(|i| i.viewport().close_requested()) {
@@ -119,3 +370,42 @@ impl SessionViewport {
         );
     }
 }
+
+/// Keymap profile selection, device wide or of the foreground application.
+///
+/// Returns the newly selected target, if any.
+fn keymap_target_ui(ui: &mut egui::Ui, state: &SessionState) -> Option<KeymapTarget> {
+    let device = KeymapTarget::Device(state.session.device_id.clone());
+    let selected_text = match &state.keymap_target {
+        KeymapTarget::Device(_) => "Device keymap",
+        KeymapTarget::Package(package) => package.as_str(),
+    };
+
+    let mut selected = None;
+    egui::ComboBox::from_id_salt("keymap_target")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            let targets = std::iter::once(device).chain(
+                state
+                    .keymap_packages
+                    .iter()
+                    .map(|package| KeymapTarget::Package(package.clone())),
+            );
+            for target in targets {
+                let label = match &target {
+                    KeymapTarget::Device(_) => "Device keymap",
+                    KeymapTarget::Package(package) => package.as_str(),
+                };
+                if ui
+                    .selectable_label(state.keymap_target == target, label)
+                    .clicked()
+                {
+                    selected = Some(target);
+                }
+            }
+        })
+        .response
+        .on_hover_text("Keymap profile applied to the session, pick the application on screen");
+
+    selected.filter(|target| *target != state.keymap_target)
+}
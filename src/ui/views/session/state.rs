@@ -1,35 +1,177 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use eframe::egui::ViewportId;
 use eframe::egui_wgpu::RenderState;
+use ffmpeg_next::frame;
 
+use crate::core::msg::Msg;
 use crate::services::session::SessionHandle;
+use crate::ui::pages::settings::{KeymapTarget, Keymaps};
 use crate::ui::perf::fps_counter::{FpsCounter, FrameStats};
+use crate::ui::perf::latency::{FrameLatency, FrameLatencyStats, SharedFrameLatency};
+use crate::ui::renderer::screen_mapping::{Orientation, ScreenMapping};
 use crate::ui::renderer::video_player::VideoPlayer;
+use crate::ui::views::session::input::PointerInput;
+use crate::ui::views::session::keyboard::KeyboardInput;
+use crate::ui::views::session::keymap::KeymapInput;
+use crate::ui::views::session::keymap_editor::KeymapEditor;
 
 pub type SharedSessionState = Arc<parking_lot::RwLock<SessionState>>;
 
+/// Session viewport preferences, persisted per device
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct DevicePreferences {
+    pub toolbar_visible: bool,
+    /// Keymap profile applied to the sessions. `None` for the device profile.
+    pub keymap_target: Option<KeymapTarget>,
+}
+
+impl Default for DevicePreferences {
+    fn default() -> Self {
+        Self {
+            toolbar_visible: true,
+            keymap_target: None,
+        }
+    }
+}
+
 /// Session viewport state
 pub struct SessionState {
     pub session: SessionHandle,
     player: Option<VideoPlayer>,
+    /// Display rotation
+    pub orientation: Orientation,
+    /// Viewport to device mapping of the last frame. `None` until the video is displayed.
+    pub mapping: Option<ScreenMapping>,
+    pub pointer: PointerInput,
+    pub keyboard: KeyboardInput,
+    /// Keymap profile applied to the session
+    pub keymap_target: KeymapTarget,
+    /// Packages with a keymap profile, selectable from the toolbar
+    pub keymap_packages: Vec<String>,
+    pub keymap: KeymapInput,
+    pub keymap_editor: KeymapEditor,
+    /// Device preferences. `None` until loaded from the persisted data.
+    pub preferences: Option<DevicePreferences>,
+    /// Stream timestamp of the displayed video frame, in microseconds
+    pub video_pts: Option<i64>,
+    /// Displayed video frame, in host or GPU memory
+    pub last_frame: Option<frame::Video>,
     pub counter: FpsCounter,
     pub counter_stats: (Instant, FrameStats),
+    pub latency: SharedFrameLatency,
+    pub latency_stats: FrameLatencyStats,
 }
 
 impl SessionState {
     pub fn new(session: SessionHandle) -> Self {
+        let keymap_target = KeymapTarget::Device(session.device_id.clone());
+
         Self {
             session,
             player: None,
+            orientation: Orientation::default(),
+            mapping: None,
+            pointer: PointerInput::default(),
+            keyboard: KeyboardInput::default(),
+            keymap_target,
+            keymap_packages: Vec::new(),
+            keymap: KeymapInput::default(),
+            keymap_editor: KeymapEditor::default(),
+            preferences: None,
+            video_pts: None,
+            last_frame: None,
             counter: FpsCounter::new(120, Duration::from_secs(1)),
             counter_stats: (Instant::now(), FrameStats::default()),
+            latency: Arc::new(parking_lot::Mutex::new(FrameLatency::new(
+                120,
+                Duration::from_secs(2),
+            ))),
+            latency_stats: FrameLatencyStats::default(),
         }
     }
 
+    /// Session viewport identifier
+    pub fn viewport_id(&self) -> ViewportId {
+        ViewportId::from_hash_of(self.session.device_id.as_str())
+    }
+
+    /// Loads the device preferences on the first call, then persists their changes.
+    ///
+    /// # Arguments
+    ///
+    /// - `persisted`: Persisted preferences, by device serial.
+    pub fn sync_preferences(&mut self, persisted: &mut HashMap<String, DevicePreferences>) {
+        let device_id = &self.session.device_id;
+
+        match &mut self.preferences {
+            Some(preferences) => {
+                // The keymap target is changed from the toolbar and the keymap editor.
+                let keymap_target = match &self.keymap_target {
+                    KeymapTarget::Device(_) => None,
+                    target @ KeymapTarget::Package(_) => Some(target),
+                };
+                if preferences.keymap_target.as_ref() != keymap_target {
+                    preferences.keymap_target = keymap_target.cloned();
+                }
+
+                if persisted.get(device_id) != Some(&*preferences) {
+                    persisted.insert(device_id.clone(), preferences.clone());
+                }
+            }
+            None => {
+                let preferences = persisted.get(device_id).cloned().unwrap_or_default();
+                if let Some(target) = &preferences.keymap_target {
+                    self.keymap_target.clone_from(target);
+                }
+                self.preferences = Some(preferences);
+            }
+        }
+    }
+
+    /// Refreshes the packages with a keymap profile.
+    ///
+    /// # Arguments
+    ///
+    /// - `keymaps`: Persisted keymaps.
+    pub fn sync_keymaps(&mut self, keymaps: &Keymaps) {
+        let packages = keymaps
+            .profiles
+            .iter()
+            .filter_map(|profile| match &profile.target {
+                KeymapTarget::Package(package) => Some(package),
+                KeymapTarget::Device(_) => None,
+            });
+        if !packages.clone().eq(self.keymap_packages.iter()) {
+            self.keymap_packages = packages.cloned().collect();
+        }
+    }
+
+    /// Screenshot request of the displayed frame. `None` until a frame is displayed.
+    pub fn screenshot(&self) -> Option<Msg> {
+        let frame = self.last_frame.clone()?;
+
+        Some(Msg::RequestScreenshot {
+            device_id: self.session.device_id.clone(),
+            config: self.session.screenshot.clone(),
+            frame,
+        })
+    }
+
+    /// Video player, once the first frame is displayed
+    pub const fn player(&self) -> Option<&VideoPlayer> {
+        self.player.as_ref()
+    }
+
     pub fn player_mut(&mut self, state: RenderState) -> Option<&mut VideoPlayer> {
-        match (self.session.video.is_some(), self.player.is_some()) {
-            (true, false) => Some(self.player.insert(VideoPlayer::new(state))),
+        match (&self.session.video, self.player.is_some()) {
+            (Some(video), false) => Some(
+                self.player
+                    .insert(VideoPlayer::new(state, video.tone_mapping)),
+            ),
             (_, _) => self.player.as_mut(),
         }
     }
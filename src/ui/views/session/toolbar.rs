@@ -0,0 +1,88 @@
+//! Device navigation toolbar
+
+use eframe::egui::{Button, Ui, vec2};
+
+use crate::services::control::{ControlMessage, KeyAction, Keycode};
+use crate::ui::renderer::screen_mapping::Orientation;
+
+/// Device navigation buttons, sending the matching control messages.
+pub struct NavigationToolbar;
+
+impl NavigationToolbar {
+    /// Toolbar width in points.
+    pub const WIDTH: f32 = 96.0;
+
+    /// Shows the navigation buttons.
+    ///
+    /// # Arguments
+    ///
+    /// - `ui`: Toolbar panel.
+    /// - `orientation`: Display rotation, rotated by the view buttons.
+    /// - `out`: Produced control messages.
+    pub fn show(ui: &mut Ui, orientation: &mut Orientation, out: &mut Vec<ControlMessage>) {
+        let size = vec2(ui.available_width(), 28.0);
+        let button = |ui: &mut Ui, label: &str, hover: &str| {
+            ui.add_sized(size, Button::new(label))
+                .on_hover_text(hover)
+                .clicked()
+        };
+
+        ui.vertical_centered_justified(|ui| {
+            if button(ui, "Back", "Back, or turn the screen on") {
+                out.extend(
+                    [KeyAction::Down, KeyAction::Up]
+                        .map(|action| ControlMessage::BackOrScreenOn { action }),
+                );
+            }
+            if button(ui, "Home", "Home") {
+                out.extend(ControlMessage::key_tap(Keycode::Home));
+            }
+            if button(ui, "Apps", "App switch") {
+                out.extend(ControlMessage::key_tap(Keycode::AppSwitch));
+            }
+
+            ui.separator();
+
+            if button(ui, "Vol +", "Volume up") {
+                out.extend(ControlMessage::key_tap(Keycode::VolumeUp));
+            }
+            if button(ui, "Vol -", "Volume down") {
+                out.extend(ControlMessage::key_tap(Keycode::VolumeDown));
+            }
+            if button(ui, "Rotate", "Rotate the device") {
+                out.push(ControlMessage::RotateDevice);
+            }
+            if button(ui, "View ⟲", "Rotate the view counterclockwise") {
+                *orientation = orientation.counterclockwise();
+            }
+            if button(ui, "View ⟳", "Rotate the view clockwise") {
+                *orientation = orientation.clockwise();
+            }
+
+            ui.separator();
+
+            if button(ui, "Notifs", "Expand the notification panel") {
+                out.push(ControlMessage::ExpandNotificationPanel);
+            }
+            if button(ui, "Quick", "Expand the quick settings panel") {
+                out.push(ControlMessage::ExpandSettingsPanel);
+            }
+            if button(ui, "Collapse", "Collapse the panels") {
+                out.push(ControlMessage::CollapsePanels);
+            }
+
+            ui.separator();
+
+            if button(ui, "Power", "Power") {
+                out.extend(ControlMessage::key_tap(Keycode::Power));
+            }
+            if button(
+                ui,
+                "Screen off",
+                "Turn the device screen off, mirroring continues",
+            ) {
+                out.push(ControlMessage::SetDisplayPower { on: false });
+            }
+        });
+    }
+}